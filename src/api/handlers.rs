@@ -10,11 +10,26 @@ use futures::StreamExt;
 use serde::{Deserialize, Serialize};
 use tracing::{error, info, warn};
 
+use crate::audio::analysis;
+use crate::audio::format::{FormatConverter, OutputFormat, OutputFormatQuery, SOURCE_SAMPLE_RATE};
+use crate::audio::frame::{AudioFrame, Payload, FLAG_DISCONTINUITY};
+use crate::audio::meter::{FrameLevels, LevelAccumulator};
+use crate::audio::opus::{OpusQuery, OpusSettings, StreamEncoding};
 use crate::audio::player::{EqBandParam, Player};
+use crate::audio::playlist::{load_playlist, Playlist};
+use crate::audio::recorder::{RecordingFormat, RecordingState};
+use crate::audio::silence::SilenceUpdate;
+use crate::audio::sink::RtpSinkReq;
+use crate::audio::spectrum::{
+    SpectrumAnalyzer, SpectrumMessage, SpectrumQuery, SpectrumSettings, SEND_INTERVAL,
+};
+use crate::audio::stream::HttpStreamFormat;
+use crate::audio::subscriber::Subscription;
 use crate::audio::track::LoopMode;
 use crate::config::{resolver_enabled, EffectiveConfig};
-use crate::resolver::{is_uri_allowed, needs_resolve, resolve_to_direct, resolve_with_retry};
+use crate::resolver::{is_uri_allowed, LoadedItem, TrackMeta};
 use crate::state::AppState;
+use crate::voice::VoiceServerInfo;
 use axum::extract::Query;
 use base64::Engine;
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -44,30 +59,22 @@ pub async fn create_player(
     State(state): State<AppState>,
     Json(req): Json<CreatePlayerReq>,
 ) -> Result<impl IntoResponse, StatusCode> {
+    let req = CreatePlayerReq { uri: resolve_search(&state, &req.uri).await?, ..req };
+    require_source(&state, &req.uri)?;
     if state.players.contains_key(&req.id) {
-        let p = state.players.get(&req.id).ok_or(StatusCode::NOT_FOUND)?;
+        let p = state.players.get(&req.id).map(|p| p.clone()).ok_or(StatusCode::NOT_FOUND)?;
         if !is_uri_allowed(&state.cfg, &req.uri) {
             warn!(uri=%req.uri, "URI blocked by config patterns");
             return Err(StatusCode::FORBIDDEN);
         }
-        let mut uri = req.uri.clone();
-        let mut prepared_path: Option<String> = None;
-        if (needs_resolve(&uri) && resolver_enabled(&state.cfg)) || resolver_enabled(&state.cfg) {
-            match resolve_with_retry(&state.cfg, &uri).await {
-                Ok(direct) => {
-                    info!(%uri, %direct, "resolved page URL to direct stream");
-                    if std::path::Path::new(&direct).exists() {
-                        prepared_path = Some(direct.clone());
-                    }
-                    uri = direct;
-                }
-                Err(e) => {
-                    warn!(%uri, ?e, "resolver failed; enqueuing original URI");
-                }
-            }
-        }
         let md = req.metadata.unwrap_or_else(|| serde_json::json!({}));
-        let _track_id = p.enqueue_prepared(uri.clone(), prepared_path, md).await;
+        if let Some(playlist) = expand_playlist(&state, &req.uri).await? {
+            p.enqueue_all(playlist.entries.into_iter().map(|e| (e.uri, md.clone())).collect()).await;
+            return Ok((StatusCode::OK, Json(CreatePlayerRes { id: req.id })));
+        }
+        let uri = resolve_for_playback(&state, &req.uri).await;
+        let prepared_path = (uri != req.uri && std::path::Path::new(&uri).exists()).then(|| uri.clone());
+        let _track_id = p.enqueue_prepared(uri, prepared_path, md, None).await;
         return Ok((StatusCode::OK, Json(CreatePlayerRes { id: req.id })));
     }
 
@@ -92,24 +99,23 @@ pub async fn create_player(
         }
     }
 
-    let mut uri = req.uri.clone();
-    if (needs_resolve(&uri) && resolver_enabled(&state.cfg)) || resolver_enabled(&state.cfg) {
-        match resolve_with_retry(&state.cfg, &uri).await {
-            Ok(direct) => {
-                info!(%uri, %direct, "resolved page URL to direct stream");
-                uri = direct;
-            }
-            Err(e) => {
-                warn!(%uri, ?e, "resolver failed; using original URI");
-            }
-        }
-    }
+    // A playlist starts the player on its first entry and queues the rest.
+    let mut playlist = expand_playlist(&state, &req.uri).await?;
+    let uri = match playlist.as_mut() {
+        Some(pl) => pl.entries.remove(0).uri,
+        None => resolve_for_playback(&state, &req.uri).await,
+    };
 
-    let player = Player::new(&req.id, &uri, state.cfg.clone()).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let player = Player::new(&req.id, &uri, state.cfg.clone(), state.sources.clone())
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
     let player = std::sync::Arc::new(player);
-    if let Some(md) = req.metadata {
+    if let Some(md) = req.metadata.clone() {
         player.set_metadata(md).await;
     }
+    if let Some(pl) = playlist.filter(|pl| !pl.entries.is_empty()) {
+        let md = req.metadata.unwrap_or_else(|| serde_json::json!({}));
+        player.enqueue_all(pl.entries.into_iter().map(|e| (e.uri, md.clone())).collect()).await;
+    }
     state.players.insert(req.id.clone(), player.clone());
 
     tokio::spawn(async move {
@@ -165,6 +171,28 @@ pub async fn update_filters(
     Ok(StatusCode::NO_CONTENT)
 }
 
+pub async fn get_silence(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let p = state.players.get(&id).map(|p| p.clone()).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(p.silence_options().await))
+}
+
+pub async fn update_silence(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(req): Json<SilenceUpdate>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let p = state
+        .players
+        .get(&id)
+        .map(|p| p.clone())
+        .ok_or((StatusCode::NOT_FOUND, "player not found".to_string()))?;
+    let opts = p.update_silence(&req).await.map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    Ok(Json(opts))
+}
+
 #[derive(Debug, Serialize)]
 pub struct TrackInfoOut {
     pub identifier: String,
@@ -200,8 +228,12 @@ pub async fn list_players(State(state): State<AppState>) -> impl IntoResponse {
     for p in state.players.iter() {
         let md = p.metadata().await;
         let ti = p.track_info_snapshot().await;
+        let voice = p.voice_status().await;
         out.push(serde_json::json!({
             "id": p.id().to_string(),
+            "voice": voice,
+            "subscribers": p.subscriber_stats(),
+            "pacing": p.pacing_stats(),
             "track": TrackOut {
                 encoded: engine.encode(p.track_identifier()),
                 info: TrackInfoOut {
@@ -217,7 +249,16 @@ pub async fn list_players(State(state): State<AppState>) -> impl IntoResponse {
                     isrc: ti.isrc,
                     source_name: ti.source_name,
                 },
-                plugin_info: serde_json::json!({}),
+                plugin_info: {
+                    let mut info = serde_json::Map::new();
+                    if let Some(album) = ti.album {
+                        info.insert("album".into(), album.into());
+                    }
+                    if !ti.chapters.is_empty() {
+                        info.insert("chapters".into(), serde_json::json!(ti.chapters));
+                    }
+                    serde_json::Value::Object(info)
+                },
                 user_data: md,
             }
         }));
@@ -225,6 +266,28 @@ pub async fn list_players(State(state): State<AppState>) -> impl IntoResponse {
     Json(out)
 }
 
+pub async fn get_lyrics(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let p = state.players.get(&id).map(|p| p.clone()).ok_or(StatusCode::NOT_FOUND)?;
+    let lyrics = p.lyrics().await.ok_or(StatusCode::NOT_FOUND)?;
+    let position = p.track_info_snapshot().await.position_ms;
+    let mut out = serde_json::to_value(&*lyrics).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    out["currentLine"] = serde_json::json!(lyrics.line_at(position));
+    Ok(Json(out))
+}
+
+pub async fn get_artwork(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    use axum::http::header;
+    let p = state.players.get(&id).map(|p| p.clone()).ok_or(StatusCode::NOT_FOUND)?;
+    let art = p.artwork().await.ok_or(StatusCode::NOT_FOUND)?;
+    Ok(([(header::CONTENT_TYPE, art.mime), (header::CACHE_CONTROL, "no-cache".to_string())], art.data))
+}
+
 #[derive(Debug, Deserialize)]
 pub struct MetadataUpdateReq {
     pub merge: bool,
@@ -250,6 +313,9 @@ pub struct EnqueueReq {
     pub uri: String,
     #[serde(default)]
     pub metadata: Option<serde_json::Value>,
+    /// LRC (or plain) lyrics for the track, overriding sidecar files and tags.
+    #[serde(default)]
+    pub lyrics: Option<String>,
 }
 
 pub async fn enqueue(
@@ -257,29 +323,105 @@ pub async fn enqueue(
     Path(id): Path<String>,
     Json(req): Json<EnqueueReq>,
 ) -> Result<impl IntoResponse, StatusCode> {
-    let p = state.players.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+    let p = state.players.get(&id).map(|p| p.clone()).ok_or(StatusCode::NOT_FOUND)?;
+    let req = EnqueueReq { uri: resolve_search(&state, &req.uri).await?, ..req };
+    require_source(&state, &req.uri)?;
     if !is_uri_allowed(&state.cfg, &req.uri) {
         return Err(StatusCode::FORBIDDEN);
     }
     let md = req.metadata.unwrap_or_else(|| serde_json::json!({}));
-    let mut uri = req.uri.clone();
-    let mut prepared_path: Option<String> = None;
-    if (needs_resolve(&uri) && resolver_enabled(&state.cfg)) || resolver_enabled(&state.cfg) {
-        match resolve_with_retry(&state.cfg, &uri).await {
-            Ok(direct) => {
-                info!(original=%req.uri, %direct, "resolved queue URL to direct stream");
-                if std::path::Path::new(&direct).exists() {
-                    prepared_path = Some(direct.clone());
-                }
-                uri = direct;
-            }
-            Err(e) => {
-                warn!(uri=%req.uri, ?e, "resolver failed; enqueued original URI");
+    if let Some(playlist) = expand_playlist(&state, &req.uri).await? {
+        let items = playlist.entries.into_iter().map(|e| (e.uri, md.clone())).collect();
+        let track_ids = p.enqueue_all(items).await;
+        return Ok((
+            StatusCode::CREATED,
+            Json(
+                serde_json::json!({"trackId": track_ids[0], "trackIds": track_ids, "playlist": playlist.name}),
+            ),
+        ));
+    }
+    let uri = resolve_for_playback(&state, &req.uri).await;
+    let prepared_path = (uri != req.uri && std::path::Path::new(&uri).exists()).then(|| uri.clone());
+    let track_id = p.enqueue_prepared(uri, prepared_path, md, req.lyrics).await;
+    Ok((StatusCode::CREATED, Json(serde_json::json!({"trackId": track_id}))))
+}
+
+/// Replaces a search query (`ytsearch:`, `localsearch:`, ...) with the uri of its
+/// best match.
+async fn resolve_search(state: &AppState, uri: &str) -> Result<String, StatusCode> {
+    if !state.sources.is_search(uri) {
+        return Ok(uri.to_string());
+    }
+    match state.sources.load(uri).await {
+        Ok(LoadedItem::Search(tracks)) if !tracks.is_empty() => {
+            info!(query=%uri, uri=%tracks[0].uri, "search matched");
+            Ok(tracks[0].uri.clone())
+        }
+        Ok(_) => {
+            warn!(query=%uri, "search found nothing");
+            Err(StatusCode::BAD_REQUEST)
+        }
+        Err(e) => {
+            warn!(query=%uri, error=%e, "search failed");
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
+/// Rejects identifiers no enabled source handles, so that sources left out of
+/// `[sources] enabled` cannot be played.
+fn require_source(state: &AppState, uri: &str) -> Result<(), StatusCode> {
+    if state.sources.find(uri).is_none() {
+        warn!(%uri, "no enabled source handles URI");
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    Ok(())
+}
+
+/// Resolves `uri` through the enabled sources into something the decoder can open,
+/// falling back to `uri` itself when that fails.
+async fn resolve_for_playback(state: &AppState, uri: &str) -> String {
+    match state.sources.resolve(uri).await {
+        Ok(direct) => {
+            if direct != uri {
+                info!(%uri, %direct, "resolved page URL to direct stream");
             }
+            direct
+        }
+        Err(e) => {
+            warn!(%uri, ?e, "resolver failed; using original URI");
+            uri.to_string()
         }
     }
-    let track_id = p.enqueue_prepared(uri, prepared_path, md).await;
-    Ok((StatusCode::CREATED, Json(serde_json::json!({"trackId": track_id}))))
+}
+
+/// Loads `uri` when it names an M3U, PLS or XSPF playlist. Entries blocked by the
+/// allow/block patterns are dropped; a playlist with nothing left is rejected.
+async fn expand_playlist(state: &AppState, uri: &str) -> Result<Option<Playlist>, StatusCode> {
+    let mut playlist = match load_playlist(uri).await {
+        Ok(Some(pl)) => pl,
+        Ok(None) => return Ok(None),
+        Err(e) => {
+            warn!(%uri, ?e, "failed to load playlist");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    };
+    let total = playlist.entries.len();
+    // A playlist fetched over HTTP must not reach local files.
+    let remote = url::Url::parse(uri).is_ok_and(|u| matches!(u.scheme(), "http" | "https"));
+    playlist.entries.retain(|e| {
+        is_uri_allowed(&state.cfg, &e.uri)
+            && state.sources.find(&e.uri).is_some_and(|s| !remote || s.name() != "local")
+    });
+    if playlist.entries.len() < total {
+        warn!(%uri, blocked = total - playlist.entries.len(), "playlist entries blocked by config patterns or disabled sources");
+    }
+    if playlist.entries.is_empty() {
+        warn!(%uri, "playlist has no playable entries");
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    info!(%uri, name = %playlist.name, tracks = playlist.entries.len(), "expanded playlist");
+    Ok(Some(playlist))
 }
 
 pub async fn get_queue(
@@ -315,6 +457,249 @@ pub async fn skip(
     Ok(StatusCode::NO_CONTENT)
 }
 
+/// Jumps to the next chapter of the current track and returns it.
+pub async fn next_chapter(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let p = state.players.get(&id).map(|p| p.clone()).ok_or(StatusCode::NOT_FOUND)?;
+    let chapter = p.next_chapter().await.ok_or(StatusCode::CONFLICT)?;
+    Ok(Json(chapter))
+}
+
+pub async fn update_voice(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(req): Json<VoiceServerInfo>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    if !state.cfg.voice_enabled {
+        return Err((StatusCode::BAD_REQUEST, "voice subsystem disabled".to_string()));
+    }
+    let p = state
+        .players
+        .get(&id)
+        .map(|p| p.clone())
+        .ok_or((StatusCode::NOT_FOUND, "player not found".to_string()))?;
+    p.connect_voice(req).await.map_err(|e| {
+        error!(?e, "failed to start voice connection");
+        (StatusCode::INTERNAL_SERVER_ERROR, "failed to start voice connection".to_string())
+    })?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn delete_voice(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let p = state.players.get(&id).map(|p| p.clone()).ok_or(StatusCode::NOT_FOUND)?;
+    if p.disconnect_voice().await {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
+
+pub async fn create_sink(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(req): Json<RtpSinkReq>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let p = state
+        .players
+        .get(&id)
+        .map(|p| p.clone())
+        .ok_or((StatusCode::NOT_FOUND, "player not found".to_string()))?;
+    let info = p.add_sink(req).await.map_err(|e| (StatusCode::BAD_REQUEST, format!("{e:#}")))?;
+    Ok((StatusCode::CREATED, Json(info)))
+}
+
+pub async fn list_sinks(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let p = state.players.get(&id).map(|p| p.clone()).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(p.sinks().await))
+}
+
+pub async fn delete_sink(
+    State(state): State<AppState>,
+    Path((id, sink_id)): Path<(String, String)>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let p = state.players.get(&id).map(|p| p.clone()).ok_or(StatusCode::NOT_FOUND)?;
+    if p.remove_sink(&sink_id).await {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StartRecordingReq {
+    pub format: RecordingFormat,
+}
+
+pub async fn start_recording(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(req): Json<StartRecordingReq>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let p = state.players.get(&id).map(|p| p.clone()).ok_or(StatusCode::NOT_FOUND)?;
+    let info =
+        state.recordings.start(&state.cfg.ffmpeg_path, p.id(), req.format, p.subscribe()).await.map_err(
+            |e| {
+                error!(?e, "failed to start recording");
+                StatusCode::INTERNAL_SERVER_ERROR
+            },
+        )?;
+    Ok((StatusCode::CREATED, Json(info)))
+}
+
+pub async fn list_recordings(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.recordings.list())
+}
+
+pub async fn stop_recording(
+    State(state): State<AppState>,
+    Path(rec_id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let rec = state.recordings.get(&rec_id).ok_or(StatusCode::NOT_FOUND)?;
+    rec.stop();
+    Ok(StatusCode::ACCEPTED)
+}
+
+pub async fn download_recording(
+    State(state): State<AppState>,
+    Path(rec_id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    use axum::http::header;
+    let rec = state.recordings.get(&rec_id).ok_or(StatusCode::NOT_FOUND)?;
+    let info = rec.info();
+    if info.state != RecordingState::Finished {
+        return Err(StatusCode::CONFLICT);
+    }
+    let file = tokio::fs::File::open(&rec.path).await.map_err(|_| StatusCode::NOT_FOUND)?;
+    let disposition = format!("attachment; filename=\"{}.{}\"", info.id, info.format.extension());
+    Ok((
+        [
+            (header::CONTENT_TYPE, info.format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        axum::body::Body::from_stream(tokio_util::io::ReaderStream::new(file)),
+    ))
+}
+
+pub async fn delete_recording(
+    State(state): State<AppState>,
+    Path(rec_id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    if state.recordings.remove(&rec_id).await {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LevelsQuery {
+    pub interval_ms: Option<u64>,
+}
+
+pub async fn ws_levels(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(q): Query<LevelsQuery>,
+    ws: WebSocketUpgrade,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let p = state.players.get(&id).ok_or((StatusCode::NOT_FOUND, "player not found".to_string()))?;
+    let interval_ms = q.interval_ms.unwrap_or(state.cfg.metering_interval_ms);
+    if !(20..=1000).contains(&interval_ms) {
+        return Err((StatusCode::BAD_REQUEST, "intervalMs must be between 20 and 1000".to_string()));
+    }
+    let rx = p.subscribe_levels();
+    let frames = interval_ms.div_ceil(20) as u32;
+    Ok(ws.on_upgrade(move |socket| async move { ws_levels_task(socket, rx, frames).await }))
+}
+
+async fn ws_levels_task(
+    mut socket: axum::extract::ws::WebSocket,
+    mut rx: tokio::sync::broadcast::Receiver<FrameLevels>,
+    frames: u32,
+) {
+    use axum::extract::ws::Message;
+    use tokio::sync::broadcast::error::RecvError;
+    let mut acc = LevelAccumulator::new(frames);
+    loop {
+        tokio::select! {
+            msg = rx.recv() => match msg {
+                Ok(lv) => {
+                    if let Some(report) = acc.push(&lv) {
+                        let text = serde_json::to_string(&report).unwrap_or_default();
+                        if socket.send(Message::Text(text.into())).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            },
+            msg = socket.next() => match msg {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+pub async fn ws_spectrum(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(q): Query<SpectrumQuery>,
+    ws: WebSocketUpgrade,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let p = state.players.get(&id).ok_or((StatusCode::NOT_FOUND, "player not found".to_string()))?;
+    let settings =
+        SpectrumSettings::from_config(&state.cfg).with_query(&q).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let rx = p.subscribe();
+    Ok(ws.on_upgrade(move |socket| async move { ws_spectrum_task(socket, rx, settings).await }))
+}
+
+async fn ws_spectrum_task(
+    mut socket: axum::extract::ws::WebSocket,
+    mut rx: tokio::sync::broadcast::Receiver<AudioFrame>,
+    settings: SpectrumSettings,
+) {
+    use axum::extract::ws::Message;
+    use tokio::sync::broadcast::error::RecvError;
+    let mut analyzer = SpectrumAnalyzer::new(settings);
+    let layout = SpectrumMessage::Layout { frequencies: analyzer.frequencies() };
+    if socket.send(Message::Text(serde_json::to_string(&layout).unwrap_or_default().into())).await.is_err() {
+        return;
+    }
+    let mut tick = tokio::time::interval(SEND_INTERVAL);
+    tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    loop {
+        tokio::select! {
+            msg = rx.recv() => match msg {
+                Ok(frame) => analyzer.push_pcm(&frame.pcm),
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            },
+            _ = tick.tick() => {
+                let Some(bands) = analyzer.analyze() else { continue };
+                let text = serde_json::to_string(&SpectrumMessage::Spectrum { bands }).unwrap_or_default();
+                if socket.send(Message::Text(text.into())).await.is_err() {
+                    break;
+                }
+            }
+            msg = socket.next() => match msg {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
 pub async fn ws_events(
     State(state): State<AppState>,
     Path(id): Path<String>,
@@ -356,35 +741,159 @@ pub struct LoadTracksQuery {
 pub enum LoadResult {
     #[serde(rename = "track")]
     Track(Box<TrackOut>),
+    #[serde(rename = "playlist")]
+    Playlist(PlaylistOut),
+    #[serde(rename = "search")]
+    Search(Vec<TrackOut>),
     #[serde(rename = "empty")]
     Empty(serde_json::Value),
+    #[serde(rename = "error")]
+    Error(LoadErrorOut),
 }
 
-pub async fn load_tracks(Query(q): Query<LoadTracksQuery>) -> impl IntoResponse {
-    let engine = base64::engine::general_purpose::STANDARD;
-    if q.identifier.trim().is_empty() {
+#[derive(Debug, Serialize)]
+pub struct PlaylistOut {
+    pub info: PlaylistInfoOut,
+    #[serde(rename = "pluginInfo")]
+    pub plugin_info: serde_json::Value,
+    pub tracks: Vec<TrackOut>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlaylistInfoOut {
+    pub name: String,
+    #[serde(rename = "selectedTrack")]
+    pub selected_track: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LoadErrorOut {
+    pub message: String,
+    pub severity: String,
+    pub cause: String,
+}
+
+/// Loads an identifier through the enabled sources: a track, a playlist (albums,
+/// playlists and playlist files), search results, `empty` when nothing was found or
+/// no source handles it, or an `error` with the reason.
+pub async fn load_tracks(
+    State(state): State<AppState>,
+    Query(q): Query<LoadTracksQuery>,
+) -> impl IntoResponse {
+    let identifier = q.identifier.trim();
+    if identifier.is_empty() {
         return Json(LoadResult::Empty(serde_json::json!({})));
     }
-    let info = TrackInfoOut {
-        identifier: q.identifier.clone(),
-        is_seekable: false,
-        author: String::new(),
-        length: 0,
-        is_stream: true,
-        position: 0,
-        title: q.identifier.clone(),
-        uri: q.identifier.clone(),
-        artwork_url: None,
-        isrc: None,
-        source_name: "direct".into(),
+    let is_search = state.sources.is_search(identifier);
+    if !is_search && !is_uri_allowed(&state.cfg, identifier) {
+        return Json(load_error("This identifier is blocked by the source patterns", "blocked".into()));
+    }
+    let allowed = |t: &TrackMeta| is_uri_allowed(&state.cfg, &t.uri) && state.sources.find(&t.uri).is_some();
+    let result = match state.sources.load(identifier).await {
+        Ok(LoadedItem::Track(track)) if allowed(&track) => LoadResult::Track(Box::new(track_out(&track))),
+        Ok(LoadedItem::Track(_)) => {
+            load_error("This identifier is blocked by the source patterns", "blocked".into())
+        }
+        Ok(LoadedItem::Playlist(playlist)) => {
+            let selected =
+                playlist.selected_track.and_then(|i| playlist.tracks.get(i)).map(|t| t.uri.clone());
+            let tracks: Vec<TrackOut> =
+                playlist.tracks.iter().filter(|t| allowed(t)).map(track_out).collect();
+            let selected_track = selected
+                .and_then(|uri| tracks.iter().position(|t| t.info.uri == uri))
+                .map_or(-1, |i| i as i32);
+            if tracks.is_empty() {
+                LoadResult::Empty(serde_json::json!({}))
+            } else {
+                LoadResult::Playlist(PlaylistOut {
+                    info: PlaylistInfoOut { name: playlist.name, selected_track },
+                    plugin_info: serde_json::json!({}),
+                    tracks,
+                })
+            }
+        }
+        Ok(LoadedItem::Search(tracks)) => {
+            let tracks: Vec<TrackOut> = tracks.iter().filter(|t| allowed(t)).map(track_out).collect();
+            if tracks.is_empty() {
+                LoadResult::Empty(serde_json::json!({}))
+            } else {
+                LoadResult::Search(tracks)
+            }
+        }
+        Ok(LoadedItem::Empty) => LoadResult::Empty(serde_json::json!({})),
+        Err(e) => {
+            warn!(identifier, error = %format!("{e:#}"), "loadtracks failed");
+            let message = if is_search { "Search failed" } else { "Failed to load" };
+            load_error(message, format!("{e:#}"))
+        }
     };
-    let encoded = engine.encode(q.identifier.clone());
-    Json(LoadResult::Track(Box::new(TrackOut {
-        encoded,
-        info,
-        plugin_info: serde_json::json!({}),
+    Json(result)
+}
+
+fn load_error(message: &str, cause: String) -> LoadResult {
+    LoadResult::Error(LoadErrorOut { message: message.into(), severity: "common".into(), cause })
+}
+
+fn track_out(track: &TrackMeta) -> TrackOut {
+    TrackOut {
+        encoded: base64::engine::general_purpose::STANDARD.encode(&track.uri),
+        info: TrackInfoOut {
+            identifier: track.identifier.clone(),
+            is_seekable: !track.is_stream && track.length_ms > 0,
+            author: track.author.clone(),
+            length: track.length_ms as i64,
+            is_stream: track.is_stream,
+            position: 0,
+            title: track.title.clone(),
+            uri: track.uri.clone(),
+            artwork_url: track.artwork_url.clone(),
+            isrc: track.isrc.clone(),
+            source_name: track.source_name.into(),
+        },
+        plugin_info: track.plugin_info.clone(),
         user_data: serde_json::json!({}),
-    })))
+    }
+}
+
+/// Waveform buckets may be 10 ms to 60 s wide; 100 ms by default.
+const ANALYZE_RESOLUTION_MS: std::ops::RangeInclusive<u32> = 10..=60_000;
+const DEFAULT_ANALYZE_RESOLUTION_MS: u32 = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct AnalyzeQuery {
+    pub identifier: String,
+    #[serde(rename = "resolutionMs")]
+    pub resolution_ms: Option<u32>,
+}
+
+/// Decodes a whole track and reports its waveform, loudness, peak, duration and tempo.
+pub async fn analyze(
+    State(state): State<AppState>,
+    Query(q): Query<AnalyzeQuery>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let resolution_ms = q.resolution_ms.unwrap_or(DEFAULT_ANALYZE_RESOLUTION_MS);
+    if !ANALYZE_RESOLUTION_MS.contains(&resolution_ms) {
+        return Err((StatusCode::BAD_REQUEST, "resolutionMs must be between 10 and 60000".to_string()));
+    }
+    let identifier = q.identifier.trim();
+    if identifier.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "missing identifier".to_string()));
+    }
+    let uri =
+        resolve_search(&state, identifier).await.map_err(|s| (s, "search found nothing".to_string()))?;
+    require_source(&state, &uri).map_err(|s| (s, "no enabled source handles this identifier".to_string()))?;
+    if !is_uri_allowed(&state.cfg, &uri) {
+        warn!(%uri, "URI blocked by config patterns");
+        return Err((StatusCode::FORBIDDEN, "identifier blocked by config patterns".to_string()));
+    }
+    let uri = resolve_for_playback(&state, &uri).await;
+    let started = std::time::Instant::now();
+    let result = analysis::analyze(&state.cfg, &uri, resolution_ms).await.map_err(|e| {
+        warn!(%uri, error = %e, "analysis failed");
+        (StatusCode::BAD_REQUEST, format!("error: {e:#}"))
+    })?;
+    info!(%uri, duration_ms = result.duration_ms, took_ms = started.elapsed().as_millis() as u64, "track analyzed");
+    Ok(Json(result))
 }
 
 #[derive(Debug, Deserialize)]
@@ -458,12 +967,14 @@ pub struct InfoResponse {
     version: String,
     #[serde(rename = "buildTime")]
     build_time: u64,
+    #[serde(rename = "sourceManagers")]
+    source_managers: Vec<&'static str>,
 }
 
-pub async fn info() -> impl IntoResponse {
+pub async fn info(State(state): State<AppState>) -> impl IntoResponse {
     let version = env!("CARGO_PKG_VERSION").to_string();
     let build_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
-    Json(InfoResponse { version, build_time: build_time })
+    Json(InfoResponse { version, build_time, source_managers: state.sources.names() })
 }
 
 pub async fn resolve_http(
@@ -488,7 +999,10 @@ pub async fn resolve_http(
                 }
             }
         }
-        match resolve_to_direct(&cfg, u).await {
+        if state.sources.find(u).is_none() {
+            return (StatusCode::BAD_REQUEST, "error: no enabled source handles this URL".to_string());
+        }
+        match state.sources.resolve(u).await {
             Ok(d) => (StatusCode::OK, d),
             Err(e) => (StatusCode::BAD_REQUEST, format!("error: {}", e)),
         }
@@ -497,36 +1011,205 @@ pub async fn resolve_http(
     }
 }
 
-pub async fn ws_stream(
+#[derive(Debug, Deserialize)]
+pub struct HttpStreamQuery {
+    #[serde(default)]
+    pub bitrate: Option<u32>,
+}
+
+pub async fn stream_ogg(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(q): Query<HttpStreamQuery>,
+) -> Result<impl IntoResponse, StatusCode> {
+    http_stream(state, id, HttpStreamFormat::Ogg, q.bitrate)
+}
+
+pub async fn stream_mp3(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(q): Query<HttpStreamQuery>,
+) -> Result<impl IntoResponse, StatusCode> {
+    http_stream(state, id, HttpStreamFormat::Mp3, q.bitrate)
+}
+
+pub async fn stream_wav(
     State(state): State<AppState>,
     Path(id): Path<String>,
-    ws: WebSocketUpgrade,
 ) -> Result<impl IntoResponse, StatusCode> {
+    http_stream(state, id, HttpStreamFormat::Wav, None)
+}
+
+fn http_stream(
+    state: AppState,
+    id: String,
+    format: HttpStreamFormat,
+    bitrate: Option<u32>,
+) -> Result<axum::response::Response, StatusCode> {
+    use axum::http::header;
     let p = state.players.get(&id).ok_or(StatusCode::NOT_FOUND)?;
-    let rx = p.subscribe();
-    info!(player_id=%id, "WS subscriber connected");
-    Ok(ws.on_upgrade(move |socket| async move { ws_task(socket, rx).await }))
+    if bitrate.is_some_and(|b| !(8_000..=320_000).contains(&b)) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let body =
+        crate::audio::stream::open(&state.cfg.ffmpeg_path, format, bitrate, p.subscribe()).map_err(|e| {
+            error!(?e, "failed to start http audio stream");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    info!(player_id=%id, ?format, "HTTP stream listener connected");
+    Ok((
+        [(header::CONTENT_TYPE, format.content_type()), (header::CACHE_CONTROL, "no-cache, no-store")],
+        axum::body::Body::from_stream(body),
+    )
+        .into_response())
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct TokenQuery {
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+pub async fn hls_playlist(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(q): Query<TokenQuery>,
+) -> Result<impl IntoResponse, StatusCode> {
+    use axum::http::header;
+    let p = state.players.get(&id).map(|p| p.clone()).ok_or(StatusCode::NOT_FOUND)?;
+    let packager = p.hls_packager().await.map_err(|e| {
+        error!(?e, "failed to start hls packager");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let mut playlist = packager.playlist().await.ok_or(StatusCode::SERVICE_UNAVAILABLE)?;
+    if let Some(token) = q.token.as_deref() {
+        playlist = crate::audio::hls::with_token(&playlist, token);
+    }
+    Ok((
+        [(header::CONTENT_TYPE, "application/vnd.apple.mpegurl"), (header::CACHE_CONTROL, "no-cache")],
+        playlist,
+    ))
+}
+
+pub async fn hls_segment(
+    State(state): State<AppState>,
+    Path((id, name)): Path<(String, String)>,
+) -> Result<impl IntoResponse, StatusCode> {
+    use axum::http::header;
+    let p = state.players.get(&id).map(|p| p.clone()).ok_or(StatusCode::NOT_FOUND)?;
+    let packager = p.hls_packager().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let data = packager.segment(&name).await.ok_or(StatusCode::NOT_FOUND)?;
+    Ok(([(header::CONTENT_TYPE, crate::audio::hls::segment_content_type(&name))], data))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct FramingQuery {
+    #[serde(default)]
+    pub framed: bool,
+}
+
+pub async fn ws_stream(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(q): Query<OutputFormatQuery>,
+    Query(opus_q): Query<OpusQuery>,
+    Query(framing): Query<FramingQuery>,
+    ws: WebSocketUpgrade,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let p = state
+        .players
+        .get(&id)
+        .map(|p| p.clone())
+        .ok_or((StatusCode::NOT_FOUND, "player not found".to_string()))?;
+    if opus_q.encoding.unwrap_or_default() == StreamEncoding::Opus {
+        if framing.framed {
+            return Err((StatusCode::BAD_REQUEST, "framed mode is only available for PCM".to_string()));
+        }
+        let settings = OpusSettings::from_config(&state.cfg)
+            .with_query(&opus_q)
+            .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+        let rx = p.subscribe_opus(settings).await.map_err(|e| {
+            error!(?e, "failed to start opus encoder");
+            (StatusCode::INTERNAL_SERVER_ERROR, "failed to start opus encoder".to_string())
+        })?;
+        info!(player_id=%id, ?settings, "WS opus subscriber connected");
+        let sub = p.subscribe_queued("opus", rx);
+        return Ok(ws.on_upgrade(move |socket| async move {
+            ws_task(socket, sub, |pkt: &Bytes, _| vec![pkt.clone()]).await
+        }));
+    }
+    let fmt = OutputFormat::from_query(&q).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    if framing.framed && (fmt.frame_ms != 20 || fmt.sample_rate != SOURCE_SAMPLE_RATE) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "framed mode requires frameMs=20 and sampleRate=48000".to_string(),
+        ));
+    }
+    let sub = p.subscribe_queued("pcm", p.subscribe());
+    info!(player_id=%id, ?fmt, framed = framing.framed, "WS subscriber connected");
+    let mut converter = FormatConverter::new(fmt);
+    let mut carry_flags = 0u8;
+    let render = move |frame: &AudioFrame, lagged: bool| {
+        if lagged {
+            carry_flags |= FLAG_DISCONTINUITY;
+        }
+        if frame.pcm.is_empty() {
+            // Track end marker: a header without audio in framed mode, nothing otherwise.
+            if !framing.framed {
+                return Vec::new();
+            }
+            return vec![frame.framed(std::mem::take(&mut carry_flags), &[])];
+        }
+        let out = converter.push(&frame.pcm);
+        if !framing.framed {
+            return out;
+        }
+        if out.is_empty() {
+            // Keep boundary flags until a message actually goes out.
+            carry_flags |= frame.flags;
+            return out;
+        }
+        let extra = std::mem::take(&mut carry_flags);
+        out.iter().map(|b| frame.framed(extra, b)).collect()
+    };
+    Ok(ws.on_upgrade(move |socket| async move { ws_task(socket, sub, render).await }))
 }
 
-async fn ws_task(mut socket: axum::extract::ws::WebSocket, mut rx: tokio::sync::broadcast::Receiver<Bytes>) {
+/// Forwards a queued player subscription to a websocket. `render` turns each item
+/// into the binary messages to send; its flag is set when items were dropped from
+/// the queue right before it.
+async fn ws_task<T: Payload>(
+    mut socket: axum::extract::ws::WebSocket,
+    mut sub: Subscription<T>,
+    mut render: impl FnMut(&T, bool) -> Vec<Bytes>,
+) {
+    use axum::extract::ws::{CloseFrame, Message};
     let mut ws_forwarded: u64 = 0;
-    loop {
+    'outer: loop {
         tokio::select! {
-            msg = rx.recv() => {
+            msg = sub.recv() => {
                 match msg {
-                    Ok(pkt) => {
-                        if socket.send(axum::extract::ws::Message::Binary(pkt)).await.is_err() { break; }
-                        ws_forwarded += 1;
-                        if ws_forwarded % 1000 == 0 { info!(ws_forwarded, "WS forwarded frames (summary)"); }
+                    Some((item, gap)) => {
+                        for frame in render(&item, gap) {
+                            tokio::select! {
+                                res = socket.send(Message::Binary(frame)) => if res.is_err() { break 'outer; },
+                                _ = sub.lagged_out() => break 'outer,
+                            }
+                            ws_forwarded += 1;
+                            if ws_forwarded % 1000 == 0 { info!(ws_forwarded, "WS forwarded frames (summary)"); }
+                        }
                     }
-                    Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
-                        warn!(lost = n, "WS lagged; dropped packets");
+                    None => {
+                        if sub.dropped_for_lag() {
+                            let close = CloseFrame { code: 1008, reason: "subscriber too slow".into() };
+                            let _ = socket.send(Message::Close(Some(close))).await;
+                        }
+                        break;
                     }
-                    Err(_) => break,
                 }
             }
             Some(Ok(msg)) = socket.next() => {
-                match msg { axum::extract::ws::Message::Close(_) => break, _ => {} }
+                if let Message::Close(_) = msg { break; }
             }
             else => break,
         }
@@ -0,0 +1,289 @@
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::path::PathBuf;
+use tokio::sync::mpsc;
+
+use crate::audio::{
+    decoder::{Decoder, PcmBlock},
+    meter::to_dbfs,
+    source::{is_resonix_temp_file, prepare_source, Source},
+};
+use crate::config::EffectiveConfig;
+
+const SAMPLE_RATE: u64 = 48_000;
+/// Tracks longer than this are refused rather than decoded to the end.
+const MAX_DURATION_MS: u64 = 3 * 60 * 60 * 1000;
+/// Loudness is measured over 400 ms blocks overlapping by 75% (ITU-R BS.1770).
+const LOUDNESS_STEP_SAMPLES: usize = 4_800;
+const LOUDNESS_BLOCK_STEPS: usize = 4;
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+const RELATIVE_GATE_LU: f64 = -10.0;
+/// Onset envelope hop for tempo estimation (5 ms).
+const ONSET_HOP_SAMPLES: usize = 240;
+/// Only the first five minutes are used for tempo estimation.
+const MAX_ONSET_HOPS: usize = 60_000;
+const MIN_BPM: f64 = 60.0;
+const MAX_BPM: f64 = 200.0;
+/// Tempo candidates are weighted towards this, one octave either way.
+const PREFERRED_BPM: f64 = 120.0;
+/// Tracks shorter than this get no tempo estimate.
+const MIN_TEMPO_SECS: u64 = 5;
+
+/// What `GET /v0/analyze` reports about a track.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Analysis {
+    pub duration_ms: u64,
+    /// Integrated loudness (ITU-R BS.1770); `None` for silence.
+    pub loudness_lufs: Option<f32>,
+    /// Sample peak in dBFS.
+    pub peak_db: f32,
+    /// `None` when no steady beat was found.
+    pub bpm: Option<f32>,
+    pub waveform: Waveform,
+}
+
+/// Lowest and highest sample (-1.0..1.0, both channels) of every `resolution_ms`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Waveform {
+    pub resolution_ms: u32,
+    pub min: Vec<f32>,
+    pub max: Vec<f32>,
+}
+
+/// Resolves nothing itself: `uri` is a direct file path or URL, decoded with the
+/// configured backend exactly as a player would. Resolver temp files are removed
+/// afterwards.
+pub async fn analyze(cfg: &EffectiveConfig, uri: &str, resolution_ms: u32) -> Result<Analysis> {
+    let source = prepare_source(uri).await?;
+    let temp: Option<PathBuf> = match &source {
+        Source::File(p) if is_resonix_temp_file(p) => Some(p.clone()),
+        _ => None,
+    };
+    let result = decode_and_measure(cfg, &source, resolution_ms).await;
+    if let Some(p) = temp {
+        crate::resolver::forget_download(&p.to_string_lossy());
+        let _ = tokio::fs::remove_file(p).await;
+    }
+    result
+}
+
+async fn decode_and_measure(cfg: &EffectiveConfig, source: &Source, resolution_ms: u32) -> Result<Analysis> {
+    let mut decoder = Decoder::open(source, cfg).await?;
+    if decoder.icy().is_some() || decoder.stream_info().is_some_and(|i| i.live) {
+        bail!("live streams cannot be analyzed");
+    }
+    // Measuring is CPU-bound, so it runs on the blocking pool while decoding stays here.
+    let (tx, mut rx) = mpsc::channel::<PcmBlock>(16);
+    let worker = tokio::task::spawn_blocking(move || {
+        let mut analyzer = Analyzer::new(resolution_ms);
+        while let Some(block) = rx.blocking_recv() {
+            analyzer.push(&block);
+        }
+        analyzer.finish()
+    });
+    let max_samples = MAX_DURATION_MS * SAMPLE_RATE / 1000;
+    let mut samples = 0u64;
+    while let Some(block) = decoder.next_pcm_block().await? {
+        samples += block.l.len() as u64;
+        if samples > max_samples {
+            bail!("track is longer than {} minutes", MAX_DURATION_MS / 60_000);
+        }
+        if tx.send(block).await.is_err() {
+            break;
+        }
+    }
+    drop(tx);
+    let analysis = worker.await.context("analysis task failed")?;
+    if analysis.duration_ms == 0 {
+        bail!("no audio decoded");
+    }
+    Ok(analysis)
+}
+
+/// Accumulates the measurements block by block so a track never has to be held
+/// in memory.
+pub struct Analyzer {
+    samples: u64,
+    peak: f32,
+    bucket_len: usize,
+    bucket_fill: usize,
+    bucket: (f32, f32),
+    waveform: Waveform,
+    k_weight: [[Biquad; 2]; 2],
+    step_fill: usize,
+    step_energy: f64,
+    /// Mean square of each 100 ms step, summed over both channels.
+    steps: Vec<f64>,
+    onset_fill: usize,
+    onset_energy: f64,
+    hop_energies: Vec<f32>,
+}
+
+impl Analyzer {
+    pub fn new(resolution_ms: u32) -> Self {
+        Self {
+            samples: 0,
+            peak: 0.0,
+            bucket_len: (resolution_ms as u64 * SAMPLE_RATE / 1000).max(1) as usize,
+            bucket_fill: 0,
+            bucket: (0.0, 0.0),
+            waveform: Waveform { resolution_ms, min: Vec::new(), max: Vec::new() },
+            k_weight: [k_weighting(), k_weighting()],
+            step_fill: 0,
+            step_energy: 0.0,
+            steps: Vec::new(),
+            onset_fill: 0,
+            onset_energy: 0.0,
+            hop_energies: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, block: &PcmBlock) {
+        for (&l, &r) in block.l.iter().zip(&block.r) {
+            self.samples += 1;
+            self.peak = self.peak.max(l.abs()).max(r.abs());
+
+            let (lo, hi) = &mut self.bucket;
+            *lo = lo.min(l).min(r);
+            *hi = hi.max(l).max(r);
+            self.bucket_fill += 1;
+            if self.bucket_fill == self.bucket_len {
+                self.flush_bucket();
+            }
+
+            let [kl, kr] = &mut self.k_weight;
+            let fl = kl.iter_mut().fold(l as f64, |x, f| f.process(x));
+            let fr = kr.iter_mut().fold(r as f64, |x, f| f.process(x));
+            self.step_energy += fl * fl + fr * fr;
+            self.step_fill += 1;
+            if self.step_fill == LOUDNESS_STEP_SAMPLES {
+                self.steps.push(self.step_energy / LOUDNESS_STEP_SAMPLES as f64);
+                self.step_energy = 0.0;
+                self.step_fill = 0;
+            }
+
+            if self.hop_energies.len() < MAX_ONSET_HOPS {
+                let mono = (l + r) as f64 * 0.5;
+                self.onset_energy += mono * mono;
+                self.onset_fill += 1;
+                if self.onset_fill == ONSET_HOP_SAMPLES {
+                    self.hop_energies.push((self.onset_energy / ONSET_HOP_SAMPLES as f64) as f32);
+                    self.onset_energy = 0.0;
+                    self.onset_fill = 0;
+                }
+            }
+        }
+    }
+
+    fn flush_bucket(&mut self) {
+        let round = |v: f32| (v * 10_000.0).round() / 10_000.0;
+        self.waveform.min.push(round(self.bucket.0));
+        self.waveform.max.push(round(self.bucket.1));
+        self.bucket = (0.0, 0.0);
+        self.bucket_fill = 0;
+    }
+
+    pub fn finish(mut self) -> Analysis {
+        if self.bucket_fill > 0 {
+            self.flush_bucket();
+        }
+        let bpm = (self.samples >= MIN_TEMPO_SECS * SAMPLE_RATE).then(|| estimate_bpm(&self.hop_energies));
+        Analysis {
+            duration_ms: self.samples * 1000 / SAMPLE_RATE,
+            loudness_lufs: integrated_loudness(&self.steps).map(|l| l as f32),
+            peak_db: to_dbfs(self.peak as f64),
+            bpm: bpm.flatten().map(|b| (b * 10.0).round() as f32 / 10.0),
+            waveform: self.waveform,
+        }
+    }
+}
+
+fn integrated_loudness(steps: &[f64]) -> Option<f64> {
+    let blocks: Vec<f64> = steps
+        .windows(LOUDNESS_BLOCK_STEPS)
+        .map(|w| w.iter().sum::<f64>() / LOUDNESS_BLOCK_STEPS as f64)
+        .collect();
+    let lufs = |z: f64| -0.691 + 10.0 * z.log10();
+    let mean = |it: &mut dyn Iterator<Item = f64>| {
+        let (sum, n) = it.fold((0.0, 0usize), |(s, n), z| (s + z, n + 1));
+        (n > 0).then(|| sum / n as f64)
+    };
+    let audible = mean(&mut blocks.iter().copied().filter(|&z| z > 0.0 && lufs(z) > ABSOLUTE_GATE_LUFS))?;
+    let relative_gate = lufs(audible) + RELATIVE_GATE_LU;
+    let gated = mean(
+        &mut blocks
+            .iter()
+            .copied()
+            .filter(|&z| z > 0.0 && lufs(z) > ABSOLUTE_GATE_LUFS && lufs(z) > relative_gate),
+    )?;
+    Some(lufs(gated))
+}
+
+/// Autocorrelates the onset envelope (rises in log energy) over the lags of
+/// 60-200 BPM and picks the strongest, weighted towards 120 BPM.
+fn estimate_bpm(hop_energies: &[f32]) -> Option<f64> {
+    let log: Vec<f32> = hop_energies.iter().map(|&e| (e + 1e-10).ln()).collect();
+    let mut onset: Vec<f32> = log.windows(2).map(|w| (w[1] - w[0]).max(0.0)).collect();
+    let mean = onset.iter().sum::<f32>() / onset.len().max(1) as f32;
+    onset.iter_mut().for_each(|o| *o -= mean);
+
+    let hops_per_min = 60.0 * SAMPLE_RATE as f64 / ONSET_HOP_SAMPLES as f64;
+    let min_lag = (hops_per_min / MAX_BPM).floor() as usize;
+    let max_lag = (hops_per_min / MIN_BPM).ceil() as usize;
+    if onset.len() < max_lag * 4 {
+        return None;
+    }
+    let corr = |lag: usize| -> f64 {
+        onset.iter().zip(&onset[lag..]).map(|(a, b)| (*a as f64) * (*b as f64)).sum::<f64>()
+            / (onset.len() - lag) as f64
+    };
+    let ac: Vec<f64> = (min_lag - 1..=max_lag + 1).map(corr).collect();
+    let weight = |lag: f64| (-0.5 * (hops_per_min / lag / PREFERRED_BPM).log2().powi(2)).exp();
+    let (best, _) = (1..ac.len() - 1)
+        .filter(|&i| ac[i] > 0.0 && ac[i] >= ac[i - 1] && ac[i] >= ac[i + 1])
+        .map(|i| (i, ac[i] * weight((min_lag - 1 + i) as f64)))
+        .max_by(|a, b| a.1.total_cmp(&b.1))?;
+    // Parabolic interpolation between neighbouring lags.
+    let (a, b, c) = (ac[best - 1], ac[best], ac[best + 1]);
+    let denom = a - 2.0 * b + c;
+    let shift = if denom.abs() > f64::EPSILON { (0.5 * (a - c) / denom).clamp(-0.5, 0.5) } else { 0.0 };
+    let lag = (min_lag - 1 + best) as f64 + shift;
+    Some(hops_per_min / lag)
+}
+
+/// Direct form I biquad.
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    const fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self { b, a, x: [0.0; 2], y: [0.0; 2] }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+/// BS.1770 K-weighting at 48 kHz: a high shelf followed by a high pass.
+fn k_weighting() -> [Biquad; 2] {
+    [
+        Biquad::new(
+            [1.53512485958697, -2.69169618940638, 1.19839281085285],
+            [-1.69065929318241, 0.73248077421585],
+        ),
+        Biquad::new([1.0, -2.0, 1.0], [-1.99004745483398, 0.99007225036621]),
+    ]
+}
@@ -0,0 +1,216 @@
+use anyhow::{bail, Context, Result};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+use std::{path::Path, process::Stdio, time::Duration};
+use tokio::process::Command;
+
+/// How long ffmpeg may take to list a file's chapters.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+/// YouTube only turns a description into chapters when it lists at least this many.
+const MIN_DESCRIPTION_CHAPTERS: usize = 3;
+
+static DESCRIPTION_TIMESTAMP: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?:^|[\s(\[])((?:(\d{1,2}):)?(\d{1,2}):(\d{2}))(?:$|[\s)\],.:-])")
+        .expect("valid timestamp regex")
+});
+
+/// A named section of a track.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Chapter {
+    pub title: String,
+    pub start_ms: u64,
+    /// `None` when the last chapter runs to an unknown end.
+    pub end_ms: Option<u64>,
+}
+
+/// Lists the chapters of a local file (MP4 chapters, ID3 `CHAP`, Matroska, Ogg
+/// `CHAPTERxx` comments) by having ffmpeg export its metadata.
+pub async fn probe_chapters(ffmpeg_bin: &str, path: &Path) -> Result<Vec<Chapter>> {
+    let mut cmd = Command::new(ffmpeg_bin);
+    cmd.arg("-hide_banner")
+        .arg("-loglevel")
+        .arg("error")
+        .arg("-i")
+        .arg(path)
+        // No streams are written, so nothing has to be decoded.
+        .args(["-vn", "-an", "-sn", "-dn", "-f", "ffmetadata", "-"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true);
+    let output = tokio::time::timeout(PROBE_TIMEOUT, cmd.output())
+        .await
+        .context("ffmpeg chapter probe timed out")?
+        .with_context(|| format!("spawn ffmpeg using '{ffmpeg_bin}'"))?;
+    if !output.status.success() {
+        bail!("ffmpeg exited with {}", output.status);
+    }
+    Ok(parse_ffmetadata(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Reads the `[CHAPTER]` sections of an ffmetadata file.
+fn parse_ffmetadata(text: &str) -> Vec<Chapter> {
+    #[derive(Default)]
+    struct Raw {
+        timebase: (u64, u64),
+        start: Option<u64>,
+        end: Option<u64>,
+        title: Option<String>,
+    }
+    let mut raws: Vec<Raw> = Vec::new();
+    let mut in_chapter = false;
+    for line in logical_lines(text) {
+        if line.starts_with('[') {
+            in_chapter = line == "[CHAPTER]";
+            if in_chapter {
+                raws.push(Raw { timebase: (1, 1_000_000_000), ..Default::default() });
+            }
+            continue;
+        }
+        let (Some(raw), Some((key, value))) = (raws.last_mut().filter(|_| in_chapter), split_key(&line))
+        else {
+            continue;
+        };
+        match key.to_ascii_lowercase().as_str() {
+            "timebase" => {
+                if let Some((n, d)) = value.split_once('/') {
+                    if let (Ok(n), Ok(d)) = (n.trim().parse(), d.trim().parse()) {
+                        if d > 0 {
+                            raw.timebase = (n, d);
+                        }
+                    }
+                }
+            }
+            "start" => raw.start = value.trim().parse().ok(),
+            "end" => raw.end = value.trim().parse().ok(),
+            "title" => raw.title = Some(value),
+            _ => {}
+        }
+    }
+    let to_ms = |ticks: u64, (n, d): (u64, u64)| (ticks as u128 * n as u128 * 1000 / d as u128) as u64;
+    let mut chapters: Vec<Chapter> = raws
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, r)| {
+            let start_ms = to_ms(r.start?, r.timebase);
+            Some(Chapter {
+                title: r
+                    .title
+                    .filter(|t| !t.trim().is_empty())
+                    .unwrap_or_else(|| format!("Chapter {}", i + 1)),
+                start_ms,
+                end_ms: r.end.map(|e| to_ms(e, r.timebase)).filter(|&e| e > start_ms),
+            })
+        })
+        .collect();
+    chapters.sort_by_key(|c| c.start_ms);
+    chapters
+}
+
+/// Joins lines continued with a trailing backslash and undoes ffmetadata escaping
+/// of `=`, `;`, `#` and `\`. Comment lines are dropped.
+fn logical_lines(text: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+    let mut at_line_start = true;
+    while let Some(c) = chars.next() {
+        if at_line_start && (c == ';' || c == '#') {
+            // Comment: skip to the end of the line.
+            for c in chars.by_ref() {
+                if c == '\n' {
+                    break;
+                }
+            }
+            continue;
+        }
+        at_line_start = false;
+        match c {
+            '\\' => match chars.next() {
+                Some('\r') if chars.peek() == Some(&'\n') => {
+                    chars.next();
+                }
+                Some('\n') => {}
+                // Escaped `=` stays escaped so key/value splitting can tell it apart.
+                Some('=') => current.push_str("\\="),
+                Some(other) => current.push(other),
+                None => {}
+            },
+            '\n' => {
+                out.push(std::mem::take(&mut current).trim_end_matches('\r').to_string());
+                at_line_start = true;
+            }
+            _ => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        out.push(current.trim_end_matches('\r').to_string());
+    }
+    out
+}
+
+/// Splits `key=value` at the first unescaped `=`.
+fn split_key(line: &str) -> Option<(String, String)> {
+    let bytes = line.as_bytes();
+    let pos = (0..bytes.len()).find(|&i| bytes[i] == b'=' && (i == 0 || bytes[i - 1] != b'\\'))?;
+    let unescape = |s: &str| s.replace("\\=", "=");
+    Some((unescape(&line[..pos]), unescape(&line[pos + 1..])))
+}
+
+/// Finds chapter timestamps in a video description the way YouTube does: one
+/// timestamp per line, starting at `0:00`, ascending, at least three of them.
+/// `length_ms` (0 if unknown) closes the last chapter.
+pub fn from_description(description: &str, length_ms: u64) -> Vec<Chapter> {
+    let mut starts: Vec<(u64, String)> = Vec::new();
+    for line in description.lines() {
+        let Some(caps) = DESCRIPTION_TIMESTAMP.captures(line) else { continue };
+        let num = |i| caps.get(i).map_or(0, |m| m.as_str().parse::<u64>().unwrap_or(0));
+        let (hours, minutes, seconds) = (num(2), num(3), num(4));
+        if seconds >= 60 || (caps.get(2).is_some() && minutes >= 60) {
+            continue;
+        }
+        let start_ms = ((hours * 60 + minutes) * 60 + seconds) * 1000;
+        let stamp = caps.get(1).expect("timestamp group");
+        let title = format!("{} {}", &line[..stamp.start()], &line[stamp.end()..]);
+        starts.push((start_ms, clean_title(&title)));
+    }
+    let ascending = starts.windows(2).all(|w| w[0].0 < w[1].0);
+    if starts.len() < MIN_DESCRIPTION_CHAPTERS || starts[0].0 != 0 || !ascending {
+        return Vec::new();
+    }
+    let ends: Vec<Option<u64>> = starts
+        .iter()
+        .skip(1)
+        .map(|(s, _)| Some(*s))
+        .chain(std::iter::once((length_ms > starts[starts.len() - 1].0).then_some(length_ms)))
+        .collect();
+    starts
+        .into_iter()
+        .zip(ends)
+        .enumerate()
+        .map(|(i, ((start_ms, title), end_ms))| Chapter {
+            title: if title.is_empty() { format!("Chapter {}", i + 1) } else { title },
+            start_ms,
+            end_ms,
+        })
+        .collect()
+}
+
+/// Strips the separators and numbering that usually surround a timestamp.
+fn clean_title(raw: &str) -> String {
+    let trimmed = raw.trim().trim_matches(|c: char| {
+        c.is_whitespace() || matches!(c, '-' | '–' | '—' | '|' | ':' | '(' | ')' | '[' | ']' | '•' | '·')
+    });
+    let without_number = match trimmed.split_once(['.', ')']) {
+        Some((n, rest)) if !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()) => rest.trim(),
+        _ => trimmed,
+    };
+    without_number.to_string()
+}
+
+/// Index of the chapter playing at `position_ms`.
+pub fn chapter_at(chapters: &[Chapter], position_ms: u64) -> Option<usize> {
+    chapters.partition_point(|c| c.start_ms <= position_ms).checked_sub(1)
+}
@@ -0,0 +1,111 @@
+use serde::Serialize;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::time::Instant;
+
+/// Duration of one output frame (960 samples at 48 kHz).
+pub const FRAME_DURATION: Duration = Duration::from_millis(20);
+
+/// Output timing for a player, shared so it can be listed.
+#[derive(Debug, Default)]
+pub struct PacingStats {
+    sent: AtomicU64,
+    underruns: AtomicU64,
+    deficit: AtomicU64,
+    burst_frames: AtomicU64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PacingSnapshot {
+    pub sent_frames: u64,
+    /// Frames whose audio was not decoded yet when they became due.
+    pub underruns: u64,
+    /// Frames written off because output fell further behind than a burst can recover.
+    pub frame_deficit: u64,
+    /// Frames sent late as part of a catch-up burst.
+    pub burst_frames: u64,
+}
+
+impl PacingStats {
+    pub fn snapshot(&self) -> PacingSnapshot {
+        PacingSnapshot {
+            sent_frames: self.sent.load(Ordering::Relaxed),
+            underruns: self.underruns.load(Ordering::Relaxed),
+            frame_deficit: self.deficit.load(Ordering::Relaxed),
+            burst_frames: self.burst_frames.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn underrun(&self) {
+        self.underruns.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Paces frames against a monotonic sample clock: frame `n` is due at
+/// `origin + n * 20 ms`, so timer jitter never accumulates into drift. When output
+/// falls behind, the missed frames are sent back to back, at most `max_burst` per
+/// wake-up; anything beyond that is counted as deficit and the clock moves on.
+pub struct PacingClock {
+    origin: Instant,
+    frames: u64,
+    max_burst: u64,
+    stats: Arc<PacingStats>,
+}
+
+impl PacingClock {
+    pub fn new(max_burst: u64, stats: Arc<PacingStats>) -> Self {
+        Self { origin: Instant::now(), frames: 0, max_burst: max_burst.max(1), stats }
+    }
+
+    fn deadline(&self) -> Instant {
+        self.origin + FRAME_DURATION * self.frames as u32
+    }
+
+    /// Sleeps until the next frame is due.
+    pub async fn wait(&self) {
+        tokio::time::sleep_until(self.deadline()).await;
+    }
+
+    /// Number of frames to send now (at least one). If more are overdue than a burst
+    /// allows, the rest are skipped by moving the origin forward.
+    pub fn due(&mut self) -> u64 {
+        let elapsed = Instant::now().saturating_duration_since(self.origin);
+        let due = (elapsed.as_nanos() / FRAME_DURATION.as_nanos()) as u64 + 1;
+        let due = due.saturating_sub(self.frames).max(1);
+        if due > self.max_burst {
+            let excess = due - self.max_burst;
+            self.origin += FRAME_DURATION * excess as u32;
+            self.stats.deficit.fetch_add(excess, Ordering::Relaxed);
+            return self.max_burst;
+        }
+        due
+    }
+
+    /// Records that one frame was sent; `late` marks frames sent in a catch-up burst.
+    pub fn advance(&mut self, late: bool) {
+        self.frames += 1;
+        self.stats.sent.fetch_add(1, Ordering::Relaxed);
+        if late {
+            self.stats.burst_frames.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Makes the next frame due now, dropping any backlog.
+    pub fn restart(&mut self) {
+        self.origin = Instant::now();
+        self.frames = 0;
+    }
+
+    /// Makes the next frame due one frame from now without sending anything, so
+    /// time spent paused or waiting on the decoder is not caught up afterwards.
+    pub fn hold(&mut self) {
+        self.origin = Instant::now() + FRAME_DURATION;
+        self.frames = 0;
+    }
+}
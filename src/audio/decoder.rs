@@ -1,10 +1,24 @@
 use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
 use std::path::Path;
+use std::process::Stdio;
 use tokio::{
-    io::{AsyncReadExt, BufReader},
+    io::{AsyncReadExt, AsyncWriteExt, BufReader},
     process::{Child, ChildStdout, Command},
+    sync::oneshot,
 };
-use std::process::Stdio;
+use tracing::debug;
+
+use crate::audio::{
+    http::{HttpSettings, HttpStream},
+    icy::IcyWatch,
+    native::{NativeDecoder, NeedsFfmpeg},
+    segments::StreamInfo,
+    source::Source,
+    tags::TagsReceiver,
+};
+use crate::config::EffectiveConfig;
+use crate::utils::task::AbortOnDrop;
 
 const SAMPLE_RATE: usize = 48_000;
 const CHANNELS: usize = 2;
@@ -17,20 +31,186 @@ pub struct PcmBlock {
     pub r: Vec<f32>,
 }
 
+/// Which decoder tracks are opened with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DecoderBackend {
+    /// Always spawn ffmpeg.
+    Ffmpeg,
+    /// Decode in-process, using ffmpeg only for formats that cannot be decoded natively
+    /// (Opus among them).
+    Native,
+}
+
+/// A track decoder producing 48 kHz stereo blocks.
+pub enum Decoder {
+    Ffmpeg(Box<FfmpegDecoder>),
+    Native(NativeDecoder),
+}
+
+impl Decoder {
+    /// Opens `source` with the configured backend. HTTP sources are requested again
+    /// if native decoding fails and ffmpeg has to take over, or if they are MP4
+    /// files that can only be decoded with seeking.
+    pub async fn open(source: &Source, cfg: &EffectiveConfig) -> Result<Self> {
+        let http = HttpSettings::from_config(cfg);
+        if cfg.decoder_backend == DecoderBackend::Native {
+            match NativeDecoder::open(source, &http).await {
+                Ok(d) => return Ok(Decoder::Native(d)),
+                Err(e) if !cfg.ffmpeg_available && e.is::<NeedsFfmpeg>() => return Err(e),
+                Err(e) => debug!(?source, error = %e, "native decoding unavailable, using ffmpeg"),
+            }
+            // Some containers cannot be probed without seeking; with no ffmpeg to seek
+            // over HTTP, such files are downloaded first.
+            if let (Source::Http(url), false) = (source, cfg.ffmpeg_available) {
+                return Ok(Decoder::Native(NativeDecoder::open_downloaded(url, &http).await?));
+            }
+        }
+        let dec = match source {
+            Source::File(path) => FfmpegDecoder::open(path, &cfg.ffmpeg_path)?,
+            Source::Http(url) => {
+                let mut stream = HttpStream::open(url, &http).await?;
+                if stream.needs_seeking().await? {
+                    // ffmpeg fetches such files itself so it can seek to the `moov`
+                    // box with Range requests.
+                    FfmpegDecoder::open_url(stream, url, &cfg.ffmpeg_path)?
+                } else {
+                    FfmpegDecoder::open_stream(stream, &cfg.ffmpeg_path)?
+                }
+            }
+        };
+        Ok(Decoder::Ffmpeg(Box::new(dec)))
+    }
+
+    /// Restarts decoding of a local file at `position_ms` with the same backend.
+    /// Left as it was if that fails.
+    pub async fn seek(&mut self, path: &Path, cfg: &EffectiveConfig, position_ms: u64) -> Result<()> {
+        *self = match self {
+            Decoder::Ffmpeg(_) => {
+                Decoder::Ffmpeg(Box::new(FfmpegDecoder::open_at(path, &cfg.ffmpeg_path, position_ms)?))
+            }
+            Decoder::Native(_) => Decoder::Native(NativeDecoder::open_file_at(path, position_ms).await?),
+        };
+        Ok(())
+    }
+
+    pub async fn next_pcm_block(&mut self) -> Result<Option<PcmBlock>> {
+        match self {
+            Decoder::Ffmpeg(d) => d.next_pcm_block().await,
+            Decoder::Native(d) => d.next_pcm_block().await,
+        }
+    }
+
+    /// ICY station/title updates when the source is an internet radio stream.
+    pub fn icy(&self) -> Option<IcyWatch> {
+        match self {
+            Decoder::Ffmpeg(d) => d.icy.clone(),
+            Decoder::Native(d) => d.icy(),
+        }
+    }
+
+    /// Embedded tags of an HTTP source, delivered once they have been read from the body.
+    pub fn take_tags(&mut self) -> Option<TagsReceiver> {
+        match self {
+            Decoder::Ffmpeg(d) => d.tags.take(),
+            Decoder::Native(d) => d.take_tags(),
+        }
+    }
+
+    /// Liveness and length when the source is an HLS playlist or DASH manifest.
+    pub fn stream_info(&self) -> Option<StreamInfo> {
+        match self {
+            Decoder::Ffmpeg(d) => d.info,
+            Decoder::Native(d) => d.stream_info(),
+        }
+    }
+}
+
 pub struct FfmpegDecoder {
     child: Child,
     stdout: BufReader<ChildStdout>,
     pending: Vec<u8>,
+    icy: Option<IcyWatch>,
+    info: Option<StreamInfo>,
+    tags: Option<TagsReceiver>,
+    /// Set when the HTTP body feeding stdin fails before its end.
+    feed_error: Option<oneshot::Receiver<std::io::Error>>,
+    _feeder: Option<AbortOnDrop>,
 }
 
 impl FfmpegDecoder {
     pub fn open(path: &Path, ffmpeg_bin: &str) -> Result<Self> {
-        let mut child = Command::new(ffmpeg_bin)
-            .arg("-hide_banner")
-            .arg("-loglevel")
-            .arg("error")
-            .arg("-i")
-            .arg(path)
+        Self::open_at(path, ffmpeg_bin, 0)
+    }
+
+    /// Decodes a local file from `start_ms`, letting ffmpeg seek to it.
+    pub fn open_at(path: &Path, ffmpeg_bin: &str, start_ms: u64) -> Result<Self> {
+        let mut cmd = Command::new(ffmpeg_bin);
+        cmd.arg("-hide_banner").arg("-loglevel").arg("error");
+        if start_ms > 0 {
+            cmd.arg("-ss").arg(format!("{}.{:03}", start_ms / 1000, start_ms % 1000));
+        }
+        cmd.arg("-i").arg(path);
+        Self::spawn(cmd, ffmpeg_bin)
+    }
+
+    /// Lets ffmpeg fetch an HTTP file itself. `stream`, already open on the same
+    /// URL, is only read for its tags.
+    pub fn open_url(mut stream: HttpStream, url: &str, ffmpeg_bin: &str) -> Result<Self> {
+        let mut cmd = Command::new(ffmpeg_bin);
+        cmd.arg("-hide_banner").arg("-loglevel").arg("error").arg("-i").arg(url);
+        let mut dec = Self::spawn(cmd, ffmpeg_bin)?;
+        if let Some(tags) = stream.take_tags() {
+            let (tags_tx, tags_rx) = oneshot::channel();
+            let relay = tokio::spawn(async move {
+                let drain = async {
+                    while let Some(Ok(_)) = stream.next_chunk().await {}
+                    std::future::pending::<()>().await
+                };
+                tokio::select! {
+                    Ok(tags) = tags => { let _ = tags_tx.send(tags); }
+                    _ = drain => {}
+                }
+            });
+            dec.tags = Some(tags_rx);
+            dec._feeder = Some(AbortOnDrop(relay));
+        }
+        Ok(dec)
+    }
+
+    /// Decodes an HTTP body piped into ffmpeg's stdin as it arrives.
+    pub fn open_stream(mut stream: HttpStream, ffmpeg_bin: &str) -> Result<Self> {
+        let mut cmd = Command::new(ffmpeg_bin);
+        cmd.arg("-hide_banner").arg("-loglevel").arg("error").arg("-i").arg("pipe:0").stdin(Stdio::piped());
+        let mut dec = Self::spawn(cmd, ffmpeg_bin)?;
+        dec.icy = stream.icy();
+        dec.info = stream.info();
+        dec.tags = stream.take_tags();
+        let mut stdin = dec.child.stdin.take().ok_or_else(|| anyhow!("ffmpeg stdin not captured"))?;
+        let (error_tx, error_rx) = oneshot::channel();
+        let feeder = tokio::spawn(async move {
+            while let Some(chunk) = stream.next_chunk().await {
+                match chunk {
+                    Ok(chunk) => {
+                        if stdin.write_all(&chunk).await.is_err() {
+                            break;
+                        }
+                    }
+                    // Reported before stdin closes, so it is seen when ffmpeg's output ends.
+                    Err(e) => {
+                        let _ = error_tx.send(e);
+                        break;
+                    }
+                }
+            }
+        });
+        dec.feed_error = Some(error_rx);
+        dec._feeder = Some(AbortOnDrop(feeder));
+        Ok(dec)
+    }
+
+    fn spawn(mut cmd: Command, ffmpeg_bin: &str) -> Result<Self> {
+        let mut child = cmd
             .arg("-f")
             .arg("s16le")
             .arg("-ac")
@@ -45,7 +225,16 @@ impl FfmpegDecoder {
 
         let stdout = child.stdout.take().ok_or_else(|| anyhow!("ffmpeg stdout not captured"))?;
 
-        Ok(Self { child, stdout: BufReader::new(stdout), pending: Vec::new() })
+        Ok(Self {
+            child,
+            stdout: BufReader::new(stdout),
+            pending: Vec::new(),
+            icy: None,
+            info: None,
+            tags: None,
+            feed_error: None,
+            _feeder: None,
+        })
     }
 
     pub async fn next_pcm_block(&mut self) -> Result<Option<PcmBlock>> {
@@ -63,6 +252,9 @@ impl FfmpegDecoder {
         }
 
         if raw.is_empty() {
+            if let Some(Ok(e)) = self.feed_error.as_mut().map(|rx| rx.try_recv()) {
+                return Err(anyhow!("http source failed: {e}"));
+            }
             return Ok(None);
         }
 
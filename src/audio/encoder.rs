@@ -0,0 +1,87 @@
+use anyhow::{anyhow, Context, Result};
+use std::{path::Path, process::Stdio};
+use tokio::{
+    io::AsyncWriteExt,
+    process::{Child, ChildStdin, ChildStdout, Command},
+    sync::broadcast,
+};
+use tracing::debug;
+
+use crate::audio::{
+    format::{SOURCE_CHANNELS, SOURCE_SAMPLE_RATE},
+    frame::AudioFrame,
+};
+
+/// An ffmpeg child that reads the player's raw PCM on stdin and writes an encoded
+/// stream to stdout.
+pub struct FfmpegEncoder {
+    pub child: Child,
+    pub stdin: ChildStdin,
+    pub stdout: ChildStdout,
+}
+
+impl FfmpegEncoder {
+    /// Spawns ffmpeg with the player's PCM layout as input; `output_args` describe
+    /// the codec/muxer and must end with the output target.
+    pub fn spawn<I, S>(ffmpeg_bin: &str, output_args: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<std::ffi::OsStr>,
+    {
+        Self::spawn_in(ffmpeg_bin, None, output_args)
+    }
+
+    /// Like [`FfmpegEncoder::spawn`], but runs ffmpeg from `dir` so relative output
+    /// paths (and the names muxers write into playlists) stay relative to it.
+    pub fn spawn_in<I, S>(ffmpeg_bin: &str, dir: Option<&Path>, output_args: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<std::ffi::OsStr>,
+    {
+        let mut cmd = Command::new(ffmpeg_bin);
+        if let Some(dir) = dir {
+            cmd.current_dir(dir);
+        }
+        let mut child = cmd
+            .arg("-hide_banner")
+            .arg("-loglevel")
+            .arg("error")
+            .arg("-f")
+            .arg("s16le")
+            .arg("-ar")
+            .arg(SOURCE_SAMPLE_RATE.to_string())
+            .arg("-ac")
+            .arg(SOURCE_CHANNELS.to_string())
+            .arg("-i")
+            .arg("pipe:0")
+            .args(output_args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("spawn ffmpeg encoder using '{ffmpeg_bin}'"))?;
+        let stdin = child.stdin.take().ok_or_else(|| anyhow!("ffmpeg stdin not captured"))?;
+        let stdout = child.stdout.take().ok_or_else(|| anyhow!("ffmpeg stdout not captured"))?;
+        Ok(Self { child, stdin, stdout })
+    }
+}
+
+/// Copies PCM frames from a player subscription into an encoder's stdin until the
+/// player goes away or the encoder stops reading. Lagged frames are skipped.
+pub async fn pump_pcm(mut rx: broadcast::Receiver<AudioFrame>, mut stdin: ChildStdin) {
+    loop {
+        match rx.recv().await {
+            Ok(frame) => {
+                if stdin.write_all(&frame.pcm).await.is_err() {
+                    break;
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                debug!(lost = n, "encoder input lagged; dropped frames");
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+    let _ = stdin.shutdown().await;
+}
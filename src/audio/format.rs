@@ -0,0 +1,250 @@
+use bytes::Bytes;
+use serde::Deserialize;
+
+/// Sample rate of the PCM produced by `Player::run`.
+pub const SOURCE_SAMPLE_RATE: u32 = 48_000;
+/// Channel count of the PCM produced by `Player::run`.
+pub const SOURCE_CHANNELS: usize = 2;
+
+const MIN_SAMPLE_RATE: u32 = 8_000;
+const MAX_SAMPLE_RATE: u32 = 48_000;
+const ALLOWED_FRAME_MS: [u32; 4] = [10, 20, 40, 60];
+const FIR_TAPS: usize = 33;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SampleEncoding {
+    S16le,
+    F32le,
+}
+
+impl SampleEncoding {
+    pub fn bytes_per_sample(self) -> usize {
+        match self {
+            SampleEncoding::S16le => 2,
+            SampleEncoding::F32le => 4,
+        }
+    }
+}
+
+/// PCM layout requested by a stream subscriber.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputFormat {
+    pub encoding: SampleEncoding,
+    pub channels: usize,
+    pub sample_rate: u32,
+    pub frame_ms: u32,
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        Self {
+            encoding: SampleEncoding::S16le,
+            channels: SOURCE_CHANNELS,
+            sample_rate: SOURCE_SAMPLE_RATE,
+            frame_ms: 20,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct OutputFormatQuery {
+    #[serde(default)]
+    pub format: Option<SampleEncoding>,
+    #[serde(default)]
+    pub channels: Option<usize>,
+    #[serde(default, rename = "sampleRate")]
+    pub sample_rate: Option<u32>,
+    #[serde(default, rename = "frameMs")]
+    pub frame_ms: Option<u32>,
+}
+
+impl OutputFormat {
+    pub fn from_query(q: &OutputFormatQuery) -> Result<Self, String> {
+        let d = Self::default();
+        let fmt = Self {
+            encoding: q.format.unwrap_or(d.encoding),
+            channels: q.channels.unwrap_or(d.channels),
+            sample_rate: q.sample_rate.unwrap_or(d.sample_rate),
+            frame_ms: q.frame_ms.unwrap_or(d.frame_ms),
+        };
+        if fmt.channels != 1 && fmt.channels != 2 {
+            return Err(format!("channels must be 1 or 2, got {}", fmt.channels));
+        }
+        if !(MIN_SAMPLE_RATE..=MAX_SAMPLE_RATE).contains(&fmt.sample_rate) {
+            return Err(format!(
+                "sampleRate must be between {MIN_SAMPLE_RATE} and {MAX_SAMPLE_RATE}, got {}",
+                fmt.sample_rate
+            ));
+        }
+        if !ALLOWED_FRAME_MS.contains(&fmt.frame_ms) {
+            return Err(format!("frameMs must be one of 10, 20, 40 or 60, got {}", fmt.frame_ms));
+        }
+        Ok(fmt)
+    }
+
+    pub fn is_passthrough(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn samples_per_frame(&self) -> usize {
+        (self.sample_rate as usize * self.frame_ms as usize) / 1000
+    }
+}
+
+/// Converts the player's 48 kHz stereo s16le frames into a subscriber's requested
+/// layout. Each subscriber owns one converter, so filter and resampler state never
+/// leaks between connections.
+pub struct FormatConverter {
+    fmt: OutputFormat,
+    resampler: Option<Resampler>,
+    pending: Vec<f32>,
+}
+
+impl FormatConverter {
+    pub fn new(fmt: OutputFormat) -> Self {
+        let resampler = (fmt.sample_rate != SOURCE_SAMPLE_RATE)
+            .then(|| Resampler::new(SOURCE_SAMPLE_RATE, fmt.sample_rate, fmt.channels));
+        Self { fmt, resampler, pending: Vec::new() }
+    }
+
+    /// Feeds one source frame and returns every complete output frame now available.
+    pub fn push(&mut self, pcm: &[u8]) -> Vec<Bytes> {
+        if self.fmt.is_passthrough() {
+            return vec![Bytes::copy_from_slice(pcm)];
+        }
+
+        let mut mixed = Vec::with_capacity(pcm.len() / 2);
+        for chunk in pcm.chunks_exact(SOURCE_CHANNELS * 2) {
+            let l = i16::from_le_bytes([chunk[0], chunk[1]]) as f32 / 32768.0;
+            let r = i16::from_le_bytes([chunk[2], chunk[3]]) as f32 / 32768.0;
+            if self.fmt.channels == 1 {
+                mixed.push((l + r) * 0.5);
+            } else {
+                mixed.push(l);
+                mixed.push(r);
+            }
+        }
+
+        match self.resampler.as_mut() {
+            Some(rs) => rs.process(&mixed, &mut self.pending),
+            None => self.pending.extend_from_slice(&mixed),
+        }
+
+        let frame_len = self.fmt.samples_per_frame() * self.fmt.channels;
+        let mut out = Vec::new();
+        let mut head = 0;
+        while self.pending.len() - head >= frame_len {
+            out.push(encode_samples(&self.pending[head..head + frame_len], self.fmt.encoding));
+            head += frame_len;
+        }
+        self.pending.drain(..head);
+        out
+    }
+}
+
+pub fn encode_samples(samples: &[f32], encoding: SampleEncoding) -> Bytes {
+    let mut buf = Vec::with_capacity(samples.len() * encoding.bytes_per_sample());
+    match encoding {
+        SampleEncoding::S16le => {
+            for s in samples {
+                let v = (s * 32767.0).clamp(-32768.0, 32767.0) as i16;
+                buf.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+        SampleEncoding::F32le => {
+            for s in samples {
+                buf.extend_from_slice(&s.clamp(-1.0, 1.0).to_le_bytes());
+            }
+        }
+    }
+    Bytes::from(buf)
+}
+
+/// Streaming linear-interpolation resampler for interleaved audio. When
+/// downsampling, input is first passed through a windowed-sinc low-pass so content
+/// above the new Nyquist frequency does not alias.
+pub struct Resampler {
+    channels: usize,
+    step: f64,
+    pos: f64,
+    lowpass: Option<Vec<f32>>,
+    history: Vec<f32>,
+    prev: Vec<f32>,
+}
+
+impl Resampler {
+    pub fn new(from_rate: u32, to_rate: u32, channels: usize) -> Self {
+        let lowpass = (to_rate < from_rate).then(|| lowpass_taps(0.45 * to_rate as f32 / from_rate as f32));
+        Self {
+            channels,
+            step: from_rate as f64 / to_rate as f64,
+            pos: 0.0,
+            lowpass,
+            history: vec![0.0; FIR_TAPS * channels],
+            prev: vec![0.0; channels],
+        }
+    }
+
+    /// Resamples interleaved `input` and appends the result to `out`.
+    pub fn process(&mut self, input: &[f32], out: &mut Vec<f32>) {
+        let ch = self.channels;
+        let filtered = match &self.lowpass {
+            Some(taps) => {
+                let mut y = Vec::with_capacity(input.len());
+                for frame in input.chunks_exact(ch) {
+                    self.history.drain(..ch);
+                    self.history.extend_from_slice(frame);
+                    for c in 0..ch {
+                        let mut acc = 0.0;
+                        for (t, coeff) in taps.iter().enumerate() {
+                            acc += coeff * self.history[t * ch + c];
+                        }
+                        y.push(acc);
+                    }
+                }
+                y
+            }
+            None => input.to_vec(),
+        };
+
+        // `pos` is measured relative to `prev` (index -1) so interpolation can span
+        // the boundary between successive blocks.
+        let frames = filtered.len() / ch;
+        while self.pos < frames as f64 {
+            let i = self.pos.floor() as isize - 1;
+            let frac = (self.pos - self.pos.floor()) as f32;
+            for c in 0..ch {
+                let a = if i < 0 { self.prev[c] } else { filtered[i as usize * ch + c] };
+                let b = filtered[(i + 1) as usize * ch + c];
+                out.push(a + (b - a) * frac);
+            }
+            self.pos += self.step;
+        }
+        self.pos -= frames as f64;
+        if frames > 0 {
+            self.prev.copy_from_slice(&filtered[(frames - 1) * ch..frames * ch]);
+        }
+    }
+}
+
+fn lowpass_taps(cutoff: f32) -> Vec<f32> {
+    let m = (FIR_TAPS - 1) as f32;
+    let mut taps: Vec<f32> = (0..FIR_TAPS)
+        .map(|n| {
+            let x = n as f32 - m / 2.0;
+            let sinc = if x == 0.0 {
+                2.0 * cutoff
+            } else {
+                (2.0 * std::f32::consts::PI * cutoff * x).sin() / (std::f32::consts::PI * x)
+            };
+            let window = 0.54 - 0.46 * (2.0 * std::f32::consts::PI * n as f32 / m).cos();
+            sinc * window
+        })
+        .collect();
+    let sum: f32 = taps.iter().sum();
+    for t in &mut taps {
+        *t /= sum;
+    }
+    taps
+}
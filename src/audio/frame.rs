@@ -0,0 +1,65 @@
+use bytes::{BufMut, Bytes, BytesMut};
+use std::sync::Arc;
+
+/// First frame of a track.
+pub const FLAG_TRACK_START: u8 = 0x01;
+/// End of a track (natural end, skip or trim), sent on a frame with no audio
+/// after the track's last one.
+pub const FLAG_TRACK_END: u8 = 0x02;
+/// Frames before this one were lost or output stopped for a while (pause, lag).
+pub const FLAG_DISCONTINUITY: u8 = 0x04;
+
+const HEADER_VERSION: u8 = 1;
+/// Fixed part of the framed header, before the track id.
+const HEADER_FIXED_LEN: usize = 21;
+
+/// One 20 ms block of player output (48 kHz stereo s16le) with its position in
+/// the player's output stream. Track end markers carry no audio and share `seq`
+/// with the frame that follows them.
+#[derive(Debug, Clone)]
+pub struct AudioFrame {
+    /// Increments by one per audio frame over the player's lifetime.
+    pub seq: u64,
+    /// Samples per channel (at 48 kHz) emitted before this frame.
+    pub timestamp: u64,
+    pub track_id: Arc<str>,
+    pub flags: u8,
+    pub pcm: Bytes,
+}
+
+/// Something carried on a player channel whose payload is the audio itself.
+pub trait Payload: Clone + Send + 'static {
+    fn payload(&self) -> &[u8];
+}
+
+impl Payload for Bytes {
+    fn payload(&self) -> &[u8] {
+        self
+    }
+}
+
+impl Payload for AudioFrame {
+    fn payload(&self) -> &[u8] {
+        &self.pcm
+    }
+}
+
+impl AudioFrame {
+    /// Builds a framed websocket message: the header described in the README
+    /// followed by `payload` (this frame's audio, possibly converted).
+    pub fn framed(&self, extra_flags: u8, payload: &[u8]) -> Bytes {
+        let id = self.track_id.as_bytes();
+        let id = &id[..id.len().min(u8::MAX as usize)];
+        let header_len = HEADER_FIXED_LEN + id.len();
+        let mut out = BytesMut::with_capacity(header_len + payload.len());
+        out.put_u8(HEADER_VERSION);
+        out.put_u8(self.flags | extra_flags);
+        out.put_u16_le(header_len as u16);
+        out.put_u64_le(self.seq);
+        out.put_u64_le(self.timestamp);
+        out.put_u8(id.len() as u8);
+        out.put_slice(id);
+        out.put_slice(payload);
+        out.freeze()
+    }
+}
@@ -0,0 +1,218 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{
+    process::Child,
+    sync::{broadcast, Mutex},
+};
+use tracing::{debug, info};
+
+use crate::audio::{
+    encoder::{pump_pcm, FfmpegEncoder},
+    frame::AudioFrame,
+};
+use crate::config::EffectiveConfig;
+
+const PLAYLIST_NAME: &str = "index.m3u8";
+const INIT_SEGMENT_NAME: &str = "init.mp4";
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(1);
+
+pub type HlsSlot = Arc<Mutex<Option<Arc<HlsPackager>>>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HlsCodec {
+    Aac,
+    Opus,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct HlsSettings {
+    pub codec: HlsCodec,
+    pub bitrate: u32,
+    pub segment_secs: u32,
+    pub playlist_size: u32,
+    pub idle_timeout: Duration,
+}
+
+impl HlsSettings {
+    pub fn from_config(cfg: &EffectiveConfig) -> Self {
+        Self {
+            codec: cfg.hls_codec,
+            bitrate: cfg.hls_bitrate,
+            segment_secs: cfg.hls_segment_secs.max(1),
+            playlist_size: cfg.hls_playlist_size.max(2),
+            idle_timeout: Duration::from_secs(cfg.hls_idle_timeout_secs.max(5)),
+        }
+    }
+
+    fn ffmpeg_args(&self) -> Vec<String> {
+        let mut args: Vec<String> = match self.codec {
+            HlsCodec::Aac => vec!["-c:a".into(), "aac".into()],
+            HlsCodec::Opus => vec!["-c:a".into(), "libopus".into()],
+        };
+        args.extend([
+            "-b:a".into(),
+            self.bitrate.to_string(),
+            "-f".into(),
+            "hls".into(),
+            "-hls_time".into(),
+            self.segment_secs.to_string(),
+            "-hls_list_size".into(),
+            self.playlist_size.to_string(),
+            "-hls_flags".into(),
+            "delete_segments+independent_segments+temp_file".into(),
+        ]);
+        match self.codec {
+            // Opus is only valid in HLS inside fragmented MP4.
+            HlsCodec::Opus => args.extend([
+                "-hls_segment_type".into(),
+                "fmp4".into(),
+                "-hls_fmp4_init_filename".into(),
+                INIT_SEGMENT_NAME.into(),
+                "-hls_segment_filename".into(),
+                "seg_%05d.m4s".into(),
+            ]),
+            HlsCodec::Aac => {
+                args.extend(["-hls_segment_filename".into(), "seg_%05d.ts".into()]);
+            }
+        }
+        args.push(PLAYLIST_NAME.into());
+        args
+    }
+}
+
+/// A running ffmpeg HLS muxer writing a rolling playlist and segments into a
+/// private temp directory.
+pub struct HlsPackager {
+    dir: tempfile::TempDir,
+    child: Mutex<Child>,
+    last_access: std::sync::Mutex<Instant>,
+    settings: HlsSettings,
+}
+
+impl HlsPackager {
+    fn touch(&self) {
+        if let Ok(mut t) = self.last_access.lock() {
+            *t = Instant::now();
+        }
+    }
+
+    fn idle_for(&self) -> Duration {
+        self.last_access.lock().map(|t| t.elapsed()).unwrap_or_default()
+    }
+
+    /// Returns the current playlist, waiting for the first one to be written for up
+    /// to a few segment durations after start-up.
+    pub async fn playlist(&self) -> Option<String> {
+        self.touch();
+        let deadline = Instant::now() + Duration::from_secs(self.settings.segment_secs as u64 * 3 + 2);
+        let path = self.dir.path().join(PLAYLIST_NAME);
+        loop {
+            if let Ok(s) = tokio::fs::read_to_string(&path).await {
+                return Some(s);
+            }
+            if Instant::now() >= deadline {
+                return None;
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+    }
+
+    /// Reads a segment (or the fMP4 init segment) by file name.
+    pub async fn segment(&self, name: &str) -> Option<Vec<u8>> {
+        self.touch();
+        if !is_valid_segment_name(name) {
+            return None;
+        }
+        tokio::fs::read(self.dir.path().join(name)).await.ok()
+    }
+}
+
+fn is_valid_segment_name(name: &str) -> bool {
+    name != PLAYLIST_NAME
+        && !name.starts_with('.')
+        && (name.ends_with(".ts") || name.ends_with(".m4s") || name == INIT_SEGMENT_NAME)
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+/// Appends `?token=` to every segment URI in `playlist`, so players that were
+/// given a tokenized playlist URL can fetch the segments too.
+pub fn with_token(playlist: &str, token: &str) -> String {
+    let query =
+        format!("?token={}", url::form_urlencoded::byte_serialize(token.as_bytes()).collect::<String>());
+    let init = format!("URI=\"{INIT_SEGMENT_NAME}\"");
+    playlist
+        .lines()
+        .map(|line| {
+            if line.starts_with("#EXT-X-MAP:") {
+                line.replace(&init, &format!("URI=\"{INIT_SEGMENT_NAME}{query}\""))
+            } else if !line.is_empty() && !line.starts_with('#') {
+                format!("{line}{query}")
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        + "\n"
+}
+
+pub fn segment_content_type(name: &str) -> &'static str {
+    if name.ends_with(".ts") {
+        "video/mp2t"
+    } else {
+        "audio/mp4"
+    }
+}
+
+/// Returns the player's packager, starting one on first use. A watchdog tears it
+/// down (and removes its directory) once nobody has fetched the playlist or a
+/// segment for `idle_timeout`.
+pub async fn ensure_started(
+    slot: &HlsSlot,
+    ffmpeg_bin: &str,
+    settings: HlsSettings,
+    pcm_rx: broadcast::Receiver<AudioFrame>,
+) -> Result<Arc<HlsPackager>> {
+    let mut guard = slot.lock().await;
+    if let Some(p) = guard.as_ref() {
+        return Ok(p.clone());
+    }
+
+    let dir = tempfile::Builder::new().prefix("resonix_hls_").tempdir().context("create hls directory")?;
+    let FfmpegEncoder { child, stdin, .. } =
+        FfmpegEncoder::spawn_in(ffmpeg_bin, Some(dir.path()), settings.ffmpeg_args())?;
+    tokio::spawn(pump_pcm(pcm_rx, stdin));
+    let packager = Arc::new(HlsPackager {
+        dir,
+        child: Mutex::new(child),
+        last_access: std::sync::Mutex::new(Instant::now()),
+        settings,
+    });
+    *guard = Some(packager.clone());
+    info!(dir = %packager.dir.path().display(), "HLS packager started");
+
+    let slot = slot.clone();
+    let watched = packager.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(WATCHDOG_INTERVAL).await;
+            let exited = matches!(watched.child.lock().await.try_wait(), Ok(Some(_)) | Err(_));
+            if exited || watched.idle_for() >= watched.settings.idle_timeout {
+                break;
+            }
+        }
+        let _ = watched.child.lock().await.start_kill();
+        let mut guard = slot.lock().await;
+        if guard.as_ref().is_some_and(|cur| Arc::ptr_eq(cur, &watched)) {
+            *guard = None;
+        }
+        debug!("HLS packager stopped");
+    });
+
+    Ok(packager)
+}
@@ -0,0 +1,360 @@
+use anyhow::{anyhow, Context, Result};
+use bytes::{Buf, Bytes, BytesMut};
+use futures::StreamExt;
+use std::{io::Read, sync::Arc};
+use tokio::sync::{mpsc, watch, Semaphore};
+use tracing::debug;
+
+use crate::audio::icy::{IcyDemuxer, IcyMetadata, IcyPart, IcyWatch};
+use crate::audio::segments::{SegmentedSource, StreamInfo};
+use crate::audio::tags::{TagFeed, TagsReceiver};
+use crate::config::EffectiveConfig;
+use crate::utils::task::AbortOnDrop;
+
+/// Manifests larger than this are rejected rather than buffered.
+const MAX_MANIFEST_BYTES: usize = 4 * 1024 * 1024;
+/// Bytes looked at to tell where an MP4 file keeps its `moov` box.
+const MP4_PROBE_BYTES: usize = 4096;
+
+/// How HTTP sources are read, from the `[http]` config section.
+#[derive(Debug, Clone, Copy)]
+pub struct HttpSettings {
+    pub read_ahead: usize,
+    pub segment_retries: u32,
+    pub live_edge_segments: usize,
+}
+
+impl HttpSettings {
+    pub fn from_config(cfg: &EffectiveConfig) -> Self {
+        Self {
+            read_ahead: cfg.http_read_ahead_bytes,
+            segment_retries: cfg.http_segment_retries,
+            live_edge_segments: cfg.http_live_edge_segments,
+        }
+    }
+}
+
+enum Item {
+    Data(Bytes),
+    /// Takes effect once the audio queued before it has been read.
+    StreamTitle(String),
+    Failed(std::io::Error),
+}
+
+/// An HTTP response body read ahead of the decoder. A background task keeps up to
+/// `read_ahead` bytes buffered so decoding can start with the first chunk while
+/// the rest is still arriving; live streams are read for as long as they last.
+/// ICY metadata is requested and stripped from the body when the server offers it;
+/// other bodies have their embedded tags read from the first bytes that arrive.
+/// HLS playlists and DASH manifests are followed instead of being returned, and
+/// their segments are read back to back as one body.
+pub struct HttpStream {
+    rx: mpsc::UnboundedReceiver<Item>,
+    budget: Arc<Semaphore>,
+    current: Bytes,
+    icy: Option<watch::Sender<IcyMetadata>>,
+    info: Option<StreamInfo>,
+    tags: Option<TagsReceiver>,
+    file: bool,
+    read_ahead: usize,
+    _task: AbortOnDrop,
+}
+
+impl HttpStream {
+    pub async fn open(url: &str, settings: &HttpSettings) -> Result<Self> {
+        let client = reqwest::Client::new();
+        let resp = client
+            .get(url)
+            .header("Icy-MetaData", "1")
+            .send()
+            .await
+            .context("http get")?
+            .error_for_status()
+            .context("bad status")?;
+        let read_ahead = settings.read_ahead.clamp(1, u32::MAX as usize);
+        let budget = Arc::new(Semaphore::new(read_ahead));
+        let (tx, rx) = mpsc::unbounded_channel();
+        let sink = BodySink { tx, budget: budget.clone(), read_ahead };
+
+        if is_manifest_response(&resp) {
+            let manifest_url = resp.url().clone();
+            if resp.content_length().is_some_and(|n| n as usize > MAX_MANIFEST_BYTES) {
+                return Err(anyhow!("manifest too large"));
+            }
+            let text = resp.text().await.context("read manifest")?;
+            let source = SegmentedSource::open(&client, manifest_url, &text, settings)
+                .await?
+                .ok_or_else(|| anyhow!("not an HLS playlist or DASH manifest"))?;
+            let info = source.info();
+            let task = tokio::spawn(source.run(client, sink, *settings));
+            return Ok(Self {
+                rx,
+                budget,
+                current: Bytes::new(),
+                icy: None,
+                info: Some(info),
+                tags: None,
+                file: false,
+                read_ahead,
+                _task: AbortOnDrop(task),
+            });
+        }
+
+        let header = |name: &str| resp.headers().get(name).and_then(|v| v.to_str().ok()).map(str::trim);
+        let metaint = header("icy-metaint").and_then(|v| v.parse::<usize>().ok()).filter(|&n| n > 0);
+        let station = header("icy-name").filter(|s| !s.is_empty()).map(str::to_string);
+        let icy = metaint.map(|_| watch::Sender::new(IcyMetadata { station, stream_title: None }));
+        let file = metaint.is_none() && resp.content_length().is_some();
+        // Radio streams carry their titles in band instead of in file tags.
+        let (probe, tags) = match metaint {
+            Some(_) => (None, None),
+            None => {
+                let (feed, rx) = TagFeed::spawn();
+                (Some(feed), Some(rx))
+            }
+        };
+        let task = tokio::spawn(pump(resp, metaint.map(IcyDemuxer::new), probe, sink));
+        Ok(Self {
+            rx,
+            budget,
+            current: Bytes::new(),
+            icy,
+            info: None,
+            tags,
+            file,
+            read_ahead,
+            _task: AbortOnDrop(task),
+        })
+    }
+
+    /// Liveness and length announced by an HLS playlist or DASH manifest.
+    pub fn info(&self) -> Option<StreamInfo> {
+        self.info
+    }
+
+    /// Whether the body is a file of known length, not a radio stream, manifest or
+    /// body of unknown length.
+    pub fn is_file(&self) -> bool {
+        self.file
+    }
+
+    /// Whether the body is an MP4 file with its `moov` box after the media data,
+    /// which a decoder reading it front to back cannot open. Looks at the start of
+    /// the body without consuming it.
+    pub async fn needs_seeking(&mut self) -> std::io::Result<bool> {
+        if !self.file {
+            return Ok(false);
+        }
+        let head = self.peek(MP4_PROBE_BYTES.min(self.read_ahead)).await?;
+        Ok(mp4_moov_after_mdat(head))
+    }
+
+    /// Buffers at least `len` bytes of the body (fewer if it ends first) and returns
+    /// them; they are still returned by the next read.
+    async fn peek(&mut self, len: usize) -> std::io::Result<&[u8]> {
+        while self.current.len() < len {
+            let Some(item) = self.rx.recv().await else { break };
+            if let Some(res) = self.accept(item) {
+                let mut joined = BytesMut::from(&self.current[..]);
+                joined.extend_from_slice(&res?);
+                self.current = joined.freeze();
+            }
+        }
+        Ok(&self.current)
+    }
+
+    /// Station and title updates, if the server sends ICY metadata.
+    pub fn icy(&self) -> Option<IcyWatch> {
+        self.icy.as_ref().map(|tx| tx.subscribe())
+    }
+
+    /// Title, artist, cover art etc. embedded in the body, once they have been read.
+    /// Can be taken once.
+    pub fn take_tags(&mut self) -> Option<TagsReceiver> {
+        self.tags.take()
+    }
+
+    /// Returns the next chunk of the body, or `None` once it has been read fully.
+    pub async fn next_chunk(&mut self) -> Option<std::io::Result<Bytes>> {
+        if !self.current.is_empty() {
+            let chunk = std::mem::take(&mut self.current);
+            self.budget.add_permits(chunk.len());
+            return Some(Ok(chunk));
+        }
+        loop {
+            let item = self.rx.recv().await?;
+            if let Some(res) = self.accept(item) {
+                if let Ok(c) = &res {
+                    self.budget.add_permits(c.len());
+                }
+                return Some(res);
+            }
+        }
+    }
+
+    /// Applies titles as they come up; returns data and errors.
+    fn accept(&mut self, item: Item) -> Option<std::io::Result<Bytes>> {
+        match item {
+            Item::Data(c) => Some(Ok(c)),
+            Item::Failed(e) => Some(Err(e)),
+            Item::StreamTitle(title) => {
+                if let Some(tx) = &self.icy {
+                    tx.send_if_modified(|m| {
+                        let changed = m.stream_title.as_deref() != Some(title.as_str());
+                        m.stream_title = Some(title);
+                        changed
+                    });
+                }
+                None
+            }
+        }
+    }
+
+    /// Wraps the stream in a blocking reader for decoders running on their own thread.
+    pub fn into_blocking_reader(self) -> BlockingHttpReader {
+        BlockingHttpReader(self)
+    }
+}
+
+/// Walks the top-level boxes at the start of an MP4 file. A layout that cannot be
+/// told from `head`, e.g. behind a large `free` box, counts as `moov` after `mdat`.
+fn mp4_moov_after_mdat(head: &[u8]) -> bool {
+    if head.get(4..8) != Some(b"ftyp") {
+        return false;
+    }
+    let mut pos = 0usize;
+    while let Some(header) = head.get(pos..pos + 8) {
+        match &header[4..8] {
+            b"moov" => return false,
+            b"mdat" => return true,
+            _ => {}
+        }
+        let size = match u32::from_be_bytes([header[0], header[1], header[2], header[3]]) {
+            1 => match head.get(pos + 8..pos + 16) {
+                Some(large) => u64::from_be_bytes(large.try_into().unwrap_or_default()),
+                None => break,
+            },
+            n => n as u64,
+        };
+        if size < 8 {
+            break;
+        }
+        pos = match usize::try_from(size).ok().and_then(|n| pos.checked_add(n)) {
+            Some(p) => p,
+            None => break,
+        };
+    }
+    true
+}
+
+fn is_manifest_response(resp: &reqwest::Response) -> bool {
+    let path = resp.url().path().to_ascii_lowercase();
+    let content_type = resp
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    path.ends_with(".m3u8")
+        || path.ends_with(".mpd")
+        || content_type.contains("mpegurl")
+        || content_type.contains("dash+xml")
+}
+
+/// The producing half of an [`HttpStream`].
+pub struct BodySink {
+    tx: mpsc::UnboundedSender<Item>,
+    budget: Arc<Semaphore>,
+    read_ahead: usize,
+}
+
+impl BodySink {
+    /// Queues `chunk`, waiting for the consumer whenever `read_ahead` bytes are
+    /// already buffered. Returns `false` once the stream has been dropped.
+    pub async fn data(&self, mut chunk: Bytes) -> bool {
+        // Pieces never exceed the budget, so each can always be admitted.
+        while !chunk.is_empty() {
+            let piece = chunk.split_to(chunk.len().min(self.read_ahead));
+            let Ok(permit) = self.budget.acquire_many(piece.len() as u32).await else {
+                return false;
+            };
+            permit.forget();
+            if self.tx.send(Item::Data(piece)).is_err() {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn stream_title(&self, title: String) -> bool {
+        self.tx.send(Item::StreamTitle(title)).is_ok()
+    }
+
+    pub fn fail(&self, e: std::io::Error) {
+        let _ = self.tx.send(Item::Failed(e));
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+}
+
+/// Moves the body into the sink, splitting out ICY metadata or reading tags on the way.
+async fn pump(
+    resp: reqwest::Response,
+    mut demuxer: Option<IcyDemuxer>,
+    mut probe: Option<TagFeed>,
+    sink: BodySink,
+) {
+    let mut body = resp.bytes_stream();
+    while let Some(chunk) = body.next().await {
+        let chunk = match chunk {
+            Ok(c) => c,
+            Err(e) => {
+                sink.fail(std::io::Error::other(e));
+                return;
+            }
+        };
+        if probe.as_mut().is_some_and(|p| !p.push(&chunk)) {
+            probe = None;
+        }
+        let parts = match demuxer.as_mut() {
+            Some(d) => d.push(chunk),
+            None => vec![IcyPart::Audio(chunk)],
+        };
+        for part in parts {
+            let sent = match part {
+                IcyPart::Audio(c) => sink.data(c).await,
+                IcyPart::StreamTitle(t) => sink.stream_title(t),
+            };
+            if !sent {
+                return;
+            }
+        }
+    }
+    if let Some(p) = probe {
+        p.finish();
+    }
+    debug!("http source finished");
+}
+
+pub struct BlockingHttpReader(HttpStream);
+
+impl Read for BlockingHttpReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let s = &mut self.0;
+        while s.current.is_empty() {
+            let Some(item) = s.rx.blocking_recv() else {
+                return Ok(0);
+            };
+            if let Some(res) = s.accept(item) {
+                s.current = res?;
+            }
+        }
+        let n = buf.len().min(s.current.len());
+        buf[..n].copy_from_slice(&s.current[..n]);
+        s.current.advance(n);
+        s.budget.add_permits(n);
+        Ok(n)
+    }
+}
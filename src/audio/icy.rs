@@ -0,0 +1,95 @@
+use bytes::{Buf, Bytes, BytesMut};
+use serde::Serialize;
+use tokio::sync::watch;
+
+/// Station details and the current title of a Shoutcast/Icecast stream.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IcyMetadata {
+    /// `icy-name` response header.
+    pub station: Option<String>,
+    /// Latest `StreamTitle` from the in-band metadata.
+    pub stream_title: Option<String>,
+}
+
+impl IcyMetadata {
+    /// Splits the stream title into `(author, title)` using the usual
+    /// `Artist - Title` convention; falls back to the station as author.
+    pub fn author_and_title(&self) -> Option<(String, String)> {
+        let raw = self.stream_title.as_deref()?.trim();
+        if raw.is_empty() {
+            return None;
+        }
+        match raw.split_once(" - ") {
+            Some((a, t)) if !a.trim().is_empty() && !t.trim().is_empty() => {
+                Some((a.trim().to_string(), t.trim().to_string()))
+            }
+            _ => Some((self.station.clone().unwrap_or_default(), raw.to_string())),
+        }
+    }
+}
+
+pub type IcyWatch = watch::Receiver<IcyMetadata>;
+
+/// One piece of an ICY body.
+pub enum IcyPart {
+    Audio(Bytes),
+    StreamTitle(String),
+}
+
+/// Separates the metadata blocks that an ICY server interleaves with the audio
+/// every `metaint` bytes (one length byte counting 16-byte units, then the text).
+pub struct IcyDemuxer {
+    metaint: usize,
+    audio_left: usize,
+    meta_len: Option<usize>,
+    meta: BytesMut,
+}
+
+impl IcyDemuxer {
+    pub fn new(metaint: usize) -> Self {
+        Self { metaint, audio_left: metaint, meta_len: None, meta: BytesMut::new() }
+    }
+
+    /// Splits `chunk` into audio and any titles completed within it, in stream order.
+    pub fn push(&mut self, mut chunk: Bytes) -> Vec<IcyPart> {
+        let mut parts = Vec::new();
+        while chunk.has_remaining() {
+            if self.audio_left > 0 {
+                let n = self.audio_left.min(chunk.len());
+                parts.push(IcyPart::Audio(chunk.split_to(n)));
+                self.audio_left -= n;
+                continue;
+            }
+            let len = match self.meta_len {
+                Some(len) => len,
+                None => {
+                    let len = chunk.get_u8() as usize * 16;
+                    self.meta_len = Some(len);
+                    len
+                }
+            };
+            let n = (len - self.meta.len()).min(chunk.len());
+            self.meta.extend_from_slice(&chunk.split_to(n));
+            if self.meta.len() == len {
+                let text = String::from_utf8_lossy(&self.meta);
+                if let Some(title) = parse_stream_title(text.trim_end_matches('\0')) {
+                    parts.push(IcyPart::StreamTitle(title));
+                }
+                self.meta.clear();
+                self.meta_len = None;
+                self.audio_left = self.metaint;
+            }
+        }
+        parts
+    }
+}
+
+/// Extracts `StreamTitle` from a block like `StreamTitle='Artist - Song';StreamUrl='';`.
+/// Titles may contain quotes, so the value runs up to the `';` before the next key.
+fn parse_stream_title(meta: &str) -> Option<String> {
+    let start = meta.find("StreamTitle='")? + "StreamTitle='".len();
+    let rest = &meta[start..];
+    let end = rest.find("';Stream").or_else(|| rest.rfind("';")).or_else(|| rest.rfind('\''))?;
+    Some(rest[..end].to_string())
+}
@@ -0,0 +1,176 @@
+use serde::Serialize;
+use std::path::Path;
+
+/// Where a track's lyrics came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LyricsSource {
+    /// LRC text sent with the enqueue request.
+    Request,
+    /// An `.lrc` file next to the track.
+    Sidecar,
+    /// SYLT/USLT frames, a `LYRICS` comment or an MP4 `©lyr` atom.
+    Embedded,
+}
+
+/// A track's lyrics. Synced lyrics have one timed entry per line; unsynced ones
+/// only have the text.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Lyrics {
+    pub source: LyricsSource,
+    pub synced: bool,
+    pub text: String,
+    pub lines: Vec<LyricsLine>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LyricsLine {
+    pub time_ms: u64,
+    pub text: String,
+}
+
+impl Lyrics {
+    /// Parses LRC text (`[mm:ss.xx]line`); text without any timestamps becomes
+    /// unsynced lyrics. Returns `None` if there is nothing to show.
+    pub fn from_lrc(text: &str, source: LyricsSource) -> Option<Self> {
+        let mut offset_ms = 0i64;
+        let mut lines = Vec::new();
+        let mut plain = Vec::new();
+        for raw in text.lines() {
+            let mut rest = raw.trim();
+            let mut times = Vec::new();
+            let mut id_tags = false;
+            while let Some(tag_end) = rest.strip_prefix('[').and_then(|r| r.find(']')) {
+                let tag = &rest[1..=tag_end];
+                if let Some(t) = parse_timestamp(tag) {
+                    times.push(t);
+                } else if let Some(v) = tag.strip_prefix("offset:") {
+                    offset_ms = v.trim().parse().unwrap_or(0);
+                    id_tags = true;
+                } else if is_id_tag(tag) {
+                    // `[ar:...]`, `[ti:...]` and the like carry no lyrics.
+                    id_tags = true;
+                } else {
+                    // Plain text in brackets, such as `[Chorus]`.
+                    break;
+                }
+                rest = rest[tag_end + 2..].trim_start();
+            }
+            let line = strip_word_times(rest);
+            if times.is_empty() {
+                if !(id_tags && rest.is_empty()) {
+                    plain.push(line);
+                }
+                continue;
+            }
+            for t in times {
+                // A positive offset makes the lyrics appear earlier.
+                let time_ms = (t as i64 - offset_ms).max(0) as u64;
+                lines.push(LyricsLine { time_ms, text: line.clone() });
+            }
+        }
+        if lines.is_empty() {
+            let text = plain.join("\n").trim().to_string();
+            return (!text.is_empty()).then(|| Lyrics::unsynced(text, source));
+        }
+        lines.sort_by_key(|l| l.time_ms);
+        Some(Lyrics::synced(lines, source))
+    }
+
+    /// Builds synced lyrics from `(milliseconds, text)` pairs as stored in an ID3v2
+    /// SYLT frame. Entries starting with a line break begin a new line; without any,
+    /// every entry is a line of its own.
+    pub fn from_timed_pieces(pieces: &[(u32, String)], source: LyricsSource) -> Option<Self> {
+        let by_breaks = pieces.iter().skip(1).any(|(_, t)| t.starts_with(['\n', '\r']));
+        let mut lines: Vec<LyricsLine> = Vec::new();
+        for (time, piece) in pieces {
+            let starts_line = !by_breaks || lines.is_empty() || piece.starts_with(['\n', '\r']);
+            let piece = piece.trim_start_matches(['\n', '\r']);
+            match lines.last_mut() {
+                Some(last) if !starts_line => last.text.push_str(piece),
+                _ => lines.push(LyricsLine { time_ms: *time as u64, text: piece.to_string() }),
+            }
+        }
+        for line in &mut lines {
+            line.text = line.text.trim().to_string();
+        }
+        if lines.iter().all(|l| l.text.is_empty()) {
+            return None;
+        }
+        lines.sort_by_key(|l| l.time_ms);
+        Some(Lyrics::synced(lines, source))
+    }
+
+    /// Reads the `.lrc` file sharing `track`'s name, if there is one. Blocking.
+    pub fn from_sidecar(track: &Path) -> Option<Self> {
+        ["lrc", "LRC"].iter().find_map(|ext| {
+            let bytes = std::fs::read(track.with_extension(ext)).ok()?;
+            Lyrics::from_lrc(&String::from_utf8_lossy(&bytes), LyricsSource::Sidecar)
+        })
+    }
+
+    fn synced(lines: Vec<LyricsLine>, source: LyricsSource) -> Self {
+        let text = lines.iter().map(|l| l.text.as_str()).collect::<Vec<_>>().join("\n");
+        Self { source, synced: true, text, lines }
+    }
+
+    fn unsynced(text: String, source: LyricsSource) -> Self {
+        Self { source, synced: false, text, lines: Vec::new() }
+    }
+
+    /// Index of the line being sung at `position_ms`.
+    pub fn line_at(&self, position_ms: u64) -> Option<usize> {
+        if !self.synced {
+            return None;
+        }
+        self.lines.partition_point(|l| l.time_ms <= position_ms).checked_sub(1)
+    }
+}
+
+/// `mm:ss`, `mm:ss.xx`, `mm:ss.xxx` or `mm:ss:xx` to milliseconds.
+fn parse_timestamp(tag: &str) -> Option<u64> {
+    let (min, rest) = tag.trim().split_once(':')?;
+    let (sec, frac) = match rest.split_once(['.', ':']) {
+        Some((s, f)) => (s, f),
+        None => (rest, ""),
+    };
+    let min: u64 = min.parse().ok()?;
+    let sec: u64 = sec.parse().ok()?;
+    if sec >= 60 || !frac.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let frac_ms = match frac.len() {
+        0 => 0,
+        1 => frac.parse::<u64>().ok()? * 100,
+        2 => frac.parse::<u64>().ok()? * 10,
+        _ => frac[..3].parse().ok()?,
+    };
+    Some(min * 60_000 + sec * 1000 + frac_ms)
+}
+
+fn is_id_tag(tag: &str) -> bool {
+    tag.split_once(':')
+        .is_some_and(|(key, _)| !key.is_empty() && key.chars().all(|c| c.is_ascii_alphabetic() || c == '#'))
+}
+
+/// Drops the per-word `<mm:ss.xx>` times of enhanced LRC.
+fn strip_word_times(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(start) = rest.find('<') {
+        match rest[start..].find('>') {
+            Some(len) if parse_timestamp(&rest[start + 1..start + len]).is_some() => {
+                out.push_str(&rest[..start]);
+                rest = &rest[start + len + 1..];
+            }
+            _ => {
+                out.push_str(&rest[..=start]);
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out.trim().to_string()
+}
@@ -0,0 +1,211 @@
+use anyhow::{bail, Result};
+
+use crate::audio::segments::ByteRange;
+
+/// A parsed HLS playlist: either a list of variants or the segments of one.
+pub enum Playlist {
+    Master(MasterPlaylist),
+    Media(MediaPlaylist),
+}
+
+pub struct MasterPlaylist {
+    pub variants: Vec<Variant>,
+    /// `#EXT-X-MEDIA:TYPE=AUDIO` renditions that have their own playlist.
+    pub audio: Vec<Rendition>,
+}
+
+pub struct Variant {
+    pub uri: String,
+    pub bandwidth: u64,
+    pub codecs: Option<String>,
+    pub audio_group: Option<String>,
+}
+
+pub struct Rendition {
+    pub group_id: String,
+    pub uri: String,
+    pub default: bool,
+}
+
+#[derive(Clone)]
+pub struct MediaPlaylist {
+    pub target_duration: f64,
+    pub media_sequence: u64,
+    pub segments: Vec<Segment>,
+    /// `#EXT-X-ENDLIST` was seen: the playlist is complete (VOD or a finished event).
+    pub ended: bool,
+}
+
+#[derive(Clone)]
+pub struct Segment {
+    pub uri: String,
+    pub duration: f64,
+    pub range: Option<ByteRange>,
+    /// `#EXT-X-MAP` in effect for this segment (fMP4 / CMAF init section).
+    pub init: Option<InitSection>,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct InitSection {
+    pub uri: String,
+    pub range: Option<ByteRange>,
+}
+
+impl MediaPlaylist {
+    pub fn duration_ms(&self) -> u64 {
+        (self.segments.iter().map(|s| s.duration).sum::<f64>() * 1000.0) as u64
+    }
+}
+
+impl MasterPlaylist {
+    /// Picks the playlist to play: an audio-only variant (highest bandwidth) if
+    /// there is one, else the default audio rendition, else the cheapest variant
+    /// so as little video as possible is downloaded.
+    pub fn audio_uri(&self) -> Option<&str> {
+        let audio_only = self
+            .variants
+            .iter()
+            .filter(|v| v.codecs.as_deref().is_some_and(is_audio_only))
+            .max_by_key(|v| v.bandwidth);
+        if let Some(v) = audio_only {
+            return Some(&v.uri);
+        }
+        let cheapest = self.variants.iter().min_by_key(|v| v.bandwidth);
+        let group = cheapest.and_then(|v| v.audio_group.as_deref());
+        let rendition =
+            self.audio.iter().filter(|r| group.map_or(true, |g| r.group_id == g)).max_by_key(|r| r.default);
+        rendition.map(|r| r.uri.as_str()).or(cheapest.map(|v| v.uri.as_str()))
+    }
+}
+
+fn is_audio_only(codecs: &str) -> bool {
+    const VIDEO: [&str; 6] = ["avc", "hvc", "hev", "vp0", "av01", "dvh"];
+    !codecs.split(',').map(str::trim).any(|c| VIDEO.iter().any(|v| c.starts_with(v)))
+}
+
+/// HLS playlists are extended M3U with at least one `#EXT-X-` tag; a plain M3U
+/// track list has none.
+pub fn is_playlist(text: &str) -> bool {
+    text.trim_start_matches('\u{feff}').trim_start().starts_with("#EXTM3U") && text.contains("#EXT-X-")
+}
+
+pub fn parse(text: &str) -> Result<Playlist> {
+    if !is_playlist(text) {
+        bail!("not an HLS playlist");
+    }
+    let mut master = MasterPlaylist { variants: Vec::new(), audio: Vec::new() };
+    let mut media =
+        MediaPlaylist { target_duration: 0.0, media_sequence: 0, segments: Vec::new(), ended: false };
+    let mut pending_variant: Option<Variant> = None;
+    let mut duration = None;
+    let mut range = None;
+    let mut next_offset = 0u64;
+    let mut init: Option<InitSection> = None;
+
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if let Some(tag) = line.strip_prefix('#') {
+            let (name, value) = tag.split_once(':').unwrap_or((tag, ""));
+            match name {
+                "EXT-X-STREAM-INF" => {
+                    let attrs = attributes(value);
+                    pending_variant = Some(Variant {
+                        uri: String::new(),
+                        bandwidth: attr(&attrs, "BANDWIDTH").and_then(|b| b.parse().ok()).unwrap_or(0),
+                        codecs: attr(&attrs, "CODECS").map(str::to_string),
+                        audio_group: attr(&attrs, "AUDIO").map(str::to_string),
+                    });
+                }
+                "EXT-X-MEDIA" => {
+                    let attrs = attributes(value);
+                    if let (Some("AUDIO"), Some(uri)) = (attr(&attrs, "TYPE"), attr(&attrs, "URI")) {
+                        master.audio.push(Rendition {
+                            group_id: attr(&attrs, "GROUP-ID").unwrap_or_default().to_string(),
+                            uri: uri.to_string(),
+                            default: attr(&attrs, "DEFAULT") == Some("YES"),
+                        });
+                    }
+                }
+                "EXT-X-TARGETDURATION" => media.target_duration = value.parse().unwrap_or(0.0),
+                "EXT-X-MEDIA-SEQUENCE" => media.media_sequence = value.parse().unwrap_or(0),
+                "EXT-X-ENDLIST" => media.ended = true,
+                "EXTINF" => {
+                    duration = value.split(',').next().and_then(|d| d.trim().parse::<f64>().ok());
+                }
+                "EXT-X-BYTERANGE" => range = Some(parse_byte_range(value, next_offset)?),
+                "EXT-X-MAP" => {
+                    let attrs = attributes(value);
+                    let Some(uri) = attr(&attrs, "URI") else {
+                        bail!("EXT-X-MAP without URI");
+                    };
+                    let range = match attr(&attrs, "BYTERANGE") {
+                        Some(r) => Some(parse_byte_range(r, 0)?),
+                        None => None,
+                    };
+                    init = Some(InitSection { uri: uri.to_string(), range });
+                }
+                "EXT-X-KEY" => {
+                    let attrs = attributes(value);
+                    if attr(&attrs, "METHOD").is_some_and(|m| m != "NONE") {
+                        bail!("encrypted HLS streams are not supported");
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+        if let Some(mut v) = pending_variant.take() {
+            v.uri = line.to_string();
+            master.variants.push(v);
+        } else {
+            if let Some(r) = range {
+                next_offset = r.end + 1;
+            }
+            media.segments.push(Segment {
+                uri: line.to_string(),
+                duration: duration.take().unwrap_or(media.target_duration),
+                range: range.take(),
+                init: init.clone(),
+            });
+        }
+    }
+
+    if !master.variants.is_empty() {
+        return Ok(Playlist::Master(master));
+    }
+    Ok(Playlist::Media(media))
+}
+
+/// `<length>[@<offset>]`; without an offset the range follows the previous one.
+fn parse_byte_range(value: &str, next_offset: u64) -> Result<ByteRange> {
+    let (len, offset) = match value.split_once('@') {
+        Some((l, o)) => (l.trim().parse::<u64>()?, o.trim().parse::<u64>()?),
+        None => (value.trim().parse::<u64>()?, next_offset),
+    };
+    if len == 0 {
+        bail!("empty byte range");
+    }
+    Ok(ByteRange { start: offset, end: offset + len - 1 })
+}
+
+/// Splits an attribute list (`KEY=VALUE,KEY="quoted, value"`).
+fn attributes(list: &str) -> Vec<(&str, &str)> {
+    let mut out = Vec::new();
+    let mut rest = list.trim();
+    while let Some((key, after)) = rest.split_once('=') {
+        let key = key.trim().trim_start_matches(',').trim();
+        let (value, remaining) = match after.strip_prefix('"') {
+            Some(quoted) => match quoted.split_once('"') {
+                Some((v, r)) => (v, r),
+                None => (quoted, ""),
+            },
+            None => after.split_once(',').unwrap_or((after, "")),
+        };
+        out.push((key, value.trim()));
+        rest = remaining.trim_start_matches(',').trim();
+    }
+    out
+}
+
+fn attr<'a>(attrs: &[(&'a str, &'a str)], key: &str) -> Option<&'a str> {
+    attrs.iter().find(|(k, _)| *k == key).map(|(_, v)| *v)
+}
@@ -0,0 +1,92 @@
+use serde::Serialize;
+
+use crate::audio::format::SOURCE_CHANNELS;
+
+/// Levels below this are reported as silence instead of tending to -inf.
+const DBFS_FLOOR: f32 = -120.0;
+/// Samples at or above this magnitude are counted as clipped.
+const CLIP_LEVEL: i32 = i16::MAX as i32;
+
+/// Raw per-channel measurements of one 20 ms output frame.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameLevels {
+    sum_sq: [f64; SOURCE_CHANNELS],
+    peak: [i32; SOURCE_CHANNELS],
+    samples: u32,
+    clipped: u32,
+}
+
+impl FrameLevels {
+    /// Measures interleaved stereo samples after volume and EQ have been applied.
+    pub fn measure(frame: &[i16]) -> Self {
+        let mut lv = Self::default();
+        for s in frame.chunks_exact(SOURCE_CHANNELS) {
+            for (ch, &v) in s.iter().enumerate() {
+                let v = v as i32;
+                lv.sum_sq[ch] += (v * v) as f64;
+                lv.peak[ch] = lv.peak[ch].max(v.abs());
+                if v.abs() >= CLIP_LEVEL {
+                    lv.clipped += 1;
+                }
+            }
+            lv.samples += 1;
+        }
+        lv
+    }
+
+    fn merge(&mut self, other: &FrameLevels) {
+        for ch in 0..SOURCE_CHANNELS {
+            self.sum_sq[ch] += other.sum_sq[ch];
+            self.peak[ch] = self.peak[ch].max(other.peak[ch]);
+        }
+        self.samples += other.samples;
+        self.clipped += other.clipped;
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LevelReport {
+    /// `[left, right]` RMS level in dBFS.
+    pub rms_db: [f32; SOURCE_CHANNELS],
+    /// `[left, right]` sample peak in dBFS.
+    pub peak_db: [f32; SOURCE_CHANNELS],
+    pub clipped_samples: u32,
+}
+
+/// Collects frame levels until `frames` of them have been seen, then yields one report.
+pub struct LevelAccumulator {
+    frames: u32,
+    seen: u32,
+    acc: FrameLevels,
+}
+
+impl LevelAccumulator {
+    pub fn new(frames: u32) -> Self {
+        Self { frames: frames.max(1), seen: 0, acc: FrameLevels::default() }
+    }
+
+    pub fn push(&mut self, lv: &FrameLevels) -> Option<LevelReport> {
+        self.acc.merge(lv);
+        self.seen += 1;
+        if self.seen < self.frames {
+            return None;
+        }
+        let acc = std::mem::take(&mut self.acc);
+        self.seen = 0;
+        let n = acc.samples.max(1) as f64;
+        let full_scale = i16::MAX as f64 + 1.0;
+        Some(LevelReport {
+            rms_db: acc.sum_sq.map(|s| to_dbfs((s / n).sqrt() / full_scale)),
+            peak_db: acc.peak.map(|p| to_dbfs(p as f64 / full_scale)),
+            clipped_samples: acc.clipped,
+        })
+    }
+}
+
+pub fn to_dbfs(linear: f64) -> f32 {
+    if linear <= 0.0 {
+        return DBFS_FLOOR;
+    }
+    ((20.0 * linear.log10()) as f32).max(DBFS_FLOOR)
+}
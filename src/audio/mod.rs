@@ -1,5 +1,31 @@
+pub mod analysis;
+pub mod chapters;
+pub mod clock;
 pub mod decoder;
 pub mod dsp;
+pub mod encoder;
+pub mod format;
+pub mod frame;
+pub mod hls;
+pub mod http;
+pub mod icy;
+pub mod lyrics;
+pub mod m3u8;
+pub mod meter;
+pub mod mpd;
+pub mod native;
+pub mod ogg;
+pub mod opus;
 pub mod player;
+pub mod playlist;
+pub mod recorder;
+pub mod rtp;
+pub mod segments;
+pub mod silence;
+pub mod sink;
 pub mod source;
+pub mod spectrum;
+pub mod stream;
+pub mod subscriber;
+pub mod tags;
 pub mod track;
@@ -0,0 +1,313 @@
+use anyhow::{anyhow, bail, Context, Result};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use url::Url;
+
+use crate::audio::segments::{ByteRange, SegmentRef};
+use crate::utils::xml::XmlNode as Node;
+
+/// What to play from a DASH manifest: the best audio representation of the
+/// current period.
+pub struct Manifest {
+    pub live: bool,
+    pub duration_ms: Option<u64>,
+    /// `minimumUpdatePeriod`: how often a live manifest should be reloaded.
+    pub update_period: Option<Duration>,
+    pub init: Option<SegmentRef>,
+    pub plan: SegmentPlan,
+}
+
+pub enum SegmentPlan {
+    /// Segments in playback order, keyed by `$Number$` or `$Time$` so a reloaded
+    /// manifest can be continued where the previous one left off.
+    Listed(Vec<(u64, SegmentRef)>),
+    /// Live `$Number$` template without a timeline; numbers follow the wall clock.
+    Numbered(NumberedTemplate),
+}
+
+pub struct NumberedTemplate {
+    media: String,
+    base: Url,
+    rep_id: String,
+    bandwidth: u64,
+    pub start_number: u64,
+    segment: Duration,
+    /// `availabilityStartTime` plus the period start.
+    origin: SystemTime,
+}
+
+impl NumberedTemplate {
+    /// Number of the newest segment that is completely available at `now`.
+    pub fn latest(&self, now: SystemTime) -> Option<u64> {
+        let elapsed = now.duration_since(self.origin).ok()?;
+        let complete = (elapsed.as_secs_f64() / self.segment.as_secs_f64()).floor() as u64;
+        complete.checked_sub(1).map(|n| self.start_number + n)
+    }
+
+    pub fn available_at(&self, number: u64) -> SystemTime {
+        let index = number.saturating_sub(self.start_number) + 1;
+        self.origin + self.segment.mul_f64(index as f64)
+    }
+
+    pub fn segment(&self, number: u64) -> Result<SegmentRef> {
+        let path = fill_template(&self.media, &self.rep_id, self.bandwidth, Some(number), None);
+        Ok(SegmentRef { url: self.base.join(&path)?, range: None })
+    }
+}
+
+pub fn is_manifest(text: &str) -> bool {
+    let head: String = text.chars().take(512).collect();
+    head.contains("<MPD")
+}
+
+pub fn parse(xml: &str, url: &Url) -> Result<Manifest> {
+    let root = Node::parse(xml).context("parse manifest")?;
+    if root.name != "MPD" {
+        bail!("not a DASH manifest");
+    }
+    let live = root.attr("type") == Some("dynamic");
+    let total = root.attr("mediaPresentationDuration").and_then(parse_duration);
+    let update_period = root.attr("minimumUpdatePeriod").and_then(parse_duration);
+    let availability_start = root.attr("availabilityStartTime").and_then(parse_datetime);
+
+    let periods: Vec<&Node> = root.children("Period").collect();
+    let period = if live { periods.last() } else { periods.first() }.ok_or_else(|| anyhow!("no Period"))?;
+    let period_start = period.attr("start").and_then(parse_duration).unwrap_or_default();
+    let period_duration = period
+        .attr("duration")
+        .and_then(parse_duration)
+        .or_else(|| total.map(|t| t.saturating_sub(period_start)));
+
+    let (set, rep) = best_audio(period).ok_or_else(|| anyhow!("no audio representation"))?;
+    let mut base = url.clone();
+    for node in [Some(&root), Some(*period), Some(set), Some(rep)].into_iter().flatten() {
+        if let Some(b) = node.child("BaseURL").map(|b| b.text.trim()).filter(|b| !b.is_empty()) {
+            base = base.join(b)?;
+        }
+    }
+    let rep_id = rep.attr("id").unwrap_or_default().to_string();
+    let bandwidth = rep.attr("bandwidth").and_then(|b| b.parse().ok()).unwrap_or(0);
+    let levels = [rep, set, *period];
+
+    let duration_ms = if live { None } else { total.map(|d| d.as_millis() as u64) };
+    let manifest = |init, plan| Manifest { live, duration_ms, update_period, init, plan };
+
+    if let Some(template) = inherited_child(&levels, "SegmentTemplate") {
+        let get =
+            |name: &str| levels.iter().find_map(|n| n.child("SegmentTemplate").and_then(|t| t.attr(name)));
+        let media = get("media").ok_or_else(|| anyhow!("SegmentTemplate without media"))?.to_string();
+        let timescale = get("timescale").and_then(|t| t.parse::<u64>().ok()).filter(|&t| t > 0).unwrap_or(1);
+        let start_number = get("startNumber").and_then(|n| n.parse().ok()).unwrap_or(1);
+        let init = match get("initialization") {
+            Some(i) => {
+                let path = fill_template(i, &rep_id, bandwidth, None, None);
+                Some(SegmentRef { url: base.join(&path)?, range: None })
+            }
+            None => None,
+        };
+        if let Some(timeline) = template.child("SegmentTimeline") {
+            let mut list = Vec::new();
+            let mut time = 0u64;
+            let mut number = start_number;
+            let entries: Vec<&Node> = timeline.children("S").collect();
+            for (i, s) in entries.iter().enumerate() {
+                let d: u64 =
+                    s.attr("d").and_then(|d| d.parse().ok()).filter(|&d| d > 0).context("S without d")?;
+                if let Some(t) = s.attr("t").and_then(|t| t.parse().ok()) {
+                    time = t;
+                }
+                let repeat: i64 = s.attr("r").and_then(|r| r.parse().ok()).unwrap_or(0);
+                let count = if repeat >= 0 {
+                    repeat as u64 + 1
+                } else {
+                    // Repeats until the next entry, or the end of the period.
+                    let until =
+                        entries.get(i + 1).and_then(|n| n.attr("t")).and_then(|t| t.parse::<u64>().ok());
+                    let until = until
+                        .or_else(|| period_duration.map(|p| (p.as_secs_f64() * timescale as f64) as u64));
+                    until.map(|u| u.saturating_sub(time).div_ceil(d)).unwrap_or(1)
+                };
+                for _ in 0..count {
+                    let path = fill_template(&media, &rep_id, bandwidth, Some(number), Some(time));
+                    list.push((time, SegmentRef { url: base.join(&path)?, range: None }));
+                    time += d;
+                    number += 1;
+                }
+            }
+            return Ok(manifest(init, SegmentPlan::Listed(list)));
+        }
+        let duration: u64 = get("duration")
+            .and_then(|d| d.parse().ok())
+            .filter(|&d| d > 0)
+            .context("SegmentTemplate without duration or timeline")?;
+        let segment = Duration::from_secs_f64(duration as f64 / timescale as f64);
+        if live {
+            let origin = availability_start
+                .ok_or_else(|| anyhow!("live manifest without availabilityStartTime"))?
+                + period_start;
+            let template = NumberedTemplate { media, base, rep_id, bandwidth, start_number, segment, origin };
+            return Ok(manifest(init, SegmentPlan::Numbered(template)));
+        }
+        let length = period_duration.ok_or_else(|| anyhow!("unknown presentation duration"))?;
+        let count = (length.as_secs_f64() / segment.as_secs_f64()).ceil() as u64;
+        let mut list = Vec::with_capacity(count as usize);
+        for number in start_number..start_number + count {
+            let path = fill_template(&media, &rep_id, bandwidth, Some(number), None);
+            list.push((number, SegmentRef { url: base.join(&path)?, range: None }));
+        }
+        return Ok(manifest(init, SegmentPlan::Listed(list)));
+    }
+
+    if let Some(seg_list) = inherited_child(&levels, "SegmentList") {
+        let init = match seg_list.child("Initialization") {
+            Some(i) => Some(SegmentRef {
+                url: i.attr("sourceURL").map(|u| base.join(u)).transpose()?.unwrap_or_else(|| base.clone()),
+                range: i.attr("range").and_then(parse_range),
+            }),
+            None => None,
+        };
+        let mut list = Vec::new();
+        for (i, s) in seg_list.children("SegmentURL").enumerate() {
+            let url = s.attr("media").map(|u| base.join(u)).transpose()?.unwrap_or_else(|| base.clone());
+            list.push((i as u64, SegmentRef { url, range: s.attr("mediaRange").and_then(parse_range) }));
+        }
+        return Ok(manifest(init, SegmentPlan::Listed(list)));
+    }
+
+    // SegmentBase or a bare BaseURL: the representation is a single file.
+    Ok(manifest(None, SegmentPlan::Listed(vec![(0, SegmentRef { url: base, range: None })])))
+}
+
+/// The audio representation with the highest bandwidth in `period`.
+fn best_audio(period: &Node) -> Option<(&Node, &Node)> {
+    period
+        .children("AdaptationSet")
+        .filter(|set| is_audio(set) || set.children("Representation").any(is_audio))
+        .flat_map(|set| set.children("Representation").map(move |rep| (set, rep)))
+        .max_by_key(|(_, rep)| rep.attr("bandwidth").and_then(|b| b.parse::<u64>().ok()).unwrap_or(0))
+}
+
+fn is_audio(node: &Node) -> bool {
+    node.attr("contentType") == Some("audio")
+        || node.attr("mimeType").is_some_and(|m| m.starts_with("audio/"))
+        || node
+            .attr("codecs")
+            .is_some_and(|c| ["mp4a", "opus", "ac-3", "ec-3", "flac"].iter().any(|a| c.starts_with(a)))
+}
+
+fn inherited_child<'a>(levels: &[&'a Node], name: &str) -> Option<&'a Node> {
+    levels.iter().find_map(|n| n.child(name))
+}
+
+/// Expands `$RepresentationID$`, `$Bandwidth$`, `$Number$` and `$Time$`, with
+/// optional printf widths like `$Number%05d$`.
+fn fill_template(
+    template: &str,
+    rep_id: &str,
+    bandwidth: u64,
+    number: Option<u64>,
+    time: Option<u64>,
+) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut parts = template.split('$');
+    if let Some(first) = parts.next() {
+        out.push_str(first);
+    }
+    let mut in_token = true;
+    for part in parts {
+        if !in_token {
+            out.push_str(part);
+            in_token = true;
+            continue;
+        }
+        in_token = false;
+        if part.is_empty() {
+            out.push('$');
+            continue;
+        }
+        let (name, width) = match part.split_once('%') {
+            Some((n, f)) => {
+                (n, f.trim_start_matches('0').trim_end_matches('d').parse::<usize>().unwrap_or(0))
+            }
+            None => (part, 0),
+        };
+        let value = match name {
+            "RepresentationID" => Some(rep_id.to_string()),
+            "Bandwidth" => Some(bandwidth.to_string()),
+            "Number" => number.map(|n| n.to_string()),
+            "Time" => time.map(|t| t.to_string()),
+            _ => None,
+        };
+        match value {
+            Some(v) => out.push_str(&format!("{v:0>width$}")),
+            None => {
+                out.push('$');
+                out.push_str(part);
+                out.push('$');
+            }
+        }
+    }
+    out
+}
+
+/// `start-end`, both inclusive.
+fn parse_range(value: &str) -> Option<ByteRange> {
+    let (start, end) = value.split_once('-')?;
+    Some(ByteRange { start: start.trim().parse().ok()?, end: end.trim().parse().ok()? })
+}
+
+/// ISO 8601 durations as used by DASH, e.g. `PT1H2M3.5S` or `P1DT2H`.
+fn parse_duration(value: &str) -> Option<Duration> {
+    let rest = value.trim().strip_prefix('P')?;
+    let (date, time) = rest.split_once('T').unwrap_or((rest, ""));
+    let mut secs = 0f64;
+    let mut num = String::new();
+    for (part, units) in [
+        (date, [('Y', 31_536_000.0), ('M', 2_592_000.0), ('D', 86_400.0)]),
+        (time, [('H', 3600.0), ('M', 60.0), ('S', 1.0)]),
+    ] {
+        for c in part.chars() {
+            if c.is_ascii_digit() || c == '.' {
+                num.push(c);
+                continue;
+            }
+            let scale = units.iter().find(|(u, _)| *u == c)?.1;
+            secs += num.parse::<f64>().ok()? * scale;
+            num.clear();
+        }
+    }
+    num.is_empty().then(|| Duration::from_secs_f64(secs))
+}
+
+/// `YYYY-MM-DDTHH:MM:SS[.fff][Z|±HH:MM]`.
+fn parse_datetime(value: &str) -> Option<SystemTime> {
+    let value = value.trim();
+    let (date, time) = value.split_once('T')?;
+    let mut d = date.split('-').map(|p| p.parse::<i64>());
+    let (year, month, day) = (d.next()?.ok()?, d.next()?.ok()?, d.next()?.ok()?);
+    let (clock, offset) = match time.find(['Z', '+', '-']) {
+        Some(i) => (&time[..i], &time[i..]),
+        None => (time, "Z"),
+    };
+    let mut t = clock.split(':');
+    let (h, m) = (t.next()?.parse::<i64>().ok()?, t.next()?.parse::<i64>().ok()?);
+    let s = t.next().map(|s| s.parse::<f64>()).transpose().ok()?.unwrap_or(0.0);
+    let offset_secs = match offset {
+        "Z" | "" => 0,
+        o => {
+            let sign = if o.starts_with('-') { -1 } else { 1 };
+            let (oh, om) = o[1..].split_once(':').unwrap_or((&o[1..], "0"));
+            sign * (oh.parse::<i64>().ok()? * 3600 + om.parse::<i64>().ok()? * 60)
+        }
+    };
+    // Days since the Unix epoch for a proleptic Gregorian date.
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    let secs = days * 86_400 + h * 3600 + m * 60 - offset_secs;
+    let secs = u64::try_from(secs).ok()?;
+    Some(UNIX_EPOCH + Duration::from_secs(secs) + Duration::from_secs_f64(s))
+}
@@ -0,0 +1,348 @@
+use anyhow::{anyhow, bail, Context, Result};
+use rubato::{FftFixedIn, Resampler};
+use std::path::Path;
+use symphonia::core::{
+    audio::SampleBuffer,
+    codecs::{Decoder as CodecDecoder, DecoderOptions, CODEC_TYPE_NULL, CODEC_TYPE_OPUS},
+    errors::Error as SymphoniaError,
+    formats::{FormatOptions, FormatReader, SeekMode, SeekTo},
+    io::{MediaSource, MediaSourceStream, ReadOnlySource},
+    meta::MetadataOptions,
+    probe::Hint,
+    units::{Time, TimeBase},
+};
+use tokio::{io::AsyncWriteExt, sync::mpsc, sync::oneshot};
+use tracing::{debug, warn};
+
+use crate::audio::{
+    decoder::PcmBlock,
+    http::{HttpSettings, HttpStream},
+    icy::IcyWatch,
+    segments::StreamInfo,
+    source::Source,
+    tags::{read_tags, TagsReceiver},
+};
+
+const OUTPUT_RATE: usize = 48_000;
+const RESAMPLE_CHUNK: usize = 1024;
+/// Decoded blocks buffered between the decode thread and the player.
+const CHANNEL_BLOCKS: usize = 16;
+
+/// Decodes a local file or HTTP stream in-process with Symphonia on a blocking thread, converting
+/// it to 48 kHz stereo. The thread stops when the decoder is dropped.
+pub struct NativeDecoder {
+    rx: mpsc::Receiver<Result<PcmBlock>>,
+    icy: Option<IcyWatch>,
+    info: Option<StreamInfo>,
+    tags: Option<TagsReceiver>,
+}
+
+/// The source was probed but its codec has no in-process decoder, so it can only
+/// be played through ffmpeg.
+#[derive(Debug, thiserror::Error)]
+#[error("{0} is not supported by the native decoder, ffmpeg is required")]
+pub struct NeedsFfmpeg(pub &'static str);
+
+struct Opened {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn CodecDecoder>,
+    track_id: u32,
+    sample_rate: usize,
+    time_base: Option<TimeBase>,
+    /// Frames at the source rate to drop before output starts, after a seek.
+    skip: u64,
+}
+
+impl NativeDecoder {
+    /// Probes `source` and starts decoding. Fails if the container or codec is not
+    /// supported natively, so the caller can fall back to ffmpeg.
+    pub async fn open(source: &Source, http: &HttpSettings) -> Result<Self> {
+        let mut icy = None;
+        let mut info = None;
+        let mut tags = None;
+        let media: Box<dyn MediaSource> = match source {
+            Source::File(path) => {
+                let file = std::fs::File::open(path).with_context(|| format!("open {}", path.display()))?;
+                Box::new(file)
+            }
+            Source::Http(url) => {
+                let mut stream = HttpStream::open(url, http).await?;
+                icy = stream.icy();
+                info = stream.info();
+                tags = stream.take_tags();
+                Box::new(ReadOnlySource::new(stream.into_blocking_reader()))
+            }
+        };
+        Self::start(media, source.extension(), None, 0).await.map(|d| Self { icy, info, tags, ..d })
+    }
+
+    /// Decodes a local file from `start_ms`, seeking in the container rather than
+    /// decoding everything before it.
+    pub async fn open_file_at(path: &Path, start_ms: u64) -> Result<Self> {
+        let file = std::fs::File::open(path).with_context(|| format!("open {}", path.display()))?;
+        let ext = Source::File(path.to_path_buf()).extension();
+        Self::start(Box::new(file), ext, None, start_ms).await
+    }
+
+    /// Downloads an HTTP file to a temp file and decodes that, for containers that
+    /// cannot be probed from a stream, such as MP4 with `moov` after the media data.
+    /// The temp file is deleted once decoding ends.
+    pub async fn open_downloaded(url: &str, http: &HttpSettings) -> Result<Self> {
+        let mut stream = HttpStream::open(url, http).await?;
+        if !stream.is_file() {
+            bail!("not a file of known length");
+        }
+        let file = tempfile::Builder::new().prefix("resonix_").tempfile().context("create temp file")?;
+        let mut out = tokio::fs::File::from_std(file.reopen()?);
+        while let Some(chunk) = stream.next_chunk().await {
+            out.write_all(&chunk.context("download")?).await.context("write temp file")?;
+        }
+        out.flush().await?;
+        debug!(%url, path = %file.path().display(), "downloaded http file for native decoding");
+        let (tags_tx, tags) = oneshot::channel();
+        let path = file.path().to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            if let Ok(t) = read_tags(&path) {
+                let _ = tags_tx.send(t);
+            }
+        });
+        let media: Box<dyn MediaSource> = Box::new(file.reopen()?);
+        let ext = Source::Http(url.to_string()).extension();
+        let decoder = Self::start(media, ext, Some(file), 0).await?;
+        Ok(Self { tags: Some(tags), ..decoder })
+    }
+
+    /// Probes `media`, seeks to `start_ms` if it is not zero and starts the decode
+    /// thread, which keeps `temp` alive until it ends.
+    async fn start(
+        media: Box<dyn MediaSource>,
+        ext: Option<String>,
+        temp: Option<tempfile::NamedTempFile>,
+        start_ms: u64,
+    ) -> Result<Self> {
+        let opened = tokio::task::spawn_blocking(move || {
+            let mut opened = probe(media, ext.as_deref())?;
+            if start_ms > 0 {
+                opened.seek(start_ms)?;
+            }
+            Ok::<_, anyhow::Error>(opened)
+        })
+        .await??;
+        let (tx, rx) = mpsc::channel(CHANNEL_BLOCKS);
+        tokio::task::spawn_blocking(move || {
+            let _temp = temp;
+            if let Err(e) = decode_loop(opened, &tx) {
+                let _ = tx.blocking_send(Err(e));
+            }
+        });
+        Ok(Self { rx, icy: None, info: None, tags: None })
+    }
+
+    pub async fn next_pcm_block(&mut self) -> Result<Option<PcmBlock>> {
+        self.rx.recv().await.transpose()
+    }
+
+    pub fn icy(&self) -> Option<IcyWatch> {
+        self.icy.clone()
+    }
+
+    pub fn stream_info(&self) -> Option<StreamInfo> {
+        self.info
+    }
+
+    pub fn take_tags(&mut self) -> Option<TagsReceiver> {
+        self.tags.take()
+    }
+}
+
+fn probe(media: Box<dyn MediaSource>, ext: Option<&str>) -> Result<Opened> {
+    let mss = MediaSourceStream::new(media, Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = ext {
+        hint.with_extension(ext);
+    }
+    let probed = symphonia::default::get_probe()
+        .format(&hint, mss, &FormatOptions::default(), &MetadataOptions::default())
+        .context("unsupported container")?;
+    let format = probed.format;
+    let track = format
+        .default_track()
+        .filter(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .or_else(|| format.tracks().iter().find(|t| t.codec_params.codec != CODEC_TYPE_NULL))
+        .ok_or_else(|| anyhow!("no audio track"))?;
+    if track.codec_params.codec == CODEC_TYPE_OPUS {
+        return Err(NeedsFfmpeg("Opus").into());
+    }
+    let decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .context("unsupported codec")?;
+    let sample_rate = track.codec_params.sample_rate.ok_or_else(|| anyhow!("unknown sample rate"))? as usize;
+    let time_base = track.codec_params.time_base;
+    Ok(Opened { track_id: track.id, format, decoder, sample_rate, time_base, skip: 0 })
+}
+
+impl Opened {
+    /// Seeks to `ms`. The container lands on a packet at or before it; the frames
+    /// up to `ms` are dropped by the decode loop.
+    fn seek(&mut self, ms: u64) -> Result<()> {
+        let time = Time::new(ms / 1000, (ms % 1000) as f64 / 1000.0);
+        let to = self
+            .format
+            .seek(SeekMode::Accurate, SeekTo::Time { time, track_id: Some(self.track_id) })
+            .map_err(|e| anyhow!("seek: {e}"))?;
+        self.decoder.reset();
+        let early = to.required_ts.saturating_sub(to.actual_ts);
+        self.skip = match self.time_base {
+            Some(tb) => {
+                let t = tb.calc_time(early);
+                ((t.seconds as f64 + t.frac) * self.sample_rate as f64) as u64
+            }
+            None => early,
+        };
+        Ok(())
+    }
+}
+
+fn decode_loop(opened: Opened, tx: &mpsc::Sender<Result<PcmBlock>>) -> Result<()> {
+    let Opened { mut format, mut decoder, track_id, sample_rate, mut skip, .. } = opened;
+    let mut out = StereoResampler::new(sample_rate)?;
+    let mut samples: Option<SampleBuffer<f32>> = None;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(p) => p,
+            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(SymphoniaError::ResetRequired) => break,
+            Err(e) => return Err(anyhow!("read packet: {e}")),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(d) => d,
+            Err(SymphoniaError::DecodeError(e)) => {
+                warn!(error = e, "skipping undecodable packet");
+                continue;
+            }
+            Err(e) => return Err(anyhow!("decode: {e}")),
+        };
+        let spec = *decoded.spec();
+        let channels = spec.channels.count().max(1);
+        let sb = match &mut samples {
+            Some(sb) if sb.capacity() >= decoded.capacity() * channels => sb,
+            _ => samples.insert(SampleBuffer::new(decoded.capacity() as u64, spec)),
+        };
+        sb.copy_interleaved_ref(decoded);
+        let mut l = Vec::with_capacity(sb.len() / channels);
+        let mut r = Vec::with_capacity(l.capacity());
+        for frame in sb.samples().chunks_exact(channels) {
+            l.push(frame[0]);
+            r.push(if channels > 1 { frame[1] } else { frame[0] });
+        }
+        if skip > 0 {
+            let n = (skip as usize).min(l.len());
+            l.drain(..n);
+            r.drain(..n);
+            skip -= n as u64;
+            if l.is_empty() {
+                continue;
+            }
+        }
+        if let Some(block) = out.push(l, r)? {
+            if tx.blocking_send(Ok(block)).is_err() {
+                debug!("native decoder dropped");
+                return Ok(());
+            }
+        }
+    }
+    if let Some(block) = out.finish()? {
+        let _ = tx.blocking_send(Ok(block));
+    }
+    Ok(())
+}
+
+/// Passes 48 kHz input through and resamples anything else, trimming the
+/// resampler's delay so output length matches input duration.
+struct StereoResampler {
+    inner: Option<FftFixedIn<f32>>,
+    rate: usize,
+    pending: [Vec<f32>; 2],
+    consumed: u64,
+    output: Output,
+}
+
+/// Output bookkeeping: frames of resampler delay still to drop and frames written.
+struct Output {
+    skip: usize,
+    produced: u64,
+}
+
+impl Output {
+    fn emit(&mut self, out: Vec<Vec<f32>>, block: &mut PcmBlock, limit: u64) {
+        let skip = self.skip.min(out[0].len());
+        self.skip -= skip;
+        let take = ((out[0].len() - skip) as u64).min(limit.saturating_sub(self.produced)) as usize;
+        block.l.extend_from_slice(&out[0][skip..skip + take]);
+        block.r.extend_from_slice(&out[1][skip..skip + take]);
+        self.produced += take as u64;
+    }
+}
+
+impl StereoResampler {
+    fn new(rate: usize) -> Result<Self> {
+        let inner = if rate == OUTPUT_RATE {
+            None
+        } else {
+            Some(
+                FftFixedIn::new(rate, OUTPUT_RATE, RESAMPLE_CHUNK, 2, 2)
+                    .map_err(|e| anyhow!("create resampler: {e}"))?,
+            )
+        };
+        let skip = inner.as_ref().map(|r| r.output_delay()).unwrap_or(0);
+        Ok(Self {
+            inner,
+            rate,
+            pending: [Vec::new(), Vec::new()],
+            consumed: 0,
+            output: Output { skip, produced: 0 },
+        })
+    }
+
+    fn push(&mut self, l: Vec<f32>, r: Vec<f32>) -> Result<Option<PcmBlock>> {
+        let Some(rs) = self.inner.as_mut() else {
+            return Ok((!l.is_empty()).then_some(PcmBlock { l, r }));
+        };
+        self.pending[0].extend_from_slice(&l);
+        self.pending[1].extend_from_slice(&r);
+        let mut block = PcmBlock { l: Vec::new(), r: Vec::new() };
+        while self.pending[0].len() >= rs.input_frames_next() {
+            let n = rs.input_frames_next();
+            let out = rs.process(&[&self.pending[0][..n], &self.pending[1][..n]], None)?;
+            self.pending[0].drain(..n);
+            self.pending[1].drain(..n);
+            self.consumed += n as u64;
+            self.output.emit(out, &mut block, u64::MAX);
+        }
+        Ok((!block.l.is_empty()).then_some(block))
+    }
+
+    /// Resamples what is left and flushes the resampler's delay line.
+    fn finish(&mut self) -> Result<Option<PcmBlock>> {
+        let Some(rs) = self.inner.as_mut() else {
+            return Ok(None);
+        };
+        self.consumed += self.pending[0].len() as u64;
+        let expected = self.consumed * OUTPUT_RATE as u64 / self.rate as u64;
+        let mut block = PcmBlock { l: Vec::new(), r: Vec::new() };
+        let tail = std::mem::take(&mut self.pending);
+        let out = rs.process_partial(Some(&[&tail[0][..], &tail[1][..]]), None)?;
+        self.output.emit(out, &mut block, expected);
+        while self.output.produced < expected {
+            let out = rs.process_partial::<&[f32]>(None, None)?;
+            if out[0].is_empty() {
+                break;
+            }
+            self.output.emit(out, &mut block, expected);
+        }
+        Ok((!block.l.is_empty()).then_some(block))
+    }
+}
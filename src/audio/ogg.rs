@@ -0,0 +1,61 @@
+use anyhow::{bail, Result};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+const CAPTURE_PATTERN: &[u8; 4] = b"OggS";
+const HEADER_LEN: usize = 27;
+
+/// Minimal Ogg demuxer that yields the packets of a single logical stream in order.
+pub struct OggPacketReader<R> {
+    inner: R,
+    partial: Vec<u8>,
+    ready: std::collections::VecDeque<Vec<u8>>,
+}
+
+impl<R: AsyncRead + Unpin> OggPacketReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self { inner, partial: Vec::new(), ready: Default::default() }
+    }
+
+    /// Returns the next complete packet, or `None` once the stream ends.
+    pub async fn next_packet(&mut self) -> Result<Option<Vec<u8>>> {
+        loop {
+            if let Some(p) = self.ready.pop_front() {
+                return Ok(Some(p));
+            }
+            if !self.read_page().await? {
+                return Ok(None);
+            }
+        }
+    }
+
+    async fn read_page(&mut self) -> Result<bool> {
+        let mut header = [0u8; HEADER_LEN];
+        match self.inner.read_exact(&mut header).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(false),
+            Err(e) => return Err(e.into()),
+        }
+        if &header[0..4] != CAPTURE_PATTERN {
+            bail!("ogg: lost page sync");
+        }
+        let continued = header[5] & 0x01 != 0;
+        if !continued {
+            self.partial.clear();
+        }
+        let mut lacing = vec![0u8; header[26] as usize];
+        self.inner.read_exact(&mut lacing).await?;
+        let body_len: usize = lacing.iter().map(|&v| v as usize).sum();
+        let mut body = vec![0u8; body_len];
+        self.inner.read_exact(&mut body).await?;
+
+        let mut offset = 0;
+        for &len in &lacing {
+            self.partial.extend_from_slice(&body[offset..offset + len as usize]);
+            offset += len as usize;
+            if len < 255 {
+                self.ready.push_back(std::mem::take(&mut self.partial));
+            }
+        }
+        Ok(true)
+    }
+}
@@ -0,0 +1,161 @@
+use anyhow::Result;
+use bytes::Bytes;
+use serde::Deserialize;
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::{broadcast, Mutex};
+use tracing::{debug, warn};
+
+use crate::audio::{
+    encoder::{pump_pcm, FfmpegEncoder},
+    frame::AudioFrame,
+    ogg::OggPacketReader,
+};
+use crate::config::EffectiveConfig;
+
+const MIN_BITRATE: u32 = 6_000;
+const MAX_BITRATE: u32 = 510_000;
+/// Ogg Opus streams start with an identification and a comment header packet.
+const OPUS_HEADER_PACKETS: usize = 2;
+
+pub type OpusEncoders = Arc<Mutex<HashMap<OpusSettings, broadcast::Sender<Bytes>>>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamEncoding {
+    #[default]
+    Pcm,
+    Opus,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct OpusQuery {
+    #[serde(default)]
+    pub encoding: Option<StreamEncoding>,
+    #[serde(default)]
+    pub bitrate: Option<u32>,
+    #[serde(default)]
+    pub fec: Option<bool>,
+    #[serde(default)]
+    pub complexity: Option<u8>,
+}
+
+/// Encoder parameters; subscribers asking for the same settings share one encoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OpusSettings {
+    pub bitrate: u32,
+    pub fec: bool,
+    pub complexity: u8,
+    pub packet_loss_percent: u8,
+}
+
+impl OpusSettings {
+    pub fn from_config(cfg: &EffectiveConfig) -> Self {
+        Self {
+            bitrate: cfg.opus_bitrate.clamp(MIN_BITRATE, MAX_BITRATE),
+            fec: cfg.opus_fec,
+            complexity: cfg.opus_complexity.min(10),
+            packet_loss_percent: cfg.opus_packet_loss_percent.min(100),
+        }
+    }
+
+    pub fn with_query(mut self, q: &OpusQuery) -> Result<Self, String> {
+        if let Some(b) = q.bitrate {
+            if !(MIN_BITRATE..=MAX_BITRATE).contains(&b) {
+                return Err(format!("bitrate must be between {MIN_BITRATE} and {MAX_BITRATE}, got {b}"));
+            }
+            self.bitrate = b;
+        }
+        if let Some(c) = q.complexity {
+            if c > 10 {
+                return Err(format!("complexity must be between 0 and 10, got {c}"));
+            }
+            self.complexity = c;
+        }
+        if let Some(f) = q.fec {
+            self.fec = f;
+        }
+        Ok(self)
+    }
+
+    fn ffmpeg_args(&self) -> Vec<String> {
+        vec![
+            "-c:a".into(),
+            "libopus".into(),
+            "-application".into(),
+            "audio".into(),
+            "-frame_duration".into(),
+            "20".into(),
+            "-b:a".into(),
+            self.bitrate.to_string(),
+            "-compression_level".into(),
+            self.complexity.to_string(),
+            "-fec".into(),
+            if self.fec { "1" } else { "0" }.into(),
+            "-packet_loss".into(),
+            if self.fec { self.packet_loss_percent.to_string() } else { "0".into() },
+            // One page per packet so every 20 ms packet is flushed as soon as it is encoded.
+            "-page_duration".into(),
+            "20000".into(),
+            "-flush_packets".into(),
+            "1".into(),
+            "-f".into(),
+            "ogg".into(),
+            "pipe:1".into(),
+        ]
+    }
+}
+
+/// Returns a receiver of Opus packets for `settings`, starting a shared encoder for
+/// the player if no live one exists. The encoder stops once its last subscriber leaves.
+pub async fn subscribe(
+    encoders: &OpusEncoders,
+    ffmpeg_bin: &str,
+    settings: OpusSettings,
+    pcm_rx: broadcast::Receiver<AudioFrame>,
+) -> Result<broadcast::Receiver<Bytes>> {
+    let mut map = encoders.lock().await;
+    if let Some(tx) = map.get(&settings) {
+        if tx.receiver_count() > 0 {
+            return Ok(tx.subscribe());
+        }
+    }
+
+    let enc = FfmpegEncoder::spawn(ffmpeg_bin, settings.ffmpeg_args())?;
+    let (tx, rx) = broadcast::channel(256);
+    map.insert(settings, tx.clone());
+    drop(map);
+
+    let FfmpegEncoder { mut child, stdin, stdout } = enc;
+    tokio::spawn(pump_pcm(pcm_rx, stdin));
+    let encoders = encoders.clone();
+    tokio::spawn(async move {
+        let mut reader = OggPacketReader::new(stdout);
+        let mut seen = 0usize;
+        loop {
+            match reader.next_packet().await {
+                Ok(Some(pkt)) => {
+                    seen += 1;
+                    if seen <= OPUS_HEADER_PACKETS {
+                        continue;
+                    }
+                    if tx.send(Bytes::from(pkt)).is_err() {
+                        break;
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    warn!(?e, "opus encoder output error");
+                    break;
+                }
+            }
+        }
+        let _ = child.start_kill();
+        let mut map = encoders.lock().await;
+        if map.get(&settings).is_some_and(|cur| cur.same_channel(&tx)) {
+            map.remove(&settings);
+        }
+        debug!(?settings, "opus encoder stopped");
+    });
+
+    Ok(rx)
+}
@@ -1,17 +1,51 @@
 use crate::audio::{
-    decoder::FfmpegDecoder,
+    chapters::{self, Chapter},
+    clock::{PacingClock, PacingSnapshot, PacingStats},
+    decoder::Decoder,
     dsp::{biquad_eq_in_place, update_eq_filters, Filters},
-    source::{is_resonix_temp_file, prepare_local_source},
+    frame::{AudioFrame, FLAG_DISCONTINUITY, FLAG_TRACK_END, FLAG_TRACK_START},
+    hls::{self, HlsPackager, HlsSettings, HlsSlot},
+    icy::IcyMetadata,
+    lyrics::{Lyrics, LyricsSource},
+    meter::FrameLevels,
+    opus::{self, OpusEncoders, OpusSettings},
+    silence::{is_silent, SilenceOptions, SilenceUpdate},
+    sink::{RtpCodec, RtpSink, RtpSinkInfo, RtpSinkReq},
+    source::{is_resonix_temp_file, prepare_source, Source},
+    subscriber::{QueueSettings, SubscriberRegistry, SubscriberStats, Subscription},
+    tags::{read_tags, Artwork, TrackTags},
     track::{LoopMode, TrackItem},
 };
 use crate::config::EffectiveConfig;
+use crate::resolver::SourceRegistry;
+use crate::voice::{self, VoiceHandle, VoiceServerInfo, VoiceStatus};
 use anyhow::Result;
 use bytes::Bytes;
-use std::{path::PathBuf, sync::Arc, time::Duration};
-use tokio::sync::{broadcast, Mutex, Notify};
-use tracing::info;
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+use tokio::sync::{broadcast, oneshot::error::TryRecvError, Mutex, Notify};
+use tracing::{debug, info, warn};
+const FRAME_SAMPLES: usize = 960;
+const CHANNELS: usize = 2;
+const SAMPLES_PER_FRAME: usize = FRAME_SAMPLES * CHANNELS;
+/// How far ahead (in 20 ms frames) trailing-silence trimming decodes to see whether audio resumes.
+const SILENCE_LOOKAHEAD_FRAMES: u64 = 1500;
+/// Blocks trailing-silence trimming may decode ahead per frame sent, so a long
+/// lookahead is spread over many frames instead of stalling output.
+const SILENCE_SCAN_BLOCKS: usize = 8;
+
+/// Result of one step of looking ahead through a silence.
+enum Lookahead {
+    /// Audio resumes this many frames ahead, or nothing is known past that point.
+    Audible(u64),
+    /// The track is silent until its end.
+    Silent,
+    /// Not decided yet; continue with the next frame.
+    Pending,
+}
+
 async fn cleanup_temp_paths(paths: &mut Vec<PathBuf>) {
     for p in paths.drain(..) {
+        crate::resolver::forget_download(&p.to_string_lossy());
         let _ = tokio::fs::remove_file(p).await;
     }
 }
@@ -24,10 +58,67 @@ pub struct EqBandParam {
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[serde(tag = "op")]
 pub enum PlayerEvent {
-    TrackStart { id: String, uri: String },
-    TrackEnd { id: String },
+    TrackStart {
+        id: String,
+        uri: String,
+        track_id: String,
+    },
+    TrackEnd {
+        id: String,
+        track_id: String,
+    },
     QueueUpdate,
     LoopModeChange(LoopMode),
+    VoiceConnected {
+        id: String,
+        guild_id: String,
+    },
+    VoiceDisconnected {
+        id: String,
+        guild_id: String,
+        code: Option<u16>,
+        reason: String,
+    },
+    SilenceDetected {
+        id: String,
+        position_ms: u64,
+        duration_ms: u64,
+    },
+    SubscriberDropped {
+        id: String,
+        subscriber_id: String,
+        kind: String,
+        lag_ms: u64,
+    },
+    StreamTitleChanged {
+        id: String,
+        track_id: String,
+        stream_title: String,
+        title: String,
+        author: String,
+    },
+    TrackTagsRead {
+        id: String,
+        track_id: String,
+        title: String,
+        author: String,
+        album: Option<String>,
+        length_ms: u64,
+    },
+    LyricsLine {
+        id: String,
+        track_id: String,
+        index: usize,
+        time_ms: u64,
+        text: String,
+    },
+    ChapterStart {
+        id: String,
+        track_id: String,
+        index: usize,
+        title: String,
+        start_ms: u64,
+    },
 }
 
 #[derive(Clone)]
@@ -35,6 +126,8 @@ struct PlayerCtrl {
     pause_tx: broadcast::Sender<bool>,
     stop_tx: broadcast::Sender<()>,
     skip_tx: broadcast::Sender<()>,
+    /// Position (ms) to fast-forward to.
+    seek_tx: broadcast::Sender<u64>,
     filters: Arc<Mutex<Filters>>,
 }
 
@@ -42,21 +135,38 @@ pub struct Player {
     id: String,
     uri: String,
     ctrl: PlayerCtrl,
-    out_tx: broadcast::Sender<Bytes>,
+    out_tx: broadcast::Sender<AudioFrame>,
+    level_tx: broadcast::Sender<FrameLevels>,
+    silence: Mutex<SilenceOptions>,
     metadata: Arc<Mutex<serde_json::Value>>,
     track_info: Arc<Mutex<InternalTrackInfo>>,
+    artwork: Mutex<Option<Artwork>>,
+    lyrics: Mutex<Option<Arc<Lyrics>>>,
     queue: Arc<Mutex<Vec<TrackItem>>>,
     loop_mode: Arc<Mutex<LoopMode>>,
     event_tx: broadcast::Sender<PlayerEvent>,
     cfg: std::sync::Arc<EffectiveConfig>,
+    sources: Arc<SourceRegistry>,
     queue_notify: Arc<Notify>,
+    opus_encoders: OpusEncoders,
+    hls: HlsSlot,
+    voice: Mutex<Option<VoiceHandle>>,
+    sinks: Mutex<HashMap<String, RtpSink>>,
+    subscribers: SubscriberRegistry,
+    pacing: Arc<PacingStats>,
 }
 
 impl Player {
-    pub fn new(id: &str, uri: &str, cfg: std::sync::Arc<EffectiveConfig>) -> Result<Self> {
+    pub fn new(
+        id: &str,
+        uri: &str,
+        cfg: std::sync::Arc<EffectiveConfig>,
+        sources: Arc<SourceRegistry>,
+    ) -> Result<Self> {
         let (pause_tx, _) = broadcast::channel(8);
         let (stop_tx, _) = broadcast::channel(1);
         let (skip_tx, _) = broadcast::channel(8);
+        let (seek_tx, _) = broadcast::channel(8);
         let filters = Arc::new(Mutex::new(Filters::default()));
         {
             let mut f = futures::executor::block_on(filters.lock());
@@ -64,80 +174,146 @@ impl Player {
         }
         let (out_tx, _) = broadcast::channel(1024);
         let (event_tx, _) = broadcast::channel(128);
+        let (level_tx, _) = broadcast::channel(64);
         Ok(Self {
             id: id.into(),
             uri: uri.into(),
-            ctrl: PlayerCtrl { pause_tx, stop_tx, skip_tx, filters },
+            ctrl: PlayerCtrl { pause_tx, stop_tx, skip_tx, seek_tx, filters },
             out_tx,
+            level_tx,
+            silence: Mutex::new(SilenceOptions::from_config(&cfg)),
             metadata: Arc::new(Mutex::new(serde_json::json!({}))),
             track_info: Arc::new(Mutex::new(InternalTrackInfo::new(id, uri))),
+            artwork: Mutex::new(None),
+            lyrics: Mutex::new(None),
             queue: Arc::new(Mutex::new(Vec::new())),
             loop_mode: Arc::new(Mutex::new(LoopMode::None)),
             event_tx,
             cfg,
+            sources,
             queue_notify: Arc::new(Notify::new()),
+            opus_encoders: Default::default(),
+            hls: Default::default(),
+            voice: Mutex::new(None),
+            sinks: Mutex::new(HashMap::new()),
+            subscribers: Default::default(),
+            pacing: Default::default(),
         })
     }
 
     pub async fn run(self: Arc<Self>) -> Result<()> {
         let mut current_uri = self.uri.clone();
         let mut current_prepared: Option<PathBuf> = None;
+        let mut current_lyrics: Option<String> = None;
+        let mut frame_seq = 0u64;
         'session: loop {
-            let source_path = if let Some(p) = current_prepared.take() {
-                p
+            let source = if let Some(p) = current_prepared.take() {
+                Source::File(p)
             } else {
-                let resolved_uri = if crate::config::resolver_enabled(&self.cfg) {
-                    match crate::resolver::resolve_with_retry(&self.cfg, &current_uri).await {
-                        Ok(s) => s,
-                        Err(_) => current_uri.clone(),
-                    }
-                } else {
-                    current_uri.clone()
+                let resolved_uri = match self.sources.resolve(&current_uri).await {
+                    Ok(s) => s,
+                    Err(_) => current_uri.clone(),
                 };
-                prepare_local_source(&resolved_uri).await?
+                prepare_source(&resolved_uri).await?
             };
             let mut temp_paths: Vec<PathBuf> = Vec::new();
-            if is_resonix_temp_file(&source_path) {
-                temp_paths.push(source_path.clone());
+            let mut chapters: Vec<Chapter> = Vec::new();
+            if let Source::File(path) = &source {
+                if is_resonix_temp_file(path) {
+                    temp_paths.push(path.clone());
+                }
             }
             {
                 let mut ti = self.track_info.lock().await;
-                ti.title =
-                    source_path.file_stem().and_then(|s| s.to_str()).unwrap_or(&current_uri).to_string();
+                ti.title = source.stem().unwrap_or_else(|| current_uri.clone());
                 ti.uri = current_uri.clone();
                 ti.identifier = current_uri.clone();
                 ti.source_name = if current_uri.starts_with("http") { "http".into() } else { "file".into() };
                 ti.position_ms = 0;
+                ti.length_ms = 0;
+                ti.author.clear();
+                ti.album = None;
+                ti.isrc = None;
+                ti.artwork_url = None;
+                ti.chapters.clear();
             }
-            let _ =
-                self.event_tx.send(PlayerEvent::TrackStart { id: self.id.clone(), uri: current_uri.clone() });
-            let mut decoder = match FfmpegDecoder::open(&source_path, &self.cfg.ffmpeg_path) {
+            *self.artwork.lock().await = None;
+            // Lyrics sent with the request win over a sidecar file, which wins over tags.
+            *self.lyrics.lock().await = current_lyrics
+                .as_deref()
+                .and_then(|text| Lyrics::from_lrc(text, LyricsSource::Request))
+                .map(Arc::new);
+            if let Source::File(path) = &source {
+                let owned = path.clone();
+                let read =
+                    tokio::task::spawn_blocking(move || (read_tags(&owned), Lyrics::from_sidecar(&owned)));
+                if let Ok((tags, sidecar)) = read.await {
+                    if let Some(sidecar) = sidecar {
+                        self.lyrics.lock().await.get_or_insert(Arc::new(sidecar));
+                    }
+                    match tags {
+                        Ok(tags) => self.apply_tags(&tags).await,
+                        Err(e) => debug!(player=%self.id, error = %e, "no readable tags"),
+                    }
+                }
+                chapters = self.find_chapters(path).await;
+                self.track_info.lock().await.chapters = chapters.clone();
+            }
+            let track_id: Arc<str> = uuid::Uuid::new_v4().to_string().into();
+            let _ = self.event_tx.send(PlayerEvent::TrackStart {
+                id: self.id.clone(),
+                uri: current_uri.clone(),
+                track_id: track_id.to_string(),
+            });
+            let mut decoder = match Decoder::open(&source, &self.cfg).await {
                 Ok(d) => d,
                 Err(e) => {
                     cleanup_temp_paths(&mut temp_paths).await;
                     return Err(e);
                 }
             };
+            let mut icy = decoder.icy();
+            let mut tags = decoder.take_tags();
+            let mut lyrics = self.lyrics.lock().await.clone();
+            let mut next_lyric = 0usize;
+            let mut next_chapter = 0usize;
             {
                 let mut ti = self.track_info.lock().await;
-                ti.is_seekable = ti.length_ms > 0;
+                // Seeks are applied to local files only.
+                ti.is_seekable = ti.length_ms > 0 && matches!(source, Source::File(_));
                 ti.is_stream = ti.length_ms == 0;
+                if let Some(station) = icy.as_mut().and_then(|rx| rx.borrow_and_update().station.clone()) {
+                    ti.is_seekable = false;
+                    ti.is_stream = true;
+                    ti.author = station;
+                }
+                if let Some(info) = decoder.stream_info() {
+                    ti.is_stream = info.live;
+                    ti.is_seekable = false;
+                    ti.length_ms = info.duration_ms;
+                }
             }
-            const FRAME_SAMPLES: usize = 960;
-            const CHANNELS: usize = 2;
-            const SAMPLES_PER_FRAME: usize = FRAME_SAMPLES * CHANNELS;
             let mut buf: Vec<i16> = Vec::with_capacity(SAMPLES_PER_FRAME * 8);
             let (mut pause_rx, mut stop_rx, mut skip_rx) = self.ctrl_channels();
+            let mut seek_rx = self.ctrl.seek_tx.subscribe();
             let mut paused = false;
             let mut sent: u64 = 0;
             let mut head = 0usize;
-            let mut tick = tokio::time::interval(Duration::from_millis(20));
-            tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            let mut next_flags = FLAG_TRACK_START;
+            let mut clock = PacingClock::new(self.cfg.pacing_max_burst_frames, self.pacing.clone());
             let mut eos = false;
             let mut skipped = false;
-            loop {
-                tick.tick().await;
-                if let Ok(_) = skip_rx.try_recv() {
+            // Frames at the start already found silent, while leading silence is still
+            // being looked for; `None` once that is settled.
+            let mut leading_checked = Some(0u64);
+            let mut silent_frames = 0u64;
+            let mut silence_reported = false;
+            let mut audible_until = 0u64;
+            // Frames after the current one already found silent by the lookahead.
+            let mut checked_silent = 0u64;
+            'track: loop {
+                clock.wait().await;
+                if skip_rx.try_recv().is_ok() {
                     skipped = true;
                     break;
                 }
@@ -148,52 +324,205 @@ impl Player {
                     Err(_) => {}
                 }
                 if let Ok(p) = pause_rx.try_recv() {
+                    if paused && !p {
+                        next_flags |= FLAG_DISCONTINUITY;
+                    }
                     paused = p;
                 }
                 if paused {
+                    clock.hold();
                     continue;
                 }
-                while buf.len().saturating_sub(head) < SAMPLES_PER_FRAME * 4 && !eos {
-                    match decoder.next_pcm_block().await? {
-                        Some(mut block) => {
-                            if block.l.is_empty() {
-                                break;
-                            }
-                            let vol = {
-                                let mut f = self.ctrl.filters.lock().await;
-                                biquad_eq_in_place(&mut block.l, &mut block.r, &mut *f);
-                                f.volume
-                            };
-                            buf.reserve(block.l.len() * 2);
-                            for i in 0..block.l.len() {
-                                buf.push((block.l[i] * vol * 32767.0).clamp(-32768.0, 32767.0) as i16);
-                                buf.push((block.r[i] * vol * 32767.0).clamp(-32768.0, 32767.0) as i16);
-                            }
+                if let (Ok(target_ms), Source::File(path)) = (seek_rx.try_recv(), &source) {
+                    match decoder.seek(path, &self.cfg, target_ms).await {
+                        Ok(()) => {
+                            buf.clear();
+                            head = 0;
+                            eos = false;
+                            silent_frames = 0;
+                            checked_silent = 0;
+                            leading_checked = None;
+                            sent = target_ms / 20;
+                            self.track_info.lock().await.position_ms = sent * 20;
+                            next_flags |= FLAG_DISCONTINUITY;
+                            clock.hold();
+                            continue;
                         }
-                        None => {
-                            eos = true;
+                        Err(e) => warn!(player=%self.id, error = %e, target_ms, "seek failed"),
+                    }
+                }
+                if let Some(rx) = icy.as_mut().filter(|rx| rx.has_changed().unwrap_or(false)) {
+                    let meta = rx.borrow_and_update().clone();
+                    self.apply_stream_title(&meta, &track_id).await;
+                }
+                if let Some(rx) = tags.as_mut() {
+                    match rx.try_recv() {
+                        Ok(read) => {
+                            tags = None;
+                            self.apply_tags(&read).await;
+                            self.announce_tags(&track_id).await;
+                            lyrics = self.lyrics.lock().await.clone();
                         }
+                        Err(TryRecvError::Closed) => tags = None,
+                        Err(TryRecvError::Empty) => {}
                     }
                 }
-                if buf.len().saturating_sub(head) >= SAMPLES_PER_FRAME {
-                    let frame = &buf[head..head + SAMPLES_PER_FRAME];
-                    let bytes = bytemuck::cast_slice(frame);
-                    let _ = self.out_tx.send(Bytes::copy_from_slice(bytes));
-                    sent += 1;
-                    head += SAMPLES_PER_FRAME;
-                    if sent % 5 == 0 {
-                        let mut ti = self.track_info.lock().await;
-                        ti.position_ms = sent * 20;
+                let silence = *self.silence.lock().await;
+                let silence_threshold = if silence.is_active() {
+                    let vol = self.ctrl.filters.lock().await.volume;
+                    (vol > 0.0).then(|| silence.threshold_at_volume(vol))
+                } else {
+                    None
+                };
+                if let Some(checked) = leading_checked.as_mut() {
+                    match silence_threshold.filter(|_| silence.trim_leading) {
+                        Some(threshold) => {
+                            let min_frames = silence.min_duration_ms.div_ceil(20);
+                            if let Some(silent) = self
+                                .scan_leading_silence(
+                                    &mut decoder,
+                                    &mut buf,
+                                    head,
+                                    &mut eos,
+                                    threshold,
+                                    checked,
+                                )
+                                .await?
+                            {
+                                leading_checked = None;
+                                if silent > 0 && (silent >= min_frames || eos) {
+                                    info!(player=%self.id, trimmed_ms = silent * 20, "trimmed leading silence");
+                                    head += silent as usize * SAMPLES_PER_FRAME;
+                                    sent += silent;
+                                    silent_frames = silent;
+                                }
+                            }
+                            clock.hold();
+                            continue;
+                        }
+                        None => leading_checked = None,
                     }
-                    if head >= SAMPLES_PER_FRAME * 8 && head > buf.len() / 2 {
-                        buf.drain(0..head);
-                        head = 0;
+                }
+                for n in 0..clock.due() {
+                    let late = n > 0;
+                    // Nothing decoded yet: this frame is late no matter what, so time
+                    // restarts from when the decoder delivers rather than bursting after it.
+                    let starved = buf.len().saturating_sub(head) < SAMPLES_PER_FRAME && !eos;
+                    if starved && sent > 0 {
+                        self.pacing.underrun();
+                    }
+                    while buf.len().saturating_sub(head) < SAMPLES_PER_FRAME * 4 && !eos {
+                        match self.decode_block(&mut decoder, &mut buf).await? {
+                            Some(0) => break,
+                            Some(_) => {}
+                            None => eos = true,
+                        }
+                    }
+                    if starved {
+                        clock.restart();
+                    }
+                    if buf.len().saturating_sub(head) >= SAMPLES_PER_FRAME {
+                        let frame = &buf[head..head + SAMPLES_PER_FRAME];
+                        if let Some(threshold) = silence_threshold {
+                            if is_silent(frame, threshold) {
+                                silent_frames += 1;
+                            } else {
+                                silent_frames = 0;
+                                silence_reported = false;
+                            }
+                            let silent_ms = silent_frames * 20;
+                            if silent_frames > 0 && silent_ms >= silence.min_duration_ms {
+                                if silence.detect && !silence_reported {
+                                    silence_reported = true;
+                                    let _ = self.event_tx.send(PlayerEvent::SilenceDetected {
+                                        id: self.id.clone(),
+                                        position_ms: (sent + 1).saturating_sub(silent_frames) * 20,
+                                        duration_ms: silent_ms,
+                                    });
+                                }
+                                if silence.auto_skip_secs.is_some_and(|s| silent_ms >= s * 1000) {
+                                    info!(player=%self.id, silent_ms, "auto-skipping silent track");
+                                    skipped = true;
+                                    break 'track;
+                                }
+                                if silence.trim_trailing && sent >= audible_until {
+                                    match self
+                                        .look_ahead(
+                                            &mut decoder,
+                                            &mut buf,
+                                            head,
+                                            &mut eos,
+                                            threshold,
+                                            &mut checked_silent,
+                                        )
+                                        .await?
+                                    {
+                                        Lookahead::Audible(ahead) => {
+                                            audible_until = sent + ahead;
+                                            checked_silent = 0;
+                                        }
+                                        Lookahead::Silent => {
+                                            info!(player=%self.id, "trimmed trailing silence");
+                                            break 'track;
+                                        }
+                                        Lookahead::Pending => {}
+                                    }
+                                }
+                            }
+                        }
+                        let frame = &buf[head..head + SAMPLES_PER_FRAME];
+                        let bytes = bytemuck::cast_slice(frame);
+                        let out = AudioFrame {
+                            seq: frame_seq,
+                            timestamp: frame_seq * FRAME_SAMPLES as u64,
+                            track_id: track_id.clone(),
+                            flags: std::mem::take(&mut next_flags),
+                            pcm: Bytes::copy_from_slice(bytes),
+                        };
+                        frame_seq += 1;
+                        let _ = self.out_tx.send(out);
+                        if self.level_tx.receiver_count() > 0 {
+                            let _ = self.level_tx.send(FrameLevels::measure(frame));
+                        }
+                        sent += 1;
+                        head += SAMPLES_PER_FRAME;
+                        checked_silent = checked_silent.saturating_sub(1);
+                        if sent % 5 == 0 {
+                            let mut ti = self.track_info.lock().await;
+                            ti.position_ms = sent * 20;
+                        }
+                        if let Some(l) = lyrics.as_deref() {
+                            self.announce_lyrics(l, &mut next_lyric, sent * 20, &track_id);
+                        }
+                        self.announce_chapter(&chapters, &mut next_chapter, sent * 20, &track_id);
+                        if head >= SAMPLES_PER_FRAME * 8 && head > buf.len() / 2 {
+                            buf.drain(0..head);
+                            head = 0;
+                        }
+                        clock.advance(late);
+                        if starved {
+                            break;
+                        }
+                    } else if eos {
+                        break 'track;
+                    } else {
+                        clock.hold();
+                        break;
                     }
-                } else if eos {
-                    break;
                 }
             }
-            let _ = self.event_tx.send(PlayerEvent::TrackEnd { id: self.id.clone() });
+            // Sent after the last frame rather than flagged on it, so no frame waits
+            // to learn whether it is the last one.
+            let _ = self.out_tx.send(AudioFrame {
+                seq: frame_seq,
+                timestamp: frame_seq * FRAME_SAMPLES as u64,
+                track_id: track_id.clone(),
+                flags: FLAG_TRACK_END,
+                pcm: Bytes::new(),
+            });
+            let _ = self
+                .event_tx
+                .send(PlayerEvent::TrackEnd { id: self.id.clone(), track_id: track_id.to_string() });
 
             let loop_mode = *self.loop_mode.lock().await;
             if matches!(loop_mode, LoopMode::None) {
@@ -208,31 +537,277 @@ impl Player {
                         current_uri.clone(),
                         current_prepared.as_ref().map(|p| p.to_string_lossy().to_string()),
                         metadata,
+                        current_lyrics.clone(),
                     )
                     .await;
             }
 
-            if let Some((next_uri, next_prepared)) = self.next_track(skipped).await {
-                info!(player=%self.id, %next_uri, "advancing to queued track");
-                current_uri = next_uri;
-                current_prepared = next_prepared.map(PathBuf::from);
-                continue;
-            } else {
-                info!(player=%self.id, "queue empty, waiting for next track");
-                match self.wait_for_next_track().await {
-                    Some((next_uri, next_prepared)) => {
-                        info!(player=%self.id, %next_uri, "received deferred queue track");
-                        current_uri = next_uri;
-                        current_prepared = next_prepared.map(PathBuf::from);
-                        continue;
+            let next = match self.next_track(skipped).await {
+                Some(next) => {
+                    info!(player=%self.id, next_uri=%next.uri, "advancing to queued track");
+                    next
+                }
+                None => {
+                    info!(player=%self.id, "queue empty, waiting for next track");
+                    match self.wait_for_next_track().await {
+                        Some(next) => {
+                            info!(player=%self.id, next_uri=%next.uri, "received deferred queue track");
+                            next
+                        }
+                        None => break 'session,
                     }
-                    None => break 'session,
                 }
-            }
+            };
+            // A track replayed by track loop keeps the lyrics it was queued with.
+            current_lyrics = match next.lyrics {
+                Some(text) => Some(text),
+                None if next.uri == current_uri => current_lyrics.take(),
+                None => None,
+            };
+            current_uri = next.uri;
+            current_prepared = next.prepared_path.map(PathBuf::from);
         }
         Ok(())
     }
 
+    /// Shows a radio stream's current title as the track's title and author.
+    async fn apply_stream_title(&self, meta: &IcyMetadata, track_id: &str) {
+        let Some((author, title)) = meta.author_and_title() else {
+            return;
+        };
+        {
+            let mut ti = self.track_info.lock().await;
+            ti.title = title.clone();
+            ti.author = author.clone();
+        }
+        info!(player=%self.id, %title, %author, "stream title changed");
+        let _ = self.event_tx.send(PlayerEvent::StreamTitleChanged {
+            id: self.id.clone(),
+            track_id: track_id.to_string(),
+            stream_title: meta.stream_title.clone().unwrap_or_default(),
+            title,
+            author,
+        });
+    }
+
+    /// Fills the current track's info from its embedded tags. The file name stays
+    /// the title when there is none.
+    async fn apply_tags(&self, tags: &TrackTags) {
+        let mut ti = self.track_info.lock().await;
+        if let Some(title) = &tags.title {
+            ti.title = title.clone();
+        }
+        if let Some(artist) = &tags.artist {
+            ti.author = artist.clone();
+        }
+        ti.album = tags.album.clone();
+        ti.isrc = tags.isrc.clone();
+        if tags.duration_ms > 0 && ti.length_ms == 0 {
+            ti.length_ms = tags.duration_ms;
+            ti.is_stream = false;
+        }
+        if let Some(l) = &tags.lyrics {
+            self.lyrics.lock().await.get_or_insert_with(|| Arc::new(l.clone()));
+        }
+        if let Some(art) = &tags.artwork {
+            ti.artwork_url = Some(format!("/v0/players/{}/artwork", self.id));
+            *self.artwork.lock().await = Some(art.clone());
+        }
+    }
+
+    /// Reports tags that were read after the track had already started.
+    async fn announce_tags(&self, track_id: &str) {
+        let ti = self.track_info.lock().await.clone();
+        info!(player=%self.id, title=%ti.title, author=%ti.author, "track tags read");
+        let _ = self.event_tx.send(PlayerEvent::TrackTagsRead {
+            id: self.id.clone(),
+            track_id: track_id.to_string(),
+            title: ti.title,
+            author: ti.author,
+            album: ti.album,
+            length_ms: ti.length_ms,
+        });
+    }
+
+    /// Reports the latest lyrics line that playback has reached since the last call.
+    fn announce_lyrics(&self, lyrics: &Lyrics, next: &mut usize, position_ms: u64, track_id: &str) {
+        let mut reached = None;
+        while lyrics.lines.get(*next).is_some_and(|l| l.time_ms <= position_ms) {
+            reached = Some(*next);
+            *next += 1;
+        }
+        let Some(index) = reached else { return };
+        let line = &lyrics.lines[index];
+        let _ = self.event_tx.send(PlayerEvent::LyricsLine {
+            id: self.id.clone(),
+            track_id: track_id.to_string(),
+            index,
+            time_ms: line.time_ms,
+            text: line.text.clone(),
+        });
+    }
+
+    /// Chapters of a local file: its own chapter marks, read by ffmpeg, or else the
+    /// timestamps in the description of a resolved YouTube video.
+    async fn find_chapters(&self, path: &std::path::Path) -> Vec<Chapter> {
+        if self.cfg.ffmpeg_available {
+            match chapters::probe_chapters(&self.cfg.ffmpeg_path, path).await {
+                Ok(found) if !found.is_empty() => return found,
+                Ok(_) => {}
+                Err(e) => debug!(player=%self.id, error = %e, "chapter probe failed"),
+            }
+        }
+        let Some(description) = crate::resolver::description_of(&path.to_string_lossy()) else {
+            return Vec::new();
+        };
+        let length_ms = self.track_info.lock().await.length_ms;
+        chapters::from_description(&description, length_ms)
+    }
+
+    /// Reports the latest chapter that playback has entered since the last call.
+    fn announce_chapter(&self, chapters: &[Chapter], next: &mut usize, position_ms: u64, track_id: &str) {
+        let mut reached = None;
+        while chapters.get(*next).is_some_and(|c| c.start_ms <= position_ms) {
+            reached = Some(*next);
+            *next += 1;
+        }
+        let Some(index) = reached else { return };
+        let chapter = &chapters[index];
+        let _ = self.event_tx.send(PlayerEvent::ChapterStart {
+            id: self.id.clone(),
+            track_id: track_id.to_string(),
+            index,
+            title: chapter.title.clone(),
+            start_ms: chapter.start_ms,
+        });
+    }
+
+    /// Jumps to the start of the chapter after the one playing. Returns it, or
+    /// `None` if the current track has no later chapter or cannot seek.
+    pub async fn next_chapter(&self) -> Option<Chapter> {
+        let ti = self.track_info.lock().await;
+        if !ti.is_seekable {
+            return None;
+        }
+        let index = chapters::chapter_at(&ti.chapters, ti.position_ms).map_or(0, |i| i + 1);
+        let chapter = ti.chapters.get(index).filter(|c| c.start_ms > ti.position_ms)?.clone();
+        let _ = self.ctrl.seek_tx.send(chapter.start_ms);
+        Some(chapter)
+    }
+
+    /// Lyrics of the current track.
+    pub async fn lyrics(&self) -> Option<Arc<Lyrics>> {
+        self.lyrics.lock().await.clone()
+    }
+
+    /// Embedded cover art of the current track.
+    pub async fn artwork(&self) -> Option<Artwork> {
+        self.artwork.lock().await.clone()
+    }
+
+    /// Decodes one block, applies EQ and volume and appends it to `buf`. Returns
+    /// `None` at the end of the stream.
+    async fn decode_block(&self, decoder: &mut Decoder, buf: &mut Vec<i16>) -> Result<Option<usize>> {
+        let Some(mut block) = decoder.next_pcm_block().await? else {
+            return Ok(None);
+        };
+        if block.l.is_empty() {
+            return Ok(Some(0));
+        }
+        let vol = {
+            let mut f = self.ctrl.filters.lock().await;
+            biquad_eq_in_place(&mut block.l, &mut block.r, &mut f);
+            f.volume
+        };
+        buf.reserve(block.l.len() * 2);
+        for i in 0..block.l.len() {
+            buf.push((block.l[i] * vol * 32767.0).clamp(-32768.0, 32767.0) as i16);
+            buf.push((block.r[i] * vol * 32767.0).clamp(-32768.0, 32767.0) as i16);
+        }
+        Ok(Some(block.l.len()))
+    }
+
+    /// Counts the silent frames from `head`, continuing after the `checked` ones
+    /// found by earlier calls and decoding at most `SILENCE_SCAN_BLOCKS` blocks, so
+    /// the caller gets back to its control checks. Returns the count once audio,
+    /// the end of the track or `SILENCE_LOOKAHEAD_FRAMES` is reached.
+    async fn scan_leading_silence(
+        &self,
+        decoder: &mut Decoder,
+        buf: &mut Vec<i16>,
+        head: usize,
+        eos: &mut bool,
+        threshold: i32,
+        checked: &mut u64,
+    ) -> Result<Option<u64>> {
+        let mut decoded = 0usize;
+        while *checked < SILENCE_LOOKAHEAD_FRAMES {
+            let pos = head + *checked as usize * SAMPLES_PER_FRAME;
+            if buf.len() < pos + SAMPLES_PER_FRAME {
+                if *eos {
+                    break;
+                }
+                if decoded == SILENCE_SCAN_BLOCKS {
+                    return Ok(None);
+                }
+                decoded += 1;
+                match self.decode_block(decoder, buf).await? {
+                    Some(0) => break,
+                    Some(_) => {}
+                    None => *eos = true,
+                }
+                continue;
+            }
+            if !is_silent(&buf[pos..pos + SAMPLES_PER_FRAME], threshold) {
+                break;
+            }
+            *checked += 1;
+        }
+        Ok(Some(*checked))
+    }
+
+    /// Looks ahead of the frame at `head` for audible audio, starting after the
+    /// `checked` frames already known to be silent and decoding at most
+    /// `SILENCE_SCAN_BLOCKS` blocks before handing back to the send loop.
+    async fn look_ahead(
+        &self,
+        decoder: &mut Decoder,
+        buf: &mut Vec<i16>,
+        head: usize,
+        eos: &mut bool,
+        threshold: i32,
+        checked: &mut u64,
+    ) -> Result<Lookahead> {
+        let mut decoded = 0usize;
+        loop {
+            let ahead = *checked + 1;
+            if ahead >= SILENCE_LOOKAHEAD_FRAMES {
+                return Ok(Lookahead::Audible(ahead));
+            }
+            let pos = head + ahead as usize * SAMPLES_PER_FRAME;
+            if buf.len() < pos + SAMPLES_PER_FRAME {
+                if *eos {
+                    // A trailing partial frame is dropped by the send loop anyway.
+                    return Ok(Lookahead::Silent);
+                }
+                if decoded == SILENCE_SCAN_BLOCKS {
+                    return Ok(Lookahead::Pending);
+                }
+                decoded += 1;
+                match self.decode_block(decoder, buf).await? {
+                    Some(0) => return Ok(Lookahead::Audible(ahead)),
+                    Some(_) => {}
+                    None => *eos = true,
+                }
+                continue;
+            }
+            if !is_silent(&buf[pos..pos + SAMPLES_PER_FRAME], threshold) {
+                return Ok(Lookahead::Audible(ahead));
+            }
+            *checked = ahead;
+        }
+    }
+
     fn ctrl_channels(&self) -> (broadcast::Receiver<bool>, broadcast::Receiver<()>, broadcast::Receiver<()>) {
         (self.ctrl.pause_tx.subscribe(), self.ctrl.stop_tx.subscribe(), self.ctrl.skip_tx.subscribe())
     }
@@ -268,9 +843,90 @@ impl Player {
             update_eq_filters(&mut fl);
         });
     }
-    pub fn subscribe(&self) -> broadcast::Receiver<Bytes> {
+    pub fn subscribe(&self) -> broadcast::Receiver<AudioFrame> {
         self.out_tx.subscribe()
     }
+    /// Wraps a subscription to one of this player's channels in a bounded queue
+    /// governed by the `[subscribers]` policy, listed in `subscriber_stats`.
+    pub fn subscribe_queued<T: Clone + Send + 'static>(
+        &self,
+        kind: &'static str,
+        rx: broadcast::Receiver<T>,
+    ) -> Subscription<T> {
+        let events = self.event_tx.clone();
+        let id = self.id.clone();
+        Subscription::start(
+            rx,
+            kind,
+            QueueSettings::from_config(&self.cfg),
+            self.subscribers.clone(),
+            move |s| {
+                let _ = events.send(PlayerEvent::SubscriberDropped {
+                    id,
+                    subscriber_id: s.id,
+                    kind: s.kind.to_string(),
+                    lag_ms: s.lag_ms,
+                });
+            },
+        )
+    }
+    pub fn subscriber_stats(&self) -> Vec<SubscriberStats> {
+        self.subscribers.iter().map(|s| s.stats()).collect()
+    }
+    pub fn pacing_stats(&self) -> PacingSnapshot {
+        self.pacing.snapshot()
+    }
+    pub async fn subscribe_opus(&self, settings: OpusSettings) -> Result<broadcast::Receiver<Bytes>> {
+        opus::subscribe(&self.opus_encoders, &self.cfg.ffmpeg_path, settings, self.out_tx.subscribe()).await
+    }
+    pub async fn hls_packager(&self) -> Result<Arc<HlsPackager>> {
+        let settings = HlsSettings::from_config(&self.cfg);
+        hls::ensure_started(&self.hls, &self.cfg.ffmpeg_path, settings, self.out_tx.subscribe()).await
+    }
+    /// Connects (or reconnects) this player to a Discord voice server, replacing any
+    /// existing voice connection.
+    pub async fn connect_voice(&self, info: VoiceServerInfo) -> Result<()> {
+        let opus_rx = self.subscribe_opus(OpusSettings::from_config(&self.cfg)).await?;
+        let handle = voice::connect(&self.id, info, opus_rx, self.event_tx.clone());
+        *self.voice.lock().await = Some(handle);
+        Ok(())
+    }
+    pub async fn disconnect_voice(&self) -> bool {
+        self.voice.lock().await.take().is_some()
+    }
+    pub async fn voice_status(&self) -> Option<VoiceStatus> {
+        self.voice.lock().await.as_ref().map(|v| v.status())
+    }
+    pub async fn add_sink(&self, req: RtpSinkReq) -> Result<RtpSinkInfo> {
+        let sink = match req.codec {
+            RtpCodec::Opus => {
+                let rx = self.subscribe_opus(OpusSettings::from_config(&self.cfg)).await?;
+                RtpSink::start(req, rx).await?
+            }
+            RtpCodec::L16 => RtpSink::start(req, self.out_tx.subscribe()).await?,
+        };
+        let info = sink.info();
+        self.sinks.lock().await.insert(sink.id().to_string(), sink);
+        Ok(info)
+    }
+    pub async fn remove_sink(&self, sink_id: &str) -> bool {
+        self.sinks.lock().await.remove(sink_id).is_some()
+    }
+    pub async fn sinks(&self) -> Vec<RtpSinkInfo> {
+        self.sinks.lock().await.values().map(|s| s.info()).collect()
+    }
+    pub async fn silence_options(&self) -> SilenceOptions {
+        *self.silence.lock().await
+    }
+    pub async fn update_silence(&self, update: &SilenceUpdate) -> Result<SilenceOptions, String> {
+        let mut opts = self.silence.lock().await;
+        opts.apply(update)?;
+        Ok(*opts)
+    }
+    /// Per-frame output levels; only measured while someone is subscribed.
+    pub fn subscribe_levels(&self) -> broadcast::Receiver<FrameLevels> {
+        self.level_tx.subscribe()
+    }
     pub fn subscribe_events(&self) -> broadcast::Receiver<PlayerEvent> {
         self.event_tx.subscribe()
     }
@@ -315,9 +971,10 @@ impl Player {
         uri: String,
         prepared_path: Option<String>,
         metadata: serde_json::Value,
+        lyrics: Option<String>,
     ) -> String {
         let mut q = self.queue.lock().await;
-        let item = TrackItem::new_with_prepared(&uri, prepared_path, metadata);
+        let item = TrackItem { lyrics, ..TrackItem::new_with_prepared(&uri, prepared_path, metadata) };
         let id = item.id.clone();
         q.push(item);
         info!(player=%self.id, queue_len=q.len(), %uri, "track enqueued");
@@ -325,40 +982,50 @@ impl Player {
         self.queue_notify.notify_one();
         id
     }
+    /// Appends several tracks at once (an expanded playlist) with a single queue update.
+    pub async fn enqueue_all(&self, items: Vec<(String, serde_json::Value)>) -> Vec<String> {
+        let mut q = self.queue.lock().await;
+        let mut ids = Vec::with_capacity(items.len());
+        for (uri, metadata) in items {
+            let item = TrackItem::new_with_prepared(&uri, None, metadata);
+            ids.push(item.id.clone());
+            q.push(item);
+        }
+        info!(player=%self.id, queue_len=q.len(), added=ids.len(), "playlist enqueued");
+        let _ = self.event_tx.send(PlayerEvent::QueueUpdate);
+        self.queue_notify.notify_one();
+        ids
+    }
     pub async fn set_loop_mode(&self, mode: LoopMode) {
         *self.loop_mode.lock().await = mode;
         let _ = self.event_tx.send(PlayerEvent::LoopModeChange(mode));
     }
-    async fn next_track(&self, skipped: bool) -> Option<(String, Option<String>)> {
+    async fn next_track(&self, skipped: bool) -> Option<TrackItem> {
         let mut q = self.queue.lock().await;
         let mode = *self.loop_mode.lock().await;
+        let replay = || TrackItem::new(&self.track_identifier(), serde_json::json!({}));
         if mode == LoopMode::Track && !skipped {
-            return Some((self.track_identifier(), None));
+            return Some(replay());
         }
         if q.is_empty() {
             return None;
         }
         match mode {
-            LoopMode::Track => Some((self.track_identifier(), None)),
+            LoopMode::Track => Some(replay()),
             LoopMode::Queue => {
                 let item = q.remove(0);
-                let uri = item.uri.clone();
-                let prepared = item.prepared_path.clone();
-                q.push(item);
-                info!(player=%self.id, queue_len=q.len(), %uri, "looping queue, advancing to next track");
-                Some((uri, prepared))
-            }
-            LoopMode::None => {
-                let item = q.remove(0);
-                Some((item.uri, item.prepared_path))
+                q.push(item.clone());
+                info!(player=%self.id, queue_len=q.len(), uri=%item.uri, "looping queue, advancing to next track");
+                Some(item)
             }
+            LoopMode::None => Some(q.remove(0)),
         }
     }
     pub async fn queue_snapshot(&self) -> Vec<TrackItem> {
         self.queue.lock().await.clone()
     }
 
-    async fn wait_for_next_track(&self) -> Option<(String, Option<String>)> {
+    async fn wait_for_next_track(&self) -> Option<TrackItem> {
         loop {
             if let Some(next) = self.dequeue_pending_track().await {
                 return Some(next);
@@ -372,13 +1039,12 @@ impl Player {
         }
     }
 
-    async fn dequeue_pending_track(&self) -> Option<(String, Option<String>)> {
+    async fn dequeue_pending_track(&self) -> Option<TrackItem> {
         let mut q = self.queue.lock().await;
         if q.is_empty() {
             None
         } else {
-            let item = q.remove(0);
-            Some((item.uri, item.prepared_path))
+            Some(q.remove(0))
         }
     }
 }
@@ -390,6 +1056,7 @@ pub struct InternalTrackInfo {
     pub uri: String,
     pub title: String,
     pub author: String,
+    pub album: Option<String>,
     pub length_ms: u64,
     pub position_ms: u64,
     pub is_stream: bool,
@@ -397,6 +1064,7 @@ pub struct InternalTrackInfo {
     pub artwork_url: Option<String>,
     pub isrc: Option<String>,
     pub source_name: String,
+    pub chapters: Vec<Chapter>,
 }
 impl InternalTrackInfo {
     fn new(id: &str, uri: &str) -> Self {
@@ -406,6 +1074,7 @@ impl InternalTrackInfo {
             uri: uri.into(),
             title: uri.into(),
             author: String::new(),
+            album: None,
             length_ms: 0,
             position_ms: 0,
             is_stream: true,
@@ -413,6 +1082,7 @@ impl InternalTrackInfo {
             artwork_url: None,
             isrc: None,
             source_name: "direct".into(),
+            chapters: Vec::new(),
         }
     }
 }
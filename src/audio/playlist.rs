@@ -0,0 +1,251 @@
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+use crate::audio::{m3u8, mpd};
+use crate::utils::xml::XmlNode;
+
+/// Playlist files larger than this are rejected rather than parsed.
+const MAX_PLAYLIST_BYTES: usize = 4 * 1024 * 1024;
+
+/// A track list file (M3U, PLS or XSPF) expanded into the URIs it references.
+#[derive(Debug, Clone)]
+pub struct Playlist {
+    pub name: String,
+    pub entries: Vec<PlaylistEntry>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PlaylistEntry {
+    /// Absolute URL or local path; relative entries are resolved against the playlist.
+    pub uri: String,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub length_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    M3u,
+    Pls,
+    Xspf,
+}
+
+/// Where a playlist was loaded from, for resolving relative entries.
+enum Base {
+    Url(Url),
+    Dir(PathBuf),
+}
+
+fn format_of(path: &str) -> Option<Format> {
+    let ext = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+    match ext.as_str() {
+        "m3u" | "m3u8" => Some(Format::M3u),
+        "pls" => Some(Format::Pls),
+        "xspf" => Some(Format::Xspf),
+        _ => None,
+    }
+}
+
+/// Loads `uri` as a playlist if its extension names one. Returns `None` for anything
+/// else, including `.m3u8` files that turn out to be HLS streams.
+pub async fn load_playlist(uri: &str) -> Result<Option<Playlist>> {
+    let fetched = match Url::parse(uri) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => {
+            let Some(format) = format_of(url.path()) else {
+                return Ok(None);
+            };
+            let resp = reqwest::get(url.clone())
+                .await
+                .context("http get")?
+                .error_for_status()
+                .context("bad status")?;
+            if resp.content_length().is_some_and(|n| n as usize > MAX_PLAYLIST_BYTES) {
+                bail!("playlist too large");
+            }
+            let base = Base::Url(resp.url().clone());
+            let bytes = resp.bytes().await.context("read playlist")?;
+            let name = url.path_segments().and_then(|mut s| s.next_back()).unwrap_or_default().to_string();
+            Fetched { format, text: decode_text(&bytes), base, file_name: name }
+        }
+        Ok(url) if url.scheme() == "file" => {
+            let Ok(path) = url.to_file_path() else {
+                bail!("invalid file:// path");
+            };
+            match read_local(&path).await? {
+                Some(loaded) => loaded,
+                None => return Ok(None),
+            }
+        }
+        _ => match read_local(Path::new(uri)).await? {
+            Some(loaded) => loaded,
+            None => return Ok(None),
+        },
+    };
+    let Fetched { format, text, base, file_name } = fetched;
+    if m3u8::is_playlist(&text) || mpd::is_manifest(&text) {
+        return Ok(None);
+    }
+    let fallback_name = Path::new(&file_name).file_stem().and_then(|s| s.to_str()).unwrap_or("Playlist");
+    let mut playlist = match format {
+        Format::M3u => parse_m3u(&text, &base),
+        Format::Pls => parse_pls(&text, &base),
+        Format::Xspf => parse_xspf(&text, &base)?,
+    };
+    if playlist.name.is_empty() {
+        playlist.name = fallback_name.to_string();
+    }
+    Ok(Some(playlist))
+}
+
+struct Fetched {
+    format: Format,
+    text: String,
+    base: Base,
+    file_name: String,
+}
+
+async fn read_local(path: &Path) -> Result<Option<Fetched>> {
+    let Some(format) = path.to_str().and_then(format_of) else {
+        return Ok(None);
+    };
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let meta = tokio::fs::metadata(path).await?;
+    if meta.len() as usize > MAX_PLAYLIST_BYTES {
+        bail!("playlist too large");
+    }
+    let bytes = tokio::fs::read(path).await.with_context(|| format!("read {}", path.display()))?;
+    let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_string();
+    Ok(Some(Fetched { format, text: decode_text(&bytes), base: Base::Dir(dir), file_name: name }))
+}
+
+/// UTF-8 (with or without BOM); plain `.m3u` exports are often Latin-1.
+fn decode_text(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(s) => s.to_string(),
+        Err(_) => bytes.iter().map(|&b| b as char).collect(),
+    }
+}
+
+fn parse_m3u(text: &str, base: &Base) -> Playlist {
+    let mut playlist = Playlist { name: String::new(), entries: Vec::new() };
+    let mut info: Option<(Option<u64>, String)> = None;
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if let Some(value) = line.strip_prefix("#PLAYLIST:") {
+            playlist.name = value.trim().to_string();
+        } else if let Some(value) = line.strip_prefix("#EXTINF:") {
+            // `#EXTINF:<seconds> [key="value" ...],<display title>`
+            let (head, title) = value.split_once(',').unwrap_or((value, ""));
+            let secs = head.split_whitespace().next().and_then(|s| s.parse::<f64>().ok());
+            let length = secs.filter(|s| *s > 0.0).map(|s| (s * 1000.0) as u64);
+            info = Some((length, title.trim().to_string()));
+        } else if !line.starts_with('#') {
+            let (length_ms, display) = info.take().unwrap_or_default();
+            let (author, title) = split_display_title(&display);
+            if let Some(uri) = resolve(line, base) {
+                playlist.entries.push(PlaylistEntry { uri, title, author, length_ms });
+            }
+        }
+    }
+    playlist
+}
+
+fn parse_pls(text: &str, base: &Base) -> Playlist {
+    // Entries are numbered (`File1`, `Title1`, `Length1`) and may come in any order.
+    let mut numbered: BTreeMap<u32, PlaylistEntry> = BTreeMap::new();
+    for line in text.lines().map(str::trim) {
+        let Some((key, value)) = line.split_once('=') else { continue };
+        let key = key.trim().to_ascii_lowercase();
+        let value = value.trim();
+        let split = key.find(|c: char| c.is_ascii_digit()).unwrap_or(key.len());
+        let Ok(n) = key[split..].parse::<u32>() else { continue };
+        let entry = numbered.entry(n).or_default();
+        match &key[..split] {
+            "file" => entry.uri = resolve(value, base).unwrap_or_default(),
+            "title" if !value.is_empty() => (entry.author, entry.title) = split_display_title(value),
+            "length" => {
+                entry.length_ms = value.parse::<i64>().ok().filter(|s| *s > 0).map(|s| s as u64 * 1000)
+            }
+            _ => {}
+        }
+    }
+    let entries = numbered.into_values().filter(|e| !e.uri.is_empty()).collect();
+    Playlist { name: String::new(), entries }
+}
+
+fn parse_xspf(text: &str, base: &Base) -> Result<Playlist> {
+    let root = XmlNode::parse(text).context("parse xspf")?;
+    if root.name != "playlist" {
+        bail!("not an XSPF playlist");
+    }
+    let text_of = |node: &XmlNode, name: &str| {
+        node.child(name).map(|c| c.text.trim().to_string()).filter(|t| !t.is_empty())
+    };
+    let mut entries = Vec::new();
+    for track in root.child("trackList").into_iter().flat_map(|l| l.children("track")) {
+        // A track may list several locations; the first usable one wins.
+        let Some(uri) = track.children("location").find_map(|l| resolve(l.text.trim(), base)) else {
+            continue;
+        };
+        entries.push(PlaylistEntry {
+            uri,
+            title: text_of(track, "title"),
+            author: text_of(track, "creator"),
+            length_ms: text_of(track, "duration").and_then(|d| d.parse().ok()).filter(|&d| d > 0),
+        });
+    }
+    Ok(Playlist { name: text_of(&root, "title").unwrap_or_default(), entries })
+}
+
+/// Splits an `Artist - Title` display string.
+fn split_display_title(display: &str) -> (Option<String>, Option<String>) {
+    let display = display.trim();
+    if display.is_empty() {
+        return (None, None);
+    }
+    match display.split_once(" - ") {
+        Some((a, t)) if !a.trim().is_empty() && !t.trim().is_empty() => {
+            (Some(a.trim().to_string()), Some(t.trim().to_string()))
+        }
+        _ => (None, Some(display.to_string())),
+    }
+}
+
+/// Turns a playlist entry into something the player can open: URLs stay as they are
+/// (relative ones are joined to the playlist URL), `file://` URLs become paths and
+/// relative paths are taken from the playlist's directory. Playlists fetched over
+/// HTTP only yield `http(s)` URLs, so they cannot point the node at local files.
+fn resolve(entry: &str, base: &Base) -> Option<String> {
+    let entry = entry.trim();
+    if entry.is_empty() {
+        return None;
+    }
+    match base {
+        Base::Url(base) => {
+            let url = base.join(entry).ok()?;
+            matches!(url.scheme(), "http" | "https").then(|| url.into())
+        }
+        Base::Dir(dir) => {
+            if let Ok(url) = Url::parse(entry) {
+                match url.scheme() {
+                    "file" => return url.to_file_path().ok().map(|p| p.to_string_lossy().into_owned()),
+                    // Single letters are Windows drive letters, not schemes.
+                    s if s.len() > 1 => return Some(url.into()),
+                    _ => {}
+                }
+            }
+            let path = PathBuf::from(entry);
+            if path.is_absolute() || entry.get(1..3) == Some(":\\") {
+                return Some(entry.to_string());
+            }
+            // Exports from Windows players use backslashes in relative paths.
+            let relative = if cfg!(windows) { entry.to_string() } else { entry.replace('\\', "/") };
+            Some(dir.join(relative).to_string_lossy().into_owned())
+        }
+    }
+}
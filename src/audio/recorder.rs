@@ -0,0 +1,226 @@
+use anyhow::{Context, Result};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    io::AsyncWriteExt,
+    sync::{broadcast, watch},
+};
+use tracing::{info, warn};
+
+use crate::audio::{encoder::FfmpegEncoder, frame::AudioFrame};
+
+const FINISH_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordingFormat {
+    Wav,
+    Flac,
+    Ogg,
+}
+
+impl RecordingFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            RecordingFormat::Wav => "wav",
+            RecordingFormat::Flac => "flac",
+            RecordingFormat::Ogg => "ogg",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            RecordingFormat::Wav => "audio/wav",
+            RecordingFormat::Flac => "audio/flac",
+            RecordingFormat::Ogg => "audio/ogg",
+        }
+    }
+
+    fn codec_args(self) -> [&'static str; 4] {
+        match self {
+            RecordingFormat::Wav => ["-c:a", "pcm_s16le", "-f", "wav"],
+            RecordingFormat::Flac => ["-c:a", "flac", "-f", "flac"],
+            RecordingFormat::Ogg => ["-c:a", "libopus", "-f", "ogg"],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordingState {
+    Recording,
+    Finished,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingInfo {
+    pub id: String,
+    pub player_id: String,
+    pub format: RecordingFormat,
+    pub state: RecordingState,
+    pub started_at: u64,
+    pub ended_at: Option<u64>,
+    pub duration_ms: u64,
+}
+
+pub struct Recording {
+    info: std::sync::Mutex<RecordingInfo>,
+    stop_tx: watch::Sender<bool>,
+    pub path: PathBuf,
+}
+
+impl Recording {
+    pub fn info(&self) -> RecordingInfo {
+        self.info.lock().map(|i| i.clone()).unwrap_or_else(|e| e.into_inner().clone())
+    }
+
+    /// Asks the writer to finalize the file; the state turns `finished` once ffmpeg exits.
+    pub fn stop(&self) {
+        let _ = self.stop_tx.send(true);
+    }
+
+    fn update(&self, f: impl FnOnce(&mut RecordingInfo)) {
+        if let Ok(mut i) = self.info.lock() {
+            f(&mut i);
+        }
+    }
+}
+
+/// All recordings known to this node, kept independent of players so finished
+/// files stay downloadable after a player is deleted.
+#[derive(Clone)]
+pub struct RecordingStore {
+    dir: PathBuf,
+    max_duration: Duration,
+    recordings: Arc<DashMap<String, Arc<Recording>>>,
+}
+
+impl RecordingStore {
+    pub fn new(dir: PathBuf, max_duration: Duration) -> Self {
+        Self { dir, max_duration, recordings: Arc::new(DashMap::new()) }
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<Recording>> {
+        self.recordings.get(id).map(|r| r.clone())
+    }
+
+    pub fn list(&self) -> Vec<RecordingInfo> {
+        let mut out: Vec<_> = self.recordings.iter().map(|r| r.info()).collect();
+        out.sort_by_key(|r| r.started_at);
+        out
+    }
+
+    /// Stops the recording if needed and deletes its file.
+    pub async fn remove(&self, id: &str) -> bool {
+        let Some((_, rec)) = self.recordings.remove(id) else {
+            return false;
+        };
+        rec.stop();
+        let _ = tokio::fs::remove_file(&rec.path).await;
+        true
+    }
+
+    /// Starts writing the frames from `rx` (the player's PCM output) to a new file.
+    pub async fn start(
+        &self,
+        ffmpeg_bin: &str,
+        player_id: &str,
+        format: RecordingFormat,
+        rx: broadcast::Receiver<AudioFrame>,
+    ) -> Result<RecordingInfo> {
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .with_context(|| format!("create recordings directory {}", self.dir.display()))?;
+        let id = uuid::Uuid::new_v4().to_string();
+        let path = self.dir.join(format!("{id}.{}", format.extension()));
+        let mut args: Vec<std::ffi::OsString> = format.codec_args().iter().map(Into::into).collect();
+        args.push("-y".into());
+        args.push(path.clone().into_os_string());
+        let enc = FfmpegEncoder::spawn(ffmpeg_bin, args)?;
+
+        let (stop_tx, stop_rx) = watch::channel(false);
+        let rec = Arc::new(Recording {
+            info: std::sync::Mutex::new(RecordingInfo {
+                id: id.clone(),
+                player_id: player_id.to_string(),
+                format,
+                state: RecordingState::Recording,
+                started_at: unix_ms(),
+                ended_at: None,
+                duration_ms: 0,
+            }),
+            stop_tx,
+            path,
+        });
+        self.recordings.insert(id.clone(), rec.clone());
+        info!(recording = %id, player = %player_id, ?format, "recording started");
+        tokio::spawn(write_loop(rec.clone(), enc, rx, stop_rx, self.max_duration));
+        Ok(rec.info())
+    }
+}
+
+async fn write_loop(
+    rec: Arc<Recording>,
+    enc: FfmpegEncoder,
+    mut rx: broadcast::Receiver<AudioFrame>,
+    mut stop_rx: watch::Receiver<bool>,
+    max_duration: Duration,
+) {
+    let FfmpegEncoder { mut child, mut stdin, .. } = enc;
+    let frame_ms = 20u64;
+    let mut frames = 0u64;
+    let mut write_failed = false;
+    loop {
+        tokio::select! {
+            _ = stop_rx.changed() => break,
+            msg = rx.recv() => match msg {
+                // Track end markers carry no audio.
+                Ok(frame) if frame.pcm.is_empty() => continue,
+                Ok(frame) => {
+                    if stdin.write_all(&frame.pcm).await.is_err() {
+                        write_failed = true;
+                        break;
+                    }
+                    frames += 1;
+                    if frames % 50 == 0 {
+                        rec.update(|i| i.duration_ms = frames * frame_ms);
+                    }
+                    if Duration::from_millis(frames * frame_ms) >= max_duration {
+                        info!(recording = %rec.info().id, "recording reached maximum duration");
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(n)) => warn!(lost = n, "recording lagged; dropped frames"),
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    let _ = stdin.shutdown().await;
+    drop(stdin);
+    let exited_ok = match tokio::time::timeout(FINISH_TIMEOUT, child.wait()).await {
+        Ok(Ok(status)) => status.success(),
+        _ => {
+            let _ = child.start_kill();
+            false
+        }
+    };
+    let state = if exited_ok && !write_failed { RecordingState::Finished } else { RecordingState::Failed };
+    rec.update(|i| {
+        i.state = state;
+        i.ended_at = Some(unix_ms());
+        i.duration_ms = frames * frame_ms;
+    });
+    info!(recording = %rec.info().id, ?state, "recording stopped");
+}
+
+fn unix_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
@@ -0,0 +1,49 @@
+pub const RTP_HEADER_LEN: usize = 12;
+const RTP_VERSION: u8 = 2;
+
+/// Tracks sequence number and timestamp for one outgoing RTP stream (RFC 3550).
+/// Both start at random offsets as the RFC recommends.
+pub struct RtpPacketizer {
+    payload_type: u8,
+    ssrc: u32,
+    seq: u16,
+    timestamp: u32,
+}
+
+impl RtpPacketizer {
+    pub fn new(payload_type: u8, ssrc: u32) -> Self {
+        Self { payload_type: payload_type & 0x7f, ssrc, seq: rand::random(), timestamp: rand::random() }
+    }
+
+    pub fn ssrc(&self) -> u32 {
+        self.ssrc
+    }
+
+    /// Returns the header for the next packet and advances the stream by `samples`
+    /// (per channel, in the payload's clock rate).
+    pub fn next_header(&mut self, samples: u32, marker: bool) -> [u8; RTP_HEADER_LEN] {
+        let mut h = [0u8; RTP_HEADER_LEN];
+        h[0] = RTP_VERSION << 6;
+        h[1] = self.payload_type | if marker { 0x80 } else { 0 };
+        h[2..4].copy_from_slice(&self.seq.to_be_bytes());
+        h[4..8].copy_from_slice(&self.timestamp.to_be_bytes());
+        h[8..12].copy_from_slice(&self.ssrc.to_be_bytes());
+        self.seq = self.seq.wrapping_add(1);
+        self.timestamp = self.timestamp.wrapping_add(samples);
+        h
+    }
+
+    /// Builds a complete unencrypted packet around `payload`.
+    pub fn packet(&mut self, payload: &[u8], samples: u32, marker: bool) -> Vec<u8> {
+        let mut pkt = Vec::with_capacity(RTP_HEADER_LEN + payload.len());
+        pkt.extend_from_slice(&self.next_header(samples, marker));
+        pkt.extend_from_slice(payload);
+        pkt
+    }
+
+    /// Skips `samples` of timeline without sending, e.g. across a pause, so the
+    /// receiver sees the gap in the timestamps.
+    pub fn advance(&mut self, samples: u32) {
+        self.timestamp = self.timestamp.wrapping_add(samples);
+    }
+}
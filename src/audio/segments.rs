@@ -0,0 +1,278 @@
+use anyhow::{anyhow, bail, Context, Result};
+use bytes::Bytes;
+use serde::Serialize;
+use std::time::{Duration, SystemTime};
+use tokio::time::sleep;
+use tracing::{debug, warn};
+use url::Url;
+
+use crate::audio::{
+    http::{BodySink, HttpSettings},
+    m3u8::{self, MediaPlaylist, Playlist},
+    mpd::{self, Manifest, SegmentPlan},
+};
+
+/// Consecutive unavailable segments after which a clock-driven live stream is
+/// considered over.
+const MAX_MISSING_LIVE_SEGMENTS: u32 = 5;
+
+/// Inclusive byte range, as sent in a `Range` header.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+/// One resolved media segment (or init section).
+#[derive(Clone, PartialEq, Eq)]
+pub struct SegmentRef {
+    pub url: Url,
+    pub range: Option<ByteRange>,
+}
+
+/// What a manifest says about the presentation before any media is fetched.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamInfo {
+    pub live: bool,
+    /// Total length of a VOD presentation; 0 for live.
+    pub duration_ms: u64,
+}
+
+/// A segmented source whose segments are fetched and concatenated into one body.
+pub enum SegmentedSource {
+    Hls { url: Url, playlist: MediaPlaylist },
+    Dash { url: Url, manifest: Box<Manifest> },
+}
+
+impl SegmentedSource {
+    /// Recognises a manifest by its text and resolves HLS master playlists to the
+    /// media playlist that will be played.
+    pub async fn open(
+        client: &reqwest::Client,
+        url: Url,
+        text: &str,
+        settings: &HttpSettings,
+    ) -> Result<Option<Self>> {
+        if mpd::is_manifest(text) {
+            let manifest = Box::new(mpd::parse(text, &url)?);
+            return Ok(Some(SegmentedSource::Dash { url, manifest }));
+        }
+        if !m3u8::is_playlist(text) {
+            return Ok(None);
+        }
+        let (url, playlist) = match m3u8::parse(text)? {
+            Playlist::Media(p) => (url, p),
+            Playlist::Master(master) => {
+                let uri = master.audio_uri().ok_or_else(|| anyhow!("master playlist has no variants"))?;
+                let media_url = url.join(uri)?;
+                let text = fetch_text(client, &media_url, settings.segment_retries).await?;
+                match m3u8::parse(&text)? {
+                    Playlist::Media(p) => (media_url, p),
+                    Playlist::Master(_) => bail!("variant playlist is itself a master playlist"),
+                }
+            }
+        };
+        Ok(Some(SegmentedSource::Hls { url, playlist }))
+    }
+
+    pub fn info(&self) -> StreamInfo {
+        match self {
+            SegmentedSource::Hls { playlist, .. } => StreamInfo {
+                live: !playlist.ended,
+                duration_ms: if playlist.ended { playlist.duration_ms() } else { 0 },
+            },
+            SegmentedSource::Dash { manifest, .. } => {
+                StreamInfo { live: manifest.live, duration_ms: manifest.duration_ms.unwrap_or(0) }
+            }
+        }
+    }
+
+    /// Feeds every segment into `sink` until the presentation ends, the consumer goes
+    /// away, or fetching fails for good.
+    pub async fn run(self, client: reqwest::Client, sink: BodySink, settings: HttpSettings) {
+        let res = match self {
+            SegmentedSource::Hls { url, playlist } => run_hls(&client, url, playlist, &sink, &settings).await,
+            SegmentedSource::Dash { url, manifest } => {
+                run_dash(&client, url, *manifest, &sink, &settings).await
+            }
+        };
+        match res {
+            Ok(()) => debug!("segmented source finished"),
+            Err(e) => sink.fail(std::io::Error::other(format!("{e:#}"))),
+        }
+    }
+}
+
+async fn run_hls(
+    client: &reqwest::Client,
+    url: Url,
+    mut playlist: MediaPlaylist,
+    sink: &BodySink,
+    settings: &HttpSettings,
+) -> Result<()> {
+    // Live playback starts a few segments behind the edge so there is some slack.
+    let mut next = playlist.media_sequence;
+    if !playlist.ended {
+        next += playlist.segments.len().saturating_sub(settings.live_edge_segments) as u64;
+    }
+    let mut init: Option<SegmentRef> = None;
+    loop {
+        let first = playlist.media_sequence;
+        if next < first {
+            warn!(skipped = first - next, "fell behind the live playlist");
+            next = first;
+        }
+        let mut progressed = false;
+        for (seq, seg) in (first..).zip(&playlist.segments) {
+            if seq < next {
+                continue;
+            }
+            let seg_init = match &seg.init {
+                Some(i) => Some(SegmentRef { url: url.join(&i.uri)?, range: i.range }),
+                None => None,
+            };
+            if seg_init != init {
+                if let Some(i) = &seg_init {
+                    if !sink.data(fetch(client, i, settings.segment_retries).await?).await {
+                        return Ok(());
+                    }
+                }
+                init = seg_init;
+            }
+            let media = SegmentRef { url: url.join(&seg.uri)?, range: seg.range };
+            match fetch(client, &media, settings.segment_retries).await {
+                Ok(bytes) => {
+                    if !sink.data(bytes).await {
+                        return Ok(());
+                    }
+                }
+                Err(e) if !playlist.ended => warn!(seq, error = %e, "skipping live segment"),
+                Err(e) => return Err(e),
+            }
+            next = seq + 1;
+            progressed = true;
+        }
+        if playlist.ended {
+            return Ok(());
+        }
+        // Reload after a target duration, or half of one if nothing new was listed.
+        let target = Duration::from_secs_f64(playlist.target_duration.max(1.0));
+        sleep(if progressed { target } else { target / 2 }).await;
+        if sink.is_closed() {
+            return Ok(());
+        }
+        let text = fetch_text(client, &url, settings.segment_retries).await?;
+        playlist = match m3u8::parse(&text)? {
+            Playlist::Media(p) => p,
+            Playlist::Master(_) => bail!("live playlist turned into a master playlist"),
+        };
+    }
+}
+
+async fn run_dash(
+    client: &reqwest::Client,
+    url: Url,
+    mut manifest: Manifest,
+    sink: &BodySink,
+    settings: &HttpSettings,
+) -> Result<()> {
+    let mut init: Option<SegmentRef> = None;
+    let mut last: Option<u64> = None;
+    loop {
+        if manifest.init != init {
+            if let Some(i) = &manifest.init {
+                if !sink.data(fetch(client, i, settings.segment_retries).await?).await {
+                    return Ok(());
+                }
+            }
+            init = manifest.init.clone();
+        }
+        match &manifest.plan {
+            SegmentPlan::Listed(list) => {
+                let start = match last {
+                    Some(key) => list.iter().position(|(k, _)| *k > key).unwrap_or(list.len()),
+                    None if manifest.live => list.len().saturating_sub(settings.live_edge_segments),
+                    None => 0,
+                };
+                for (key, seg) in &list[start..] {
+                    match fetch(client, seg, settings.segment_retries).await {
+                        Ok(bytes) => {
+                            if !sink.data(bytes).await {
+                                return Ok(());
+                            }
+                        }
+                        Err(e) if manifest.live => warn!(key, error = %e, "skipping live segment"),
+                        Err(e) => return Err(e),
+                    }
+                    last = Some(*key);
+                }
+            }
+            SegmentPlan::Numbered(template) => {
+                // Numbers follow the wall clock, so the manifest is never reloaded.
+                let edge = template.latest(SystemTime::now()).unwrap_or(template.start_number);
+                let mut number = edge
+                    .saturating_sub(settings.live_edge_segments.saturating_sub(1) as u64)
+                    .max(template.start_number);
+                let mut failures = 0;
+                loop {
+                    if let Ok(wait) = template.available_at(number).duration_since(SystemTime::now()) {
+                        sleep(wait).await;
+                    }
+                    let seg = template.segment(number)?;
+                    match fetch(client, &seg, settings.segment_retries).await {
+                        Ok(bytes) => {
+                            failures = 0;
+                            if !sink.data(bytes).await {
+                                return Ok(());
+                            }
+                        }
+                        Err(e) if failures < MAX_MISSING_LIVE_SEGMENTS => {
+                            failures += 1;
+                            warn!(number, error = %e, "skipping live segment");
+                        }
+                        Err(e) => return Err(e.context("live stream stopped publishing segments")),
+                    }
+                    number += 1;
+                }
+            }
+        }
+        if !manifest.live {
+            return Ok(());
+        }
+        sleep(manifest.update_period.unwrap_or(Duration::from_secs(2)).max(Duration::from_millis(500))).await;
+        if sink.is_closed() {
+            return Ok(());
+        }
+        let text = fetch_text(client, &url, settings.segment_retries).await?;
+        // A live manifest that turned static has ended; what remains after `last`
+        // is played out on the next pass.
+        manifest = mpd::parse(&text, &url)?;
+    }
+}
+
+/// Fetches one segment, retrying with a growing delay.
+async fn fetch(client: &reqwest::Client, seg: &SegmentRef, retries: u32) -> Result<Bytes> {
+    let mut attempt = 0;
+    loop {
+        let mut req = client.get(seg.url.clone());
+        if let Some(r) = seg.range {
+            req = req.header(reqwest::header::RANGE, format!("bytes={}-{}", r.start, r.end));
+        }
+        let res = async { req.send().await?.error_for_status()?.bytes().await }.await;
+        match res {
+            Ok(bytes) => return Ok(bytes),
+            Err(e) if attempt < retries => {
+                attempt += 1;
+                debug!(url = %seg.url, attempt, error = %e, "segment fetch failed, retrying");
+                sleep(Duration::from_millis(250 * attempt as u64)).await;
+            }
+            Err(e) => return Err(e).with_context(|| format!("fetch segment {}", seg.url)),
+        }
+    }
+}
+
+async fn fetch_text(client: &reqwest::Client, url: &Url, retries: u32) -> Result<String> {
+    let bytes = fetch(client, &SegmentRef { url: url.clone(), range: None }, retries).await?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
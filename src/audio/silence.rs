@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::EffectiveConfig;
+
+/// Per-player silence handling, applied to the PCM output in `Player::run`.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SilenceOptions {
+    /// Frames whose peak stays below this level (before volume) count as silent.
+    pub threshold_db: f32,
+    /// Silence shorter than this is left alone and not reported.
+    pub min_duration_ms: u64,
+    pub trim_leading: bool,
+    pub trim_trailing: bool,
+    /// Emit `SilenceDetected` once a silent stretch reaches `min_duration_ms`.
+    pub detect: bool,
+    /// Skip to the next track once a silent stretch lasts this long.
+    pub auto_skip_secs: Option<u64>,
+}
+
+impl SilenceOptions {
+    pub fn from_config(cfg: &EffectiveConfig) -> Self {
+        Self {
+            threshold_db: cfg.silence_threshold_db,
+            min_duration_ms: cfg.silence_min_duration_ms,
+            trim_leading: cfg.silence_trim_leading,
+            trim_trailing: cfg.silence_trim_trailing,
+            detect: cfg.silence_detect,
+            auto_skip_secs: cfg.silence_auto_skip_secs.filter(|s| *s > 0),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.trim_leading || self.trim_trailing || self.detect || self.auto_skip_secs.is_some()
+    }
+
+    pub fn apply(&mut self, u: &SilenceUpdate) -> Result<(), String> {
+        if let Some(t) = u.threshold_db {
+            if !(-120.0..=0.0).contains(&t) {
+                return Err(format!("thresholdDb must be between -120 and 0, got {t}"));
+            }
+            self.threshold_db = t;
+        }
+        if let Some(m) = u.min_duration_ms {
+            self.min_duration_ms = m;
+        }
+        if let Some(v) = u.trim_leading {
+            self.trim_leading = v;
+        }
+        if let Some(v) = u.trim_trailing {
+            self.trim_trailing = v;
+        }
+        if let Some(v) = u.detect {
+            self.detect = v;
+        }
+        if let Some(s) = u.auto_skip_secs {
+            self.auto_skip_secs = (s > 0).then_some(s);
+        }
+        Ok(())
+    }
+
+    /// The silence threshold as a linear sample magnitude at the given output volume.
+    pub fn threshold_at_volume(&self, volume: f32) -> i32 {
+        (10f32.powf(self.threshold_db / 20.0) * volume * 32768.0) as i32
+    }
+}
+
+/// Partial update for `PATCH /v0/players/{id}/silence`; `autoSkipSecs: 0` disables auto-skip.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SilenceUpdate {
+    pub threshold_db: Option<f32>,
+    pub min_duration_ms: Option<u64>,
+    pub trim_leading: Option<bool>,
+    pub trim_trailing: Option<bool>,
+    pub detect: Option<bool>,
+    pub auto_skip_secs: Option<u64>,
+}
+
+pub fn is_silent(frame: &[i16], threshold: i32) -> bool {
+    frame.iter().all(|&s| (s as i32).abs() <= threshold)
+}
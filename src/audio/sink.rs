@@ -0,0 +1,172 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tokio::{net::UdpSocket, sync::broadcast};
+use tracing::{debug, info, warn};
+
+use crate::audio::{
+    format::{SOURCE_CHANNELS, SOURCE_SAMPLE_RATE},
+    frame::Payload,
+    rtp::RtpPacketizer,
+};
+use crate::utils::task::AbortOnDrop;
+
+/// Samples per channel in one 20 ms player frame.
+const FRAME_SAMPLES: u32 = 960;
+/// L16 frames are split into 5 ms packets (960 bytes) so they fit a 1500 byte MTU.
+const L16_PACKET_SAMPLES: usize = 240;
+/// A gap longer than this between frames (e.g. a pause) starts a new talkspurt.
+const GAP_THRESHOLD: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RtpCodec {
+    /// 48 kHz stereo Opus, one packet per 20 ms frame.
+    Opus,
+    /// Uncompressed 16-bit big-endian PCM (`L16/48000/2`).
+    L16,
+}
+
+impl RtpCodec {
+    fn default_payload_type(self) -> u8 {
+        match self {
+            RtpCodec::Opus => 111,
+            RtpCodec::L16 => 96,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RtpSinkReq {
+    pub host: String,
+    pub port: u16,
+    pub codec: RtpCodec,
+    #[serde(default)]
+    pub payload_type: Option<u8>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RtpSinkInfo {
+    pub id: String,
+    pub host: String,
+    pub port: u16,
+    pub codec: RtpCodec,
+    pub payload_type: u8,
+    pub ssrc: u32,
+    pub packets_sent: u64,
+}
+
+/// A running RTP sender for one player. Dropping it stops the stream.
+pub struct RtpSink {
+    info: RtpSinkInfo,
+    packets_sent: Arc<AtomicU64>,
+    _task: AbortOnDrop,
+}
+
+impl RtpSink {
+    /// Resolves the destination and starts sending. `rx` carries the player's PCM
+    /// frames for `L16`, or its shared Opus packets for `Opus`.
+    pub async fn start<T: Payload>(req: RtpSinkReq, rx: broadcast::Receiver<T>) -> Result<Self> {
+        let payload_type = req.payload_type.unwrap_or_else(|| req.codec.default_payload_type());
+        if payload_type > 127 {
+            return Err(anyhow!("payload type must be between 0 and 127"));
+        }
+        let addr = tokio::net::lookup_host((req.host.as_str(), req.port))
+            .await
+            .with_context(|| format!("resolve rtp sink host '{}'", req.host))?
+            .next()
+            .ok_or_else(|| anyhow!("rtp sink host '{}' has no addresses", req.host))?;
+        let bind = if addr.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+        let socket = UdpSocket::bind(bind).await.context("bind rtp socket")?;
+        socket.connect(addr).await.context("connect rtp socket")?;
+
+        let rtp = RtpPacketizer::new(payload_type, rand::random());
+        let info = RtpSinkInfo {
+            id: uuid::Uuid::new_v4().to_string(),
+            host: req.host,
+            port: req.port,
+            codec: req.codec,
+            payload_type,
+            ssrc: rtp.ssrc(),
+            packets_sent: 0,
+        };
+        let packets_sent = Arc::new(AtomicU64::new(0));
+        let task = tokio::spawn(send_loop(socket, rtp, req.codec, rx, packets_sent.clone()));
+        info!(sink = %info.id, %addr, codec = ?info.codec, "RTP sink started");
+        Ok(Self { info, packets_sent, _task: AbortOnDrop(task) })
+    }
+
+    pub fn id(&self) -> &str {
+        &self.info.id
+    }
+
+    pub fn info(&self) -> RtpSinkInfo {
+        RtpSinkInfo { packets_sent: self.packets_sent.load(Ordering::Relaxed), ..self.info.clone() }
+    }
+}
+
+async fn send_loop<T: Payload>(
+    socket: UdpSocket,
+    mut rtp: RtpPacketizer,
+    codec: RtpCodec,
+    mut rx: broadcast::Receiver<T>,
+    packets_sent: Arc<AtomicU64>,
+) {
+    let mut last: Option<Instant> = None;
+    loop {
+        let frame = match rx.recv().await {
+            Ok(f) => f,
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                debug!(lost = n, "RTP sink lagged");
+                rtp.advance(n as u32 * FRAME_SAMPLES);
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        // Track end markers carry no audio.
+        if frame.payload().is_empty() {
+            continue;
+        }
+
+        let now = Instant::now();
+        let marker = match last {
+            Some(prev) if now.duration_since(prev) > GAP_THRESHOLD => {
+                let gap = now.duration_since(prev).as_secs_f64() * SOURCE_SAMPLE_RATE as f64;
+                rtp.advance((gap as u32).saturating_sub(FRAME_SAMPLES));
+                true
+            }
+            None => true,
+            _ => false,
+        };
+        last = Some(now);
+
+        let packets: Vec<Vec<u8>> = match codec {
+            RtpCodec::Opus => vec![rtp.packet(frame.payload(), FRAME_SAMPLES, marker)],
+            RtpCodec::L16 => frame
+                .payload()
+                .chunks(L16_PACKET_SAMPLES * SOURCE_CHANNELS * 2)
+                .enumerate()
+                .map(|(i, chunk)| {
+                    let be: Vec<u8> = chunk.chunks_exact(2).flat_map(|s| [s[1], s[0]]).collect();
+                    let samples = (chunk.len() / (SOURCE_CHANNELS * 2)) as u32;
+                    rtp.packet(&be, samples, marker && i == 0)
+                })
+                .collect(),
+        };
+        for pkt in packets {
+            if let Err(e) = socket.send(&pkt).await {
+                warn!(?e, "RTP send failed");
+            } else {
+                packets_sent.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
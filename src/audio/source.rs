@@ -1,12 +1,46 @@
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, Result};
 use std::{
     fs,
-    io::Write,
     path::{Path, PathBuf},
 };
 use url::Url;
 
-pub async fn prepare_local_source(uri: &str) -> Result<PathBuf> {
+/// Where a track's audio is read from.
+#[derive(Debug, Clone)]
+pub enum Source {
+    File(PathBuf),
+    /// Streamed into the decoder as it downloads.
+    Http(String),
+}
+
+impl Source {
+    /// File name without extension, used as a fallback title.
+    pub fn stem(&self) -> Option<String> {
+        match self {
+            Source::File(p) => p.file_stem().and_then(|s| s.to_str()).map(str::to_string),
+            Source::Http(u) => {
+                let url = Url::parse(u).ok()?;
+                let last = url.path_segments()?.rfind(|s| !s.is_empty())?;
+                let stem = Path::new(last).file_stem()?.to_str()?.to_string();
+                Some(stem)
+            }
+        }
+    }
+
+    /// Extension hint for probing the container.
+    pub fn extension(&self) -> Option<String> {
+        match self {
+            Source::File(p) => p.extension().and_then(|e| e.to_str()).map(str::to_string),
+            Source::Http(u) => {
+                let url = Url::parse(u).ok()?;
+                let last = url.path_segments()?.rfind(|s| !s.is_empty())?;
+                Path::new(last).extension()?.to_str().map(str::to_string)
+            }
+        }
+    }
+}
+
+pub async fn prepare_source(uri: &str) -> Result<Source> {
     if let Ok(u) = Url::parse(uri) {
         match u.scheme() {
             "file" => {
@@ -14,22 +48,9 @@ pub async fn prepare_local_source(uri: &str) -> Result<PathBuf> {
                 if !p.exists() {
                     anyhow::bail!("file not found: {}", p.display());
                 }
-                return Ok(p);
-            }
-            "http" | "https" => {
-                let resp = reqwest::Client::new()
-                    .get(uri)
-                    .send()
-                    .await
-                    .context("http get")?
-                    .error_for_status()
-                    .context("bad status")?;
-                let body = resp.bytes().await.context("read body")?;
-                let mut tmp = tempfile::Builder::new().prefix("resonix_").tempfile()?;
-                tmp.as_file_mut().write_all(&body)?;
-                let path = tmp.into_temp_path().keep()?;
-                return Ok(path);
+                return Ok(Source::File(p));
             }
+            "http" | "https" => return Ok(Source::Http(uri.to_string())),
             _ => {}
         }
     }
@@ -38,7 +59,7 @@ pub async fn prepare_local_source(uri: &str) -> Result<PathBuf> {
     if !p.exists() {
         anyhow::bail!("source not found: {}", p.display());
     }
-    Ok(p)
+    Ok(Source::File(p))
 }
 
 pub fn is_resonix_temp_file(path: &Path) -> bool {
@@ -62,7 +83,11 @@ pub fn cleanup_resonix_temp_files() {
             let p = entry.path();
             if let Some(name) = p.file_name().and_then(|s| s.to_str()) {
                 if name.starts_with("resonix_") {
-                    let _ = fs::remove_file(&p);
+                    if p.is_dir() {
+                        let _ = fs::remove_dir_all(&p);
+                    } else {
+                        let _ = fs::remove_file(&p);
+                    }
                 }
             }
         }
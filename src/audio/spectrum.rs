@@ -0,0 +1,157 @@
+use rustfft::{num_complex::Complex32, Fft, FftPlanner};
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+use crate::audio::format::{SOURCE_CHANNELS, SOURCE_SAMPLE_RATE};
+use crate::config::EffectiveConfig;
+
+/// Spectrum frames are sent at roughly 30 Hz.
+pub const SEND_INTERVAL: Duration = Duration::from_millis(33);
+const MAX_BANDS: usize = 256;
+/// Reported band levels are clamped to this floor.
+const MIN_DB: f32 = -100.0;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SpectrumQuery {
+    #[serde(default)]
+    pub bands: Option<usize>,
+    #[serde(default)]
+    pub smoothing: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SpectrumSettings {
+    pub fft_size: usize,
+    pub bands: usize,
+    pub smoothing: f32,
+    pub min_freq: f32,
+    pub max_freq: f32,
+}
+
+impl SpectrumSettings {
+    pub fn from_config(cfg: &EffectiveConfig) -> Self {
+        let nyquist = SOURCE_SAMPLE_RATE as f32 / 2.0;
+        let max_freq = cfg.spectrum_max_freq.clamp(100.0, nyquist);
+        Self {
+            fft_size: cfg.spectrum_fft_size.clamp(256, 16384).next_power_of_two(),
+            bands: cfg.spectrum_bands.clamp(1, MAX_BANDS),
+            smoothing: cfg.spectrum_smoothing.clamp(0.0, 0.99),
+            min_freq: cfg.spectrum_min_freq.clamp(1.0, max_freq / 2.0),
+            max_freq,
+        }
+    }
+
+    pub fn with_query(mut self, q: &SpectrumQuery) -> Result<Self, String> {
+        if let Some(b) = q.bands {
+            if !(1..=MAX_BANDS).contains(&b) {
+                return Err(format!("bands must be between 1 and {MAX_BANDS}, got {b}"));
+            }
+            self.bands = b;
+        }
+        if let Some(s) = q.smoothing {
+            if !(0.0..1.0).contains(&s) {
+                return Err(format!("smoothing must be at least 0 and below 1, got {s}"));
+            }
+            self.smoothing = s;
+        }
+        Ok(self)
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "op", rename_all = "camelCase")]
+pub enum SpectrumMessage<'a> {
+    /// Sent once after connecting: the centre frequency of every band in Hz.
+    Layout { frequencies: Vec<f32> },
+    /// Band levels in dBFS, lowest band first.
+    Spectrum { bands: &'a [f32] },
+}
+
+/// Windowed FFT over the most recent `fft_size` samples of the mono downmix,
+/// reduced to log-spaced bands with exponential smoothing between frames.
+pub struct SpectrumAnalyzer {
+    settings: SpectrumSettings,
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    window_gain: f32,
+    samples: VecDeque<f32>,
+    scratch: Vec<Complex32>,
+    /// Inclusive-exclusive FFT bin ranges per band.
+    band_bins: Vec<(usize, usize)>,
+    smoothed: Vec<f32>,
+    levels_db: Vec<f32>,
+    fresh: bool,
+}
+
+impl SpectrumAnalyzer {
+    pub fn new(settings: SpectrumSettings) -> Self {
+        let n = settings.fft_size;
+        let fft = FftPlanner::new().plan_fft_forward(n);
+        let window: Vec<f32> =
+            (0..n).map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / n as f32).cos()).collect();
+        let window_gain = window.iter().sum::<f32>();
+        let bin_hz = SOURCE_SAMPLE_RATE as f32 / n as f32;
+        let ratio = settings.max_freq / settings.min_freq;
+        let band_bins = (0..settings.bands)
+            .map(|b| {
+                let lo = settings.min_freq * ratio.powf(b as f32 / settings.bands as f32);
+                let hi = settings.min_freq * ratio.powf((b + 1) as f32 / settings.bands as f32);
+                let start = ((lo / bin_hz).round() as usize).clamp(1, n / 2 - 1);
+                let end = ((hi / bin_hz).round() as usize).clamp(start + 1, n / 2);
+                (start, end)
+            })
+            .collect();
+        Self {
+            settings,
+            fft,
+            window,
+            window_gain,
+            samples: VecDeque::from(vec![0.0; n]),
+            scratch: vec![Complex32::default(); n],
+            band_bins,
+            smoothed: vec![0.0; settings.bands],
+            levels_db: vec![MIN_DB; settings.bands],
+            fresh: false,
+        }
+    }
+
+    pub fn frequencies(&self) -> Vec<f32> {
+        let ratio = self.settings.max_freq / self.settings.min_freq;
+        (0..self.settings.bands)
+            .map(|b| self.settings.min_freq * ratio.powf((b as f32 + 0.5) / self.settings.bands as f32))
+            .collect()
+    }
+
+    /// Appends interleaved s16le stereo PCM from the player output.
+    pub fn push_pcm(&mut self, pcm: &[u8]) {
+        for s in pcm.chunks_exact(2 * SOURCE_CHANNELS) {
+            let l = i16::from_le_bytes([s[0], s[1]]) as f32;
+            let r = i16::from_le_bytes([s[2], s[3]]) as f32;
+            self.samples.push_back((l + r) / (2.0 * 32768.0));
+        }
+        let excess = self.samples.len().saturating_sub(self.settings.fft_size);
+        self.samples.drain(..excess);
+        self.fresh = true;
+    }
+
+    /// Runs the FFT if new audio arrived since the last call and returns the band
+    /// levels; returns `None` while the player is paused or idle.
+    pub fn analyze(&mut self) -> Option<&[f32]> {
+        if !std::mem::take(&mut self.fresh) {
+            return None;
+        }
+        for (i, (s, w)) in self.samples.iter().zip(&self.window).enumerate() {
+            self.scratch[i] = Complex32::new(s * w, 0.0);
+        }
+        self.fft.process(&mut self.scratch);
+        let scale = 2.0 / self.window_gain;
+        let alpha = self.settings.smoothing;
+        for (b, &(start, end)) in self.band_bins.iter().enumerate() {
+            let peak = self.scratch[start..end].iter().map(|c| c.norm()).fold(0.0f32, f32::max) * scale;
+            self.smoothed[b] = alpha * self.smoothed[b] + (1.0 - alpha) * peak;
+            self.levels_db[b] =
+                if self.smoothed[b] > 0.0 { (20.0 * self.smoothed[b].log10()).max(MIN_DB) } else { MIN_DB };
+        }
+        Some(&self.levels_db)
+    }
+}
@@ -0,0 +1,126 @@
+use anyhow::Result;
+use bytes::Bytes;
+use futures::Stream;
+use tokio::{io::AsyncReadExt, process::ChildStdout, sync::broadcast};
+
+use crate::audio::{
+    encoder::{pump_pcm, FfmpegEncoder},
+    format::{SOURCE_CHANNELS, SOURCE_SAMPLE_RATE},
+    frame::AudioFrame,
+};
+
+const READ_CHUNK: usize = 8 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpStreamFormat {
+    Ogg,
+    Mp3,
+    Wav,
+}
+
+impl HttpStreamFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            HttpStreamFormat::Ogg => "audio/ogg",
+            HttpStreamFormat::Mp3 => "audio/mpeg",
+            HttpStreamFormat::Wav => "audio/wav",
+        }
+    }
+
+    fn default_bitrate(self) -> u32 {
+        match self {
+            HttpStreamFormat::Ogg => 128_000,
+            HttpStreamFormat::Mp3 => 192_000,
+            HttpStreamFormat::Wav => 0,
+        }
+    }
+}
+
+/// Opens a continuous encoded stream of a player's output. Ogg/Opus and MP3 are
+/// encoded by a dedicated ffmpeg child per listener; WAV is the raw PCM behind a
+/// header with unbounded chunk sizes, so no encoder is needed.
+pub fn open(
+    ffmpeg_bin: &str,
+    format: HttpStreamFormat,
+    bitrate: Option<u32>,
+    rx: broadcast::Receiver<AudioFrame>,
+) -> Result<impl Stream<Item = std::io::Result<Bytes>> + Send + 'static> {
+    let bitrate = bitrate.unwrap_or_else(|| format.default_bitrate()).to_string();
+    let args: Vec<&str> = match format {
+        HttpStreamFormat::Ogg => {
+            vec!["-c:a", "libopus", "-b:a", &bitrate, "-page_duration", "20000", "-f", "ogg", "pipe:1"]
+        }
+        HttpStreamFormat::Mp3 => vec!["-c:a", "libmp3lame", "-b:a", &bitrate, "-f", "mp3", "pipe:1"],
+        HttpStreamFormat::Wav => {
+            return Ok(StreamSource::Wav { header: Some(wav_header()), rx }.into_stream())
+        }
+    };
+    let FfmpegEncoder { child, stdin, stdout } = FfmpegEncoder::spawn(ffmpeg_bin, args)?;
+    tokio::spawn(pump_pcm(rx, stdin));
+    Ok(StreamSource::Encoded { _child: child, stdout }.into_stream())
+}
+
+enum StreamSource {
+    Encoded { _child: tokio::process::Child, stdout: ChildStdout },
+    Wav { header: Option<Bytes>, rx: broadcast::Receiver<AudioFrame> },
+}
+
+impl StreamSource {
+    fn into_stream(self) -> impl Stream<Item = std::io::Result<Bytes>> + Send + 'static {
+        futures::stream::unfold(self, |mut src| async move {
+            let item = src.next_chunk().await?;
+            Some((item, src))
+        })
+    }
+
+    async fn next_chunk(&mut self) -> Option<std::io::Result<Bytes>> {
+        match self {
+            StreamSource::Encoded { stdout, .. } => {
+                let mut buf = vec![0u8; READ_CHUNK];
+                match stdout.read(&mut buf).await {
+                    Ok(0) => None,
+                    Ok(n) => {
+                        buf.truncate(n);
+                        Some(Ok(Bytes::from(buf)))
+                    }
+                    Err(e) => Some(Err(e)),
+                }
+            }
+            StreamSource::Wav { header, rx } => {
+                if let Some(h) = header.take() {
+                    return Some(Ok(h));
+                }
+                loop {
+                    match rx.recv().await {
+                        Ok(frame) if frame.pcm.is_empty() => continue,
+                        Ok(frame) => return Some(Ok(frame.pcm)),
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => return None,
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// RIFF/WAVE header for an endless 16-bit PCM stream; the size fields are set to
+/// their maximum since the length is unknown.
+fn wav_header() -> Bytes {
+    let channels = SOURCE_CHANNELS as u16;
+    let block_align = channels * 2;
+    let byte_rate = SOURCE_SAMPLE_RATE * block_align as u32;
+    let mut h = Vec::with_capacity(44);
+    h.extend_from_slice(b"RIFF");
+    h.extend_from_slice(&u32::MAX.to_le_bytes());
+    h.extend_from_slice(b"WAVEfmt ");
+    h.extend_from_slice(&16u32.to_le_bytes());
+    h.extend_from_slice(&1u16.to_le_bytes());
+    h.extend_from_slice(&channels.to_le_bytes());
+    h.extend_from_slice(&SOURCE_SAMPLE_RATE.to_le_bytes());
+    h.extend_from_slice(&byte_rate.to_le_bytes());
+    h.extend_from_slice(&block_align.to_le_bytes());
+    h.extend_from_slice(&16u16.to_le_bytes());
+    h.extend_from_slice(b"data");
+    h.extend_from_slice(&u32::MAX.to_le_bytes());
+    Bytes::from(h)
+}
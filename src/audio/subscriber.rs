@@ -0,0 +1,243 @@
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use tokio::sync::{broadcast, Notify};
+use tracing::{debug, warn};
+
+use crate::config::EffectiveConfig;
+use crate::utils::task::AbortOnDrop;
+
+/// Every item on the player channels covers 20 ms of audio.
+const ITEM_MS: u64 = 20;
+
+/// What to do when a subscriber's queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueuePolicy {
+    /// Discard the oldest queued frame to make room, keeping latency bounded.
+    DropOldest,
+    /// Discard incoming frames until the subscriber catches up.
+    DropNewest,
+    /// Disconnect the subscriber once it falls `disconnect_after_ms` behind.
+    Disconnect,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct QueueSettings {
+    pub capacity: usize,
+    pub policy: QueuePolicy,
+    pub disconnect_after_ms: u64,
+}
+
+impl QueueSettings {
+    pub fn from_config(cfg: &EffectiveConfig) -> Self {
+        Self {
+            capacity: cfg.subscriber_queue_frames.max(1),
+            policy: cfg.subscriber_policy,
+            disconnect_after_ms: cfg.subscriber_disconnect_after_ms,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscriberStats {
+    pub id: String,
+    pub kind: &'static str,
+    pub policy: QueuePolicy,
+    /// Audio currently queued for this subscriber, i.e. how far behind live it is.
+    pub lag_ms: u64,
+    pub max_lag_ms: u64,
+    pub sent_frames: u64,
+    pub dropped_frames: u64,
+}
+
+/// Counters of one subscriber, shared with the player so they can be listed.
+pub struct SubscriberInfo {
+    id: String,
+    kind: &'static str,
+    policy: QueuePolicy,
+    queued: AtomicUsize,
+    max_queued: AtomicUsize,
+    sent: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl SubscriberInfo {
+    pub fn stats(&self) -> SubscriberStats {
+        SubscriberStats {
+            id: self.id.clone(),
+            kind: self.kind,
+            policy: self.policy,
+            lag_ms: self.queued.load(Ordering::Relaxed) as u64 * ITEM_MS,
+            max_lag_ms: self.max_queued.load(Ordering::Relaxed) as u64 * ITEM_MS,
+            sent_frames: self.sent.load(Ordering::Relaxed),
+            dropped_frames: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+pub type SubscriberRegistry = Arc<DashMap<String, Arc<SubscriberInfo>>>;
+
+struct QueueState<T> {
+    /// Items with a flag telling whether anything was dropped right before them.
+    items: VecDeque<(T, bool)>,
+    gap_pending: bool,
+    closed: bool,
+    too_slow: bool,
+}
+
+struct Queue<T> {
+    state: std::sync::Mutex<QueueState<T>>,
+    notify: Notify,
+}
+
+/// A bounded, per-subscriber queue fed from one of the player's broadcast channels.
+/// It unregisters itself when dropped.
+pub struct Subscription<T> {
+    queue: Arc<Queue<T>>,
+    info: Arc<SubscriberInfo>,
+    registry: SubscriberRegistry,
+    _task: AbortOnDrop,
+}
+
+impl<T: Clone + Send + 'static> Subscription<T> {
+    /// Starts draining `rx` into a new queue. `on_too_slow` runs if the subscriber
+    /// is disconnected by the `disconnect` policy.
+    pub fn start(
+        rx: broadcast::Receiver<T>,
+        kind: &'static str,
+        settings: QueueSettings,
+        registry: SubscriberRegistry,
+        on_too_slow: impl FnOnce(SubscriberStats) + Send + 'static,
+    ) -> Self {
+        let info = Arc::new(SubscriberInfo {
+            id: uuid::Uuid::new_v4().to_string(),
+            kind,
+            policy: settings.policy,
+            queued: AtomicUsize::new(0),
+            max_queued: AtomicUsize::new(0),
+            sent: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        });
+        let queue = Arc::new(Queue {
+            state: std::sync::Mutex::new(QueueState {
+                items: VecDeque::new(),
+                gap_pending: false,
+                closed: false,
+                too_slow: false,
+            }),
+            notify: Notify::new(),
+        });
+        registry.insert(info.id.clone(), info.clone());
+        let task =
+            tokio::spawn(forward(rx, queue.clone(), info.clone(), registry.clone(), settings, on_too_slow));
+        Self { queue, info, registry, _task: AbortOnDrop(task) }
+    }
+
+    /// Waits for the next item. The flag is set when items were dropped just before
+    /// it. Returns `None` once the player is gone or the subscriber was disconnected.
+    pub async fn recv(&mut self) -> Option<(T, bool)> {
+        loop {
+            {
+                let mut st = self.queue.state.lock().unwrap_or_else(|e| e.into_inner());
+                if let Some(item) = st.items.pop_front() {
+                    self.info.queued.store(st.items.len(), Ordering::Relaxed);
+                    self.info.sent.fetch_add(1, Ordering::Relaxed);
+                    return Some(item);
+                }
+                if st.closed {
+                    return None;
+                }
+            }
+            self.queue.notify.notified().await;
+        }
+    }
+
+    /// Whether the subscription ended because the subscriber fell too far behind.
+    pub fn dropped_for_lag(&self) -> bool {
+        self.queue.state.lock().map(|s| s.too_slow).unwrap_or(false)
+    }
+
+    /// Resolves once the subscriber has been dropped for lagging, so a send that is
+    /// stuck on a stalled client can be abandoned.
+    pub async fn lagged_out(&self) {
+        while !self.dropped_for_lag() {
+            self.queue.notify.notified().await;
+        }
+    }
+}
+
+impl<T> Drop for Subscription<T> {
+    fn drop(&mut self) {
+        self.registry.remove(&self.info.id);
+    }
+}
+
+async fn forward<T: Clone + Send + 'static>(
+    mut rx: broadcast::Receiver<T>,
+    queue: Arc<Queue<T>>,
+    info: Arc<SubscriberInfo>,
+    registry: SubscriberRegistry,
+    settings: QueueSettings,
+    on_too_slow: impl FnOnce(SubscriberStats) + Send + 'static,
+) {
+    let max_items = settings.disconnect_after_ms.div_ceil(ITEM_MS).max(1) as usize;
+    loop {
+        let item = match rx.recv().await {
+            Ok(item) => item,
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                info.dropped.fetch_add(n, Ordering::Relaxed);
+                queue.state.lock().unwrap_or_else(|e| e.into_inner()).gap_pending = true;
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        let mut st = queue.state.lock().unwrap_or_else(|e| e.into_inner());
+        match settings.policy {
+            QueuePolicy::DropOldest if st.items.len() >= settings.capacity => {
+                st.items.pop_front();
+                if let Some(front) = st.items.front_mut() {
+                    front.1 = true;
+                } else {
+                    st.gap_pending = true;
+                }
+                info.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            QueuePolicy::DropNewest if st.items.len() >= settings.capacity => {
+                st.gap_pending = true;
+                info.dropped.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+            QueuePolicy::Disconnect if st.items.len() >= max_items => {
+                st.items.clear();
+                st.closed = true;
+                st.too_slow = true;
+                drop(st);
+                queue.notify.notify_one();
+                registry.remove(&info.id);
+                let stats = info.stats();
+                warn!(subscriber = %stats.id, kind = stats.kind, lag_ms = stats.lag_ms, "dropping slow subscriber");
+                on_too_slow(stats);
+                return;
+            }
+            _ => {}
+        }
+        let gap = std::mem::take(&mut st.gap_pending);
+        st.items.push_back((item, gap));
+        let len = st.items.len();
+        drop(st);
+        info.queued.store(len, Ordering::Relaxed);
+        info.max_queued.fetch_max(len, Ordering::Relaxed);
+        queue.notify.notify_one();
+    }
+    queue.state.lock().unwrap_or_else(|e| e.into_inner()).closed = true;
+    queue.notify.notify_one();
+    debug!(subscriber = %info.id, "subscriber source closed");
+}
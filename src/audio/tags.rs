@@ -0,0 +1,262 @@
+use anyhow::{Context, Result};
+use bytes::{Bytes, BytesMut};
+use lofty::config::ParseOptions;
+use lofty::file::{FileType, TaggedFile};
+use lofty::id3::v2::{Frame, FrameId, Id3v2Tag, SynchronizedTextFrame, TimestampFormat};
+use lofty::picture::PictureType;
+use lofty::prelude::*;
+use lofty::{iff::aiff::AiffFile, iff::wav::WavFile, mpeg::MpegFile};
+use std::borrow::Cow;
+use std::io::{BufReader, Cursor, Read, Seek};
+use std::path::Path;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::audio::lyrics::{Lyrics, LyricsSource};
+
+/// First attempt at reading tags from the start of an HTTP body.
+const FIRST_PROBE_BYTES: usize = 256 * 1024;
+/// Bodies whose tags are not readable within this many bytes are given up on.
+const MAX_PROBE_BYTES: usize = 16 * 1024 * 1024;
+/// Audio kept past the end of an ID3v2 tag so the stream properties can be read.
+const AUDIO_AFTER_TAG_BYTES: usize = 64 * 1024;
+
+/// Metadata embedded in an audio file (ID3v2, Vorbis comments, MP4 atoms, ...).
+#[derive(Debug, Clone, Default)]
+pub struct TrackTags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub isrc: Option<String>,
+    /// 0 when unknown.
+    pub duration_ms: u64,
+    pub artwork: Option<Artwork>,
+    pub lyrics: Option<Lyrics>,
+}
+
+/// An embedded cover image.
+#[derive(Debug, Clone)]
+pub struct Artwork {
+    pub mime: String,
+    pub data: Bytes,
+}
+
+/// Tags read from an HTTP body, delivered once enough of it has arrived. Closed
+/// without a value when none could be read.
+pub type TagsReceiver = oneshot::Receiver<TrackTags>;
+
+/// Reads the primary tag (falling back to whichever tag the file has) and the
+/// stream duration. Blocking; call from a blocking context.
+pub fn read_tags(path: &Path) -> Result<TrackTags> {
+    let file = lofty::read_from_path(path).with_context(|| format!("read tags of {}", path.display()))?;
+    let mut tags = from_tagged_file(&file);
+    if tags.lyrics.as_ref().map_or(true, |l| !l.synced) && has_id3v2(file.file_type()) {
+        let mut reader = BufReader::new(std::fs::File::open(path)?);
+        if let Some(synced) = read_synced_lyrics(&mut reader, file.file_type()) {
+            tags.lyrics = Some(synced);
+        }
+    }
+    Ok(tags)
+}
+
+/// Reads tags from the first bytes of a file. The duration is only trusted when
+/// `complete` is set, since it may be estimated from the length of the data.
+fn read_tags_from_head(head: &[u8], complete: bool) -> Result<TrackTags> {
+    let file = lofty::probe::Probe::new(Cursor::new(head)).guess_file_type()?.read()?;
+    let mut tags = from_tagged_file(&file);
+    if tags.lyrics.as_ref().map_or(true, |l| !l.synced) && has_id3v2(file.file_type()) {
+        if let Some(synced) = read_synced_lyrics(&mut Cursor::new(head), file.file_type()) {
+            tags.lyrics = Some(synced);
+        }
+    }
+    if !complete {
+        tags.duration_ms = 0;
+    }
+    Ok(tags)
+}
+
+fn from_tagged_file(file: &TaggedFile) -> TrackTags {
+    let duration_ms = file.properties().duration().as_millis() as u64;
+    let Some(tag) = file.primary_tag().or_else(|| file.first_tag()) else {
+        return TrackTags { duration_ms, ..Default::default() };
+    };
+    let text = |v: Option<&str>| v.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    let pictures = tag.pictures();
+    let cover = pictures.iter().find(|p| p.pic_type() == PictureType::CoverFront).or(pictures.first());
+    let artwork = cover.filter(|p| !p.data().is_empty()).map(|p| Artwork {
+        mime: p
+            .mime_type()
+            .map(|m| m.as_str().to_string())
+            .unwrap_or_else(|| sniff_image_mime(p.data()).to_string()),
+        data: Bytes::copy_from_slice(p.data()),
+    });
+    TrackTags {
+        title: text(tag.title().as_deref()),
+        artist: text(tag.artist().as_deref()),
+        album: text(tag.album().as_deref()),
+        isrc: text(tag.get_string(&ItemKey::Isrc)),
+        duration_ms,
+        artwork,
+        // USLT, `LYRICS` and `©lyr` values are often LRC themselves.
+        lyrics: tag.get_string(&ItemKey::Lyrics).and_then(|l| Lyrics::from_lrc(l, LyricsSource::Embedded)),
+    }
+}
+
+/// Formats whose ID3v2 tag may hold a SYLT frame.
+fn has_id3v2(file_type: FileType) -> bool {
+    matches!(file_type, FileType::Mpeg | FileType::Wav | FileType::Aiff)
+}
+
+/// SYLT frames are not carried over into lofty's generic tag, so the ID3v2 tag
+/// is read on its own.
+fn read_synced_lyrics<R: Read + Seek>(reader: &mut R, file_type: FileType) -> Option<Lyrics> {
+    let options = ParseOptions::new().read_properties(false).read_cover_art(false);
+    let id3v2 = match file_type {
+        FileType::Mpeg => MpegFile::read_from(reader, options).ok()?.id3v2().cloned(),
+        FileType::Wav => WavFile::read_from(reader, options).ok()?.id3v2().cloned(),
+        FileType::Aiff => AiffFile::read_from(reader, options).ok()?.id3v2().cloned(),
+        _ => None,
+    }?;
+    sylt_lyrics(&id3v2)
+}
+
+fn sylt_lyrics(tag: &Id3v2Tag) -> Option<Lyrics> {
+    let Some(Frame::Binary(frame)) = tag.get(&FrameId::Valid(Cow::Borrowed("SYLT"))) else {
+        return None;
+    };
+    let sylt = SynchronizedTextFrame::parse(&frame.data, frame.flags()).ok()?;
+    // MPEG-frame timestamps would need the frame rate; they are rare in practice.
+    if sylt.timestamp_format != TimestampFormat::MS {
+        return None;
+    }
+    Lyrics::from_timed_pieces(&sylt.content, LyricsSource::Embedded)
+}
+
+fn sniff_image_mime(data: &[u8]) -> &'static str {
+    if data.starts_with(b"\x89PNG") {
+        "image/png"
+    } else if data.starts_with(b"\xFF\xD8") {
+        "image/jpeg"
+    } else if data.starts_with(b"GIF8") {
+        "image/gif"
+    } else {
+        "application/octet-stream"
+    }
+}
+
+/// Collects the start of an HTTP body and publishes its tags as soon as they can
+/// be parsed. Tags usually sit at the front of the file; an ID3v2 header says how
+/// far to read, otherwise progressively larger prefixes are tried.
+pub struct TagProbe {
+    head: BytesMut,
+    next_attempt: usize,
+    tx: Option<oneshot::Sender<TrackTags>>,
+}
+
+impl TagProbe {
+    pub fn new() -> (Self, TagsReceiver) {
+        let (tx, rx) = oneshot::channel();
+        (Self { head: BytesMut::new(), next_attempt: FIRST_PROBE_BYTES, tx: Some(tx) }, rx)
+    }
+
+    /// Adds the next piece of the body, parsing once enough has been collected.
+    pub async fn push(&mut self, chunk: &[u8]) {
+        if self.tx.is_none() {
+            return;
+        }
+        self.head.extend_from_slice(chunk);
+        if let Some(end) = id3v2_end(&self.head) {
+            self.next_attempt = self.next_attempt.max(end + AUDIO_AFTER_TAG_BYTES);
+        }
+        if self.head.len() < self.next_attempt {
+            return;
+        }
+        if !self.attempt(false).await {
+            self.next_attempt *= 2;
+            if self.next_attempt > MAX_PROBE_BYTES {
+                self.tx = None;
+                self.head = BytesMut::new();
+            }
+        }
+    }
+
+    /// Whether the tags have been published or the probe gave up.
+    pub fn is_done(&self) -> bool {
+        self.tx.is_none()
+    }
+
+    /// The whole body has arrived; parses it if that has not happened yet.
+    pub async fn finish(mut self) {
+        if self.tx.is_some() && !self.head.is_empty() {
+            self.attempt(true).await;
+        }
+    }
+
+    async fn attempt(&mut self, complete: bool) -> bool {
+        let head = self.head.clone().freeze();
+        let parsed = tokio::task::spawn_blocking(move || read_tags_from_head(&head, complete)).await;
+        match parsed {
+            Ok(Ok(tags)) => {
+                if let Some(tx) = self.tx.take() {
+                    let _ = tx.send(tags);
+                }
+                self.head = BytesMut::new();
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Hands body chunks to a [`TagProbe`] running on a task of its own, so parsing
+/// never holds up the body it reads.
+pub struct TagFeed {
+    tx: mpsc::UnboundedSender<Bytes>,
+    fed: usize,
+}
+
+impl TagFeed {
+    pub fn spawn() -> (Self, TagsReceiver) {
+        let (mut probe, rx) = TagProbe::new();
+        let (tx, mut chunks) = mpsc::unbounded_channel::<Bytes>();
+        tokio::spawn(async move {
+            while let Some(chunk) = chunks.recv().await {
+                // An empty chunk marks the end of the body.
+                if chunk.is_empty() {
+                    probe.finish().await;
+                    return;
+                }
+                probe.push(&chunk).await;
+                if probe.is_done() {
+                    return;
+                }
+            }
+        });
+        (Self { tx, fed: 0 }, rx)
+    }
+
+    /// Queues `chunk` for the probe; `false` once it needs no more.
+    pub fn push(&mut self, chunk: &Bytes) -> bool {
+        if chunk.is_empty() {
+            return true;
+        }
+        self.fed += chunk.len();
+        self.fed <= MAX_PROBE_BYTES + chunk.len() && self.tx.send(chunk.clone()).is_ok()
+    }
+
+    /// The whole body has been pushed.
+    pub fn finish(self) {
+        let _ = self.tx.send(Bytes::new());
+    }
+}
+
+/// Offset just past an ID3v2 tag at the start of `data`, if there is one.
+fn id3v2_end(data: &[u8]) -> Option<usize> {
+    let header = data.get(..10)?;
+    if &header[..3] != b"ID3" {
+        return None;
+    }
+    // Syncsafe integer: 7 bits per byte; a footer adds another 10 bytes.
+    let size = header[6..10].iter().fold(0usize, |acc, &b| (acc << 7) | (b & 0x7f) as usize);
+    let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+    Some(10 + size + footer)
+}
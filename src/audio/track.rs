@@ -7,28 +7,31 @@ pub struct TrackItem {
     pub uri: String,
     pub prepared_path: Option<String>,
     pub metadata: serde_json::Value,
+    /// LRC text sent with the enqueue request.
+    #[serde(default, skip_serializing)]
+    pub lyrics: Option<String>,
 }
 
 impl TrackItem {
-    #[allow(dead_code)]
     pub fn new(uri: &str, metadata: serde_json::Value) -> Self {
-        Self { id: Uuid::new_v4().to_string(), uri: uri.to_string(), prepared_path: None, metadata }
+        Self {
+            id: Uuid::new_v4().to_string(),
+            uri: uri.to_string(),
+            prepared_path: None,
+            metadata,
+            lyrics: None,
+        }
     }
     pub fn new_with_prepared(uri: &str, prepared_path: Option<String>, metadata: serde_json::Value) -> Self {
-        Self { id: Uuid::new_v4().to_string(), uri: uri.to_string(), prepared_path, metadata }
+        Self { id: Uuid::new_v4().to_string(), uri: uri.to_string(), prepared_path, metadata, lyrics: None }
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum LoopMode {
+    #[default]
     None,
     Track,
     Queue,
 }
-
-impl Default for LoopMode {
-    fn default() -> Self {
-        LoopMode::None
-    }
-}
@@ -1,4 +1,6 @@
 use regex::Regex;
+
+use crate::audio::{decoder::DecoderBackend, hls::HlsCodec, subscriber::QueuePolicy};
 use serde::Deserialize;
 
 #[derive(Debug, Clone, Deserialize)]
@@ -13,6 +15,30 @@ pub struct RawConfig {
     pub spotify: SpotifyConfig,
     #[serde(default)]
     pub sources: SourcesConfig,
+    #[serde(default)]
+    pub opus: OpusConfig,
+    #[serde(default)]
+    pub hls: HlsConfig,
+    #[serde(default)]
+    pub voice: VoiceConfig,
+    #[serde(default)]
+    pub recording: RecordingConfig,
+    #[serde(default)]
+    pub metering: MeteringConfig,
+    #[serde(default)]
+    pub spectrum: SpectrumConfig,
+    #[serde(default)]
+    pub silence: SilenceConfig,
+    #[serde(default)]
+    pub subscribers: SubscribersConfig,
+    #[serde(default)]
+    pub pacing: PacingConfig,
+    #[serde(default)]
+    pub decoder: DecoderConfig,
+    #[serde(default)]
+    pub http: HttpConfig,
+    #[serde(default)]
+    pub library: LibraryConfig,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -90,12 +116,326 @@ pub struct SpotifyConfig {
     pub client_secret: Option<String>,
 }
 
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct SourcesConfig {
     #[serde(default)]
     pub allowed: Vec<String>,
     #[serde(default)]
     pub blocked: Vec<String>,
+    #[serde(default = "default_sources_enabled")]
+    pub enabled: Vec<String>,
+}
+fn default_sources_enabled() -> Vec<String> {
+    ["youtube", "soundcloud", "spotify", "local", "http"].map(String::from).to_vec()
+}
+impl Default for SourcesConfig {
+    fn default() -> Self {
+        Self { allowed: Vec::new(), blocked: Vec::new(), enabled: default_sources_enabled() }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OpusConfig {
+    #[serde(default = "default_opus_bitrate")]
+    pub bitrate: u32,
+    #[serde(default = "default_opus_fec")]
+    pub fec: bool,
+    #[serde(default = "default_opus_complexity")]
+    pub complexity: u8,
+    #[serde(default = "default_opus_packet_loss")]
+    pub packet_loss_percent: u8,
+}
+fn default_opus_bitrate() -> u32 {
+    96_000
+}
+fn default_opus_fec() -> bool {
+    true
+}
+fn default_opus_complexity() -> u8 {
+    10
+}
+fn default_opus_packet_loss() -> u8 {
+    10
+}
+impl Default for OpusConfig {
+    fn default() -> Self {
+        Self {
+            bitrate: default_opus_bitrate(),
+            fec: default_opus_fec(),
+            complexity: default_opus_complexity(),
+            packet_loss_percent: default_opus_packet_loss(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HlsConfig {
+    #[serde(default = "default_hls_codec")]
+    pub codec: HlsCodec,
+    #[serde(default = "default_hls_bitrate")]
+    pub bitrate: u32,
+    #[serde(default = "default_hls_segment_secs")]
+    pub segment_secs: u32,
+    #[serde(default = "default_hls_playlist_size")]
+    pub playlist_size: u32,
+    #[serde(default = "default_hls_idle_timeout")]
+    pub idle_timeout_secs: u64,
+}
+fn default_hls_codec() -> HlsCodec {
+    HlsCodec::Aac
+}
+fn default_hls_bitrate() -> u32 {
+    128_000
+}
+fn default_hls_segment_secs() -> u32 {
+    2
+}
+fn default_hls_playlist_size() -> u32 {
+    6
+}
+fn default_hls_idle_timeout() -> u64 {
+    60
+}
+impl Default for HlsConfig {
+    fn default() -> Self {
+        Self {
+            codec: default_hls_codec(),
+            bitrate: default_hls_bitrate(),
+            segment_secs: default_hls_segment_secs(),
+            playlist_size: default_hls_playlist_size(),
+            idle_timeout_secs: default_hls_idle_timeout(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct VoiceConfig {
+    #[serde(default)]
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecordingConfig {
+    #[serde(default = "default_recording_dir")]
+    pub dir: String,
+    #[serde(default = "default_recording_max_duration")]
+    pub max_duration_secs: u64,
+}
+fn default_recording_dir() -> String {
+    "recordings".into()
+}
+fn default_recording_max_duration() -> u64 {
+    3600
+}
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        Self { dir: default_recording_dir(), max_duration_secs: default_recording_max_duration() }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MeteringConfig {
+    #[serde(default = "default_metering_interval")]
+    pub interval_ms: u64,
+}
+fn default_metering_interval() -> u64 {
+    100
+}
+impl Default for MeteringConfig {
+    fn default() -> Self {
+        Self { interval_ms: default_metering_interval() }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpectrumConfig {
+    #[serde(default = "default_spectrum_fft_size")]
+    pub fft_size: usize,
+    #[serde(default = "default_spectrum_bands")]
+    pub bands: usize,
+    #[serde(default = "default_spectrum_smoothing")]
+    pub smoothing: f32,
+    #[serde(default = "default_spectrum_min_freq")]
+    pub min_freq: f32,
+    #[serde(default = "default_spectrum_max_freq")]
+    pub max_freq: f32,
+}
+fn default_spectrum_fft_size() -> usize {
+    2048
+}
+fn default_spectrum_bands() -> usize {
+    32
+}
+fn default_spectrum_smoothing() -> f32 {
+    0.6
+}
+fn default_spectrum_min_freq() -> f32 {
+    20.0
+}
+fn default_spectrum_max_freq() -> f32 {
+    16000.0
+}
+impl Default for SpectrumConfig {
+    fn default() -> Self {
+        Self {
+            fft_size: default_spectrum_fft_size(),
+            bands: default_spectrum_bands(),
+            smoothing: default_spectrum_smoothing(),
+            min_freq: default_spectrum_min_freq(),
+            max_freq: default_spectrum_max_freq(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SilenceConfig {
+    #[serde(default = "default_silence_threshold")]
+    pub threshold_db: f32,
+    #[serde(default = "default_silence_min_duration")]
+    pub min_duration_ms: u64,
+    #[serde(default)]
+    pub trim_leading: bool,
+    #[serde(default)]
+    pub trim_trailing: bool,
+    #[serde(default)]
+    pub detect: bool,
+    #[serde(default)]
+    pub auto_skip_secs: Option<u64>,
+}
+fn default_silence_threshold() -> f32 {
+    -60.0
+}
+fn default_silence_min_duration() -> u64 {
+    2000
+}
+impl Default for SilenceConfig {
+    fn default() -> Self {
+        Self {
+            threshold_db: default_silence_threshold(),
+            min_duration_ms: default_silence_min_duration(),
+            trim_leading: false,
+            trim_trailing: false,
+            detect: false,
+            auto_skip_secs: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SubscribersConfig {
+    #[serde(default = "default_subscriber_queue_frames")]
+    pub queue_frames: usize,
+    #[serde(default = "default_subscriber_policy")]
+    pub policy: QueuePolicy,
+    #[serde(default = "default_subscriber_disconnect_after")]
+    pub disconnect_after_ms: u64,
+}
+fn default_subscriber_queue_frames() -> usize {
+    50
+}
+fn default_subscriber_policy() -> QueuePolicy {
+    QueuePolicy::DropOldest
+}
+fn default_subscriber_disconnect_after() -> u64 {
+    5000
+}
+impl Default for SubscribersConfig {
+    fn default() -> Self {
+        Self {
+            queue_frames: default_subscriber_queue_frames(),
+            policy: default_subscriber_policy(),
+            disconnect_after_ms: default_subscriber_disconnect_after(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PacingConfig {
+    #[serde(default = "default_pacing_max_burst")]
+    pub max_burst_frames: u64,
+}
+fn default_pacing_max_burst() -> u64 {
+    5
+}
+impl Default for PacingConfig {
+    fn default() -> Self {
+        Self { max_burst_frames: default_pacing_max_burst() }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DecoderConfig {
+    #[serde(default = "default_decoder_backend")]
+    pub backend: DecoderBackend,
+}
+fn default_decoder_backend() -> DecoderBackend {
+    DecoderBackend::Ffmpeg
+}
+impl Default for DecoderConfig {
+    fn default() -> Self {
+        Self { backend: default_decoder_backend() }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HttpConfig {
+    #[serde(default = "default_http_read_ahead_kb")]
+    pub read_ahead_kb: usize,
+    #[serde(default = "default_http_segment_retries")]
+    pub segment_retries: u32,
+    #[serde(default = "default_http_live_edge_segments")]
+    pub live_edge_segments: usize,
+}
+fn default_http_read_ahead_kb() -> usize {
+    512
+}
+fn default_http_segment_retries() -> u32 {
+    3
+}
+fn default_http_live_edge_segments() -> usize {
+    3
+}
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            read_ahead_kb: default_http_read_ahead_kb(),
+            segment_retries: default_http_segment_retries(),
+            live_edge_segments: default_http_live_edge_segments(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LibraryConfig {
+    #[serde(default)]
+    pub roots: Vec<String>,
+    #[serde(default = "default_library_rescan_interval")]
+    pub rescan_interval_secs: u64,
+    #[serde(default = "default_library_extensions")]
+    pub extensions: Vec<String>,
+    #[serde(default = "default_library_search_limit")]
+    pub search_limit: usize,
+}
+fn default_library_rescan_interval() -> u64 {
+    300
+}
+fn default_library_extensions() -> Vec<String> {
+    ["mp3", "flac", "ogg", "opus", "m4a", "aac", "wav", "aiff", "wv", "ape", "mpc", "wma"]
+        .map(String::from)
+        .to_vec()
+}
+fn default_library_search_limit() -> usize {
+    25
+}
+impl Default for LibraryConfig {
+    fn default() -> Self {
+        Self {
+            roots: Vec::new(),
+            rescan_interval_secs: default_library_rescan_interval(),
+            extensions: default_library_extensions(),
+            search_limit: default_library_search_limit(),
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -105,13 +445,52 @@ pub struct EffectiveConfig {
     pub clean_log_on_start: bool,
     pub resolver_enabled: bool,
     pub ffmpeg_path: String,
+    /// Cleared at startup when the native backend runs without ffmpeg.
+    pub ffmpeg_available: bool,
     pub resolve_timeout_ms: u64,
     pub allow_spotify_title_search: bool,
     pub allow_patterns: Vec<Regex>,
     pub block_patterns: Vec<Regex>,
+    pub sources_enabled: Vec<String>,
     pub password: Option<String>,
     pub spotify_client_id: Option<String>,
     pub spotify_client_secret: Option<String>,
+    pub opus_bitrate: u32,
+    pub opus_fec: bool,
+    pub opus_complexity: u8,
+    pub opus_packet_loss_percent: u8,
+    pub hls_codec: HlsCodec,
+    pub hls_bitrate: u32,
+    pub hls_segment_secs: u32,
+    pub hls_playlist_size: u32,
+    pub hls_idle_timeout_secs: u64,
+    pub voice_enabled: bool,
+    pub recording_dir: String,
+    pub recording_max_duration_secs: u64,
+    pub metering_interval_ms: u64,
+    pub spectrum_fft_size: usize,
+    pub spectrum_bands: usize,
+    pub spectrum_smoothing: f32,
+    pub spectrum_min_freq: f32,
+    pub spectrum_max_freq: f32,
+    pub silence_threshold_db: f32,
+    pub silence_min_duration_ms: u64,
+    pub silence_trim_leading: bool,
+    pub silence_trim_trailing: bool,
+    pub silence_detect: bool,
+    pub silence_auto_skip_secs: Option<u64>,
+    pub subscriber_queue_frames: usize,
+    pub subscriber_policy: QueuePolicy,
+    pub subscriber_disconnect_after_ms: u64,
+    pub pacing_max_burst_frames: u64,
+    pub decoder_backend: DecoderBackend,
+    pub http_read_ahead_bytes: usize,
+    pub http_segment_retries: u32,
+    pub http_live_edge_segments: usize,
+    pub library_roots: Vec<String>,
+    pub library_rescan_interval_secs: u64,
+    pub library_extensions: Vec<String>,
+    pub library_search_limit: usize,
 }
 
 pub const DEFAULT_CONFIG_TEMPLATE: &str = r#"# Resonix Node Configuration
@@ -156,7 +535,116 @@ allowed = []
 # Regex patterns that are blocked. These take priority over allowed.
 # Example: block SoundCloud completely
 # blocked = ["(^|.*)soundcloud\\.com(/|$)"]
-blocked = []"#;
+blocked = []
+
+# Sources tried for an identifier, in order; the first that handles it wins.
+# Sources left out are disabled. youtube, soundcloud and spotify also need [resolver] enabled.
+# Default: ["youtube", "soundcloud", "spotify", "local", "http"]
+enabled = ["youtube", "soundcloud", "spotify", "local", "http"]
+
+[opus]
+# Defaults for `encoding=opus` stream subscribers; query parameters can override them per connection.
+# Target bitrate in bits per second (6000-510000). Default: 96000
+bitrate = 96000
+# Enable in-band forward error correction. Default: true
+fec = true
+# Encoder complexity 0-10 (higher is slower but better). Default: 10
+complexity = 10
+# Expected packet loss percentage used to tune FEC. Default: 10
+packet_loss_percent = 10
+
+[hls]
+# Live HLS output served at /v0/players/{id}/hls/index.m3u8 (started on first request).
+# Segment codec: "aac" (MPEG-TS segments) or "opus" (fMP4 segments). Default: "aac"
+codec = "aac"
+# Target bitrate in bits per second. Default: 128000
+bitrate = 128000
+# Target segment duration in seconds. Default: 2
+segment_secs = 2
+# Number of segments kept in the rolling playlist. Default: 6
+playlist_size = 6
+# Stop packaging after this many seconds without playlist/segment requests. Default: 60
+idle_timeout_secs = 60
+
+[voice]
+# Let players join Discord voice channels directly (PATCH /v0/players/{id}/voice). Default: false
+enabled = false
+
+[recording]
+# Directory where player recordings are written. Default: "recordings"
+dir = "recordings"
+# Recordings stop automatically after this many seconds. Default: 3600
+max_duration_secs = 3600
+
+[metering]
+# Default interval between level reports on /v0/players/{id}/levels, in ms (20-1000). Default: 100
+interval_ms = 100
+
+[spectrum]
+# FFT window size in samples (power of two). Default: 2048
+fft_size = 2048
+# Default number of log-spaced bands sent on /v0/players/{id}/spectrum. Default: 32
+bands = 32
+# Smoothing between successive frames, 0 (none) to 0.99. Default: 0.6
+smoothing = 0.6
+# Frequency range covered by the bands, in Hz. Defaults: 20 and 16000
+min_freq = 20.0
+max_freq = 16000.0
+
+[silence]
+# Defaults for new players; each player can change them with PATCH /v0/players/{id}/silence.
+# Peak level (dBFS, before volume) below which audio counts as silent. Default: -60.0
+threshold_db = -60.0
+# Silence shorter than this is ignored. Default: 2000
+min_duration_ms = 2000
+# Skip silence at the start / end of tracks. Default: false
+trim_leading = false
+trim_trailing = false
+# Emit a SilenceDetected event on the player event websocket. Default: false
+detect = false
+# Skip to the next track after this many seconds of silence (0 disables). Default: 0
+auto_skip_secs = 0
+
+[subscribers]
+# Per-subscriber queue for stream websocket clients, in 20 ms frames. Default: 50
+queue_frames = 50
+# When a queue is full: "drop_oldest", "drop_newest" or "disconnect". Default: "drop_oldest"
+policy = "drop_oldest"
+# With "disconnect", clients further behind than this are dropped. Default: 5000
+disconnect_after_ms = 5000
+
+[pacing]
+# Most overdue 20 ms frames sent back to back when output falls behind; anything
+# older is dropped and counted as frame deficit. Default: 5
+max_burst_frames = 5
+
+[decoder]
+# "ffmpeg" spawns ffmpeg for every track. "native" decodes MP3, AAC/M4A, FLAC,
+# Ogg Vorbis and WAV in-process and only uses ffmpeg for anything else; ffmpeg
+# then becomes optional at startup. Default: "ffmpeg"
+backend = "ffmpeg"
+
+[http]
+# HTTP sources are decoded while they download; this much of the body is read
+# ahead of the decoder, in KiB. Default: 512
+read_ahead_kb = 512
+# HLS (.m3u8) and DASH (.mpd) sources: attempts per segment after the first
+# failure. Default: 3
+segment_retries = 3
+# Live playlists start this many segments behind the live edge. Default: 3
+live_edge_segments = 3
+
+[library]
+# Directories indexed for `localsearch:` queries (scanned recursively, tags read
+# from each file). Empty disables the library. Example: ["/srv/music"]
+roots = []
+# Seconds between rescans; only new or modified files are read again. 0 scans
+# once at startup. Default: 300
+rescan_interval_secs = 300
+# File extensions that are indexed.
+extensions = ["mp3", "flac", "ogg", "opus", "m4a", "aac", "wav", "aiff", "wv", "ape", "mpc", "wma"]
+# Maximum results returned by a search. Default: 25
+search_limit = 25"#;
 
 pub fn load_config() -> EffectiveConfig {
     let _ = dotenvy::dotenv();
@@ -167,6 +655,18 @@ pub fn load_config() -> EffectiveConfig {
         resolver: Default::default(),
         spotify: Default::default(),
         sources: Default::default(),
+        opus: Default::default(),
+        hls: Default::default(),
+        voice: Default::default(),
+        recording: Default::default(),
+        metering: Default::default(),
+        spectrum: Default::default(),
+        silence: Default::default(),
+        subscribers: Default::default(),
+        pacing: Default::default(),
+        decoder: Default::default(),
+        http: Default::default(),
+        library: Default::default(),
     };
 
     let config_paths = ["resonix.toml", "Resonix.toml"];
@@ -217,13 +717,51 @@ pub fn load_config() -> EffectiveConfig {
         resolver_enabled: resolver_env.unwrap_or(raw.resolver.enabled),
         ffmpeg_path: ffmpeg_env
             .unwrap_or_else(|| raw.resolver.ffmpeg_path.clone().unwrap_or_else(|| "ffmpeg".into())),
+        ffmpeg_available: true,
         resolve_timeout_ms: timeout_env.unwrap_or(raw.resolver.timeout_ms),
         allow_spotify_title_search: raw.resolver.allow_spotify_title_search,
         allow_patterns,
         block_patterns,
+        sources_enabled: raw.sources.enabled,
         password: raw.server.password,
         spotify_client_id,
         spotify_client_secret,
+        opus_bitrate: raw.opus.bitrate,
+        opus_fec: raw.opus.fec,
+        opus_complexity: raw.opus.complexity,
+        opus_packet_loss_percent: raw.opus.packet_loss_percent,
+        hls_codec: raw.hls.codec,
+        hls_bitrate: raw.hls.bitrate,
+        hls_segment_secs: raw.hls.segment_secs,
+        hls_playlist_size: raw.hls.playlist_size,
+        hls_idle_timeout_secs: raw.hls.idle_timeout_secs,
+        voice_enabled: raw.voice.enabled,
+        recording_dir: raw.recording.dir,
+        recording_max_duration_secs: raw.recording.max_duration_secs,
+        metering_interval_ms: raw.metering.interval_ms.clamp(20, 1000),
+        spectrum_fft_size: raw.spectrum.fft_size,
+        spectrum_bands: raw.spectrum.bands,
+        spectrum_smoothing: raw.spectrum.smoothing,
+        spectrum_min_freq: raw.spectrum.min_freq,
+        spectrum_max_freq: raw.spectrum.max_freq,
+        silence_threshold_db: raw.silence.threshold_db.clamp(-120.0, 0.0),
+        silence_min_duration_ms: raw.silence.min_duration_ms,
+        silence_trim_leading: raw.silence.trim_leading,
+        silence_trim_trailing: raw.silence.trim_trailing,
+        silence_detect: raw.silence.detect,
+        silence_auto_skip_secs: raw.silence.auto_skip_secs,
+        subscriber_queue_frames: raw.subscribers.queue_frames,
+        subscriber_policy: raw.subscribers.policy,
+        subscriber_disconnect_after_ms: raw.subscribers.disconnect_after_ms,
+        pacing_max_burst_frames: raw.pacing.max_burst_frames.max(1),
+        decoder_backend: raw.decoder.backend,
+        http_read_ahead_bytes: raw.http.read_ahead_kb.max(16) * 1024,
+        http_segment_retries: raw.http.segment_retries,
+        http_live_edge_segments: raw.http.live_edge_segments.max(1),
+        library_roots: raw.library.roots,
+        library_rescan_interval_secs: raw.library.rescan_interval_secs,
+        library_extensions: raw.library.extensions,
+        library_search_limit: raw.library.search_limit.max(1),
     }
 }
 
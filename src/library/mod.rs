@@ -0,0 +1,199 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, Instant, SystemTime},
+};
+use tracing::{debug, info, warn};
+
+use crate::audio::tags::read_tags;
+use crate::config::EffectiveConfig;
+
+mod search;
+
+use search::SearchKey;
+
+/// Identifier prefix for searching the library, e.g. `localsearch:daft punk around`.
+pub const SEARCH_PREFIX: &str = "localsearch:";
+
+/// One indexed file.
+#[derive(Debug)]
+pub struct LibraryTrack {
+    pub path: PathBuf,
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub duration_ms: u64,
+    modified: Option<SystemTime>,
+    size: u64,
+    key: SearchKey,
+}
+
+impl LibraryTrack {
+    pub fn uri(&self) -> String {
+        self.path.to_string_lossy().into_owned()
+    }
+}
+
+/// Audio files under the configured `[library]` roots, indexed by their tags.
+/// The roots are walked again every `rescan_interval`; only files whose size or
+/// modification time changed are re-read.
+pub struct Library {
+    roots: Vec<PathBuf>,
+    extensions: Vec<String>,
+    rescan_interval: Duration,
+    search_limit: usize,
+    tracks: RwLock<HashMap<PathBuf, Arc<LibraryTrack>>>,
+}
+
+impl Library {
+    pub fn from_config(cfg: &EffectiveConfig) -> Self {
+        Self {
+            roots: cfg.library_roots.iter().map(PathBuf::from).collect(),
+            extensions: cfg
+                .library_extensions
+                .iter()
+                .map(|e| e.trim_start_matches('.').to_ascii_lowercase())
+                .collect(),
+            rescan_interval: Duration::from_secs(cfg.library_rescan_interval_secs),
+            search_limit: cfg.library_search_limit,
+            tracks: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.roots.is_empty()
+    }
+
+    /// Scans once, then keeps rescanning in the background if an interval is set.
+    pub fn spawn_scanner(self: &Arc<Self>) {
+        if !self.is_enabled() {
+            return;
+        }
+        let lib = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let scan = lib.clone();
+                if let Err(e) = tokio::task::spawn_blocking(move || scan.scan()).await {
+                    warn!(?e, "library scan panicked");
+                }
+                if lib.rescan_interval.is_zero() {
+                    break;
+                }
+                tokio::time::sleep(lib.rescan_interval).await;
+            }
+        });
+    }
+
+    /// Walks every root and swaps in the updated index. Blocking.
+    fn scan(&self) {
+        let started = Instant::now();
+        let previous = self.tracks.read().map(|t| t.clone()).unwrap_or_default();
+        let mut next = HashMap::with_capacity(previous.len());
+        let (mut added, mut updated) = (0usize, 0usize);
+        for root in &self.roots {
+            let mut dirs = vec![root.clone()];
+            while let Some(dir) = dirs.pop() {
+                let entries = match std::fs::read_dir(&dir) {
+                    Ok(e) => e,
+                    Err(e) => {
+                        warn!(dir = %dir.display(), error = %e, "cannot read library directory");
+                        continue;
+                    }
+                };
+                for entry in entries.flatten() {
+                    let path = entry.path();
+                    let Ok(file_type) = entry.file_type() else { continue };
+                    // Symlinked directories are not followed, so link cycles cannot recurse forever.
+                    if file_type.is_dir() {
+                        dirs.push(path);
+                        continue;
+                    }
+                    if !self.has_audio_extension(&path) {
+                        continue;
+                    }
+                    let Ok(meta) = std::fs::metadata(&path) else { continue };
+                    if !meta.is_file() {
+                        continue;
+                    }
+                    let modified = meta.modified().ok();
+                    let size = meta.len();
+                    if let Some(old) =
+                        previous.get(&path).filter(|t| t.modified == modified && t.size == size)
+                    {
+                        next.insert(path, old.clone());
+                        continue;
+                    }
+                    if previous.contains_key(&path) {
+                        updated += 1;
+                    } else {
+                        added += 1;
+                    }
+                    let track = index_file(&path, modified, size);
+                    next.insert(path, Arc::new(track));
+                }
+            }
+        }
+        let removed = previous.keys().filter(|p| !next.contains_key(*p)).count();
+        let total = next.len();
+        if let Ok(mut tracks) = self.tracks.write() {
+            *tracks = next;
+        }
+        let took_ms = started.elapsed().as_millis() as u64;
+        if added + updated + removed > 0 {
+            info!(total, added, updated, removed, took_ms, "library scanned");
+        } else {
+            debug!(total, took_ms, "library unchanged");
+        }
+    }
+
+    fn has_audio_extension(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| self.extensions.iter().any(|x| x.eq_ignore_ascii_case(e)))
+    }
+
+    /// Best matches for `query` over title, artist and album, tolerating typos.
+    /// Scoring runs on the blocking pool since it touches every indexed track.
+    pub async fn search(self: &Arc<Self>, query: &str, limit: Option<usize>) -> Vec<Arc<LibraryTrack>> {
+        let lib = self.clone();
+        let query = query.to_string();
+        tokio::task::spawn_blocking(move || lib.search_blocking(&query, limit)).await.unwrap_or_default()
+    }
+
+    fn search_blocking(&self, query: &str, limit: Option<usize>) -> Vec<Arc<LibraryTrack>> {
+        let query = SearchKey::query(query);
+        if query.is_empty() {
+            return Vec::new();
+        }
+        let Ok(tracks) = self.tracks.read() else {
+            return Vec::new();
+        };
+        let mut scored: Vec<(f32, &Arc<LibraryTrack>)> =
+            tracks.values().filter_map(|t| t.key.score(&query).map(|s| (s, t))).collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.title.cmp(&b.1.title)));
+        scored.into_iter().take(limit.unwrap_or(self.search_limit)).map(|(_, t)| t.clone()).collect()
+    }
+}
+
+fn index_file(path: &Path, modified: Option<SystemTime>, size: u64) -> LibraryTrack {
+    let tags = read_tags(path).unwrap_or_else(|e| {
+        debug!(path = %path.display(), error = %e, "no readable tags");
+        Default::default()
+    });
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    let title = tags.title.unwrap_or_else(|| stem.to_string());
+    let artist = tags.artist.unwrap_or_default();
+    let album = tags.album.unwrap_or_default();
+    let key = SearchKey::new(&title, &artist, &album);
+    LibraryTrack {
+        path: path.to_path_buf(),
+        title,
+        artist,
+        album,
+        duration_ms: tags.duration_ms,
+        modified,
+        size,
+        key,
+    }
+}
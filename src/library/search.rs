@@ -0,0 +1,146 @@
+/// Field weights: a title hit counts more than an artist or album hit.
+const TITLE_WEIGHT: f32 = 1.0;
+const ARTIST_WEIGHT: f32 = 0.9;
+const ALBUM_WEIGHT: f32 = 0.7;
+
+/// Normalised words of a track's title, artist and album, computed once at index time.
+#[derive(Debug, Default)]
+pub struct SearchKey {
+    title: Vec<String>,
+    artist: Vec<String>,
+    album: Vec<String>,
+    title_phrase: String,
+}
+
+impl SearchKey {
+    pub fn new(title: &str, artist: &str, album: &str) -> Self {
+        let title = words(title);
+        Self { title_phrase: title.join(" "), title, artist: words(artist), album: words(album) }
+    }
+
+    /// Normalised words of a search query.
+    pub fn query(query: &str) -> Vec<String> {
+        words(query)
+    }
+
+    /// Scores a query against this track; `None` unless every query word matches
+    /// some field.
+    pub fn score(&self, query: &[String]) -> Option<f32> {
+        let mut total = 0.0;
+        for q in query {
+            let best =
+                [(&self.title, TITLE_WEIGHT), (&self.artist, ARTIST_WEIGHT), (&self.album, ALBUM_WEIGHT)]
+                    .into_iter()
+                    .map(|(field, weight)| {
+                        field.iter().map(|w| word_score(q, w)).fold(0.0, f32::max) * weight
+                    })
+                    .fold(0.0, f32::max);
+            if best == 0.0 {
+                return None;
+            }
+            total += best;
+        }
+        let mut score = total / query.len() as f32;
+        let phrase = query.join(" ");
+        if self.title_phrase == phrase {
+            score += 0.5;
+        } else if self.title_phrase.contains(&phrase) {
+            score += 0.2;
+        }
+        Some(score)
+    }
+}
+
+/// How well query word `q` matches indexed word `w`: exact, prefix (typing in
+/// progress), substring, then within a small edit distance.
+fn word_score(q: &str, w: &str) -> f32 {
+    if q == w {
+        return 1.0;
+    }
+    let q_len = q.chars().count();
+    if w.starts_with(q) {
+        return if q_len >= 2 { 0.9 } else { 0.5 };
+    }
+    if q_len >= 3 && w.contains(q) {
+        return 0.75;
+    }
+    let allowed = match q_len {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    };
+    if allowed == 0 {
+        return 0.0;
+    }
+    let w_len = w.chars().count();
+    if q_len.abs_diff(w_len) <= allowed {
+        let d = edit_distance(q, w);
+        if d <= allowed {
+            return 0.7 - 0.1 * d as f32;
+        }
+    }
+    // A typo in a word that is still being typed.
+    if w_len > q_len {
+        let prefix: String = w.chars().take(q_len).collect();
+        if edit_distance(q, &prefix) <= allowed {
+            return 0.5;
+        }
+    }
+    0.0
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut cur = vec![0; b.len() + 1];
+    for (i, ca) in a.chars().enumerate() {
+        cur[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != cb);
+            cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
+}
+
+/// Lowercases, folds common accents and splits on anything that is not a letter or digit.
+fn words(text: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut word = String::new();
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            match fold(c) {
+                Some(folded) => word.push_str(folded),
+                None => word.push(c),
+            }
+        } else if c != '\'' && !word.is_empty() {
+            out.push(std::mem::take(&mut word));
+        }
+    }
+    if !word.is_empty() {
+        out.push(word);
+    }
+    out
+}
+
+fn fold(c: char) -> Option<&'static str> {
+    Some(match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' => "a",
+        'æ' => "ae",
+        'ç' | 'č' | 'ć' => "c",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ę' | 'ě' => "e",
+        'ì' | 'í' | 'î' | 'ï' | 'ī' => "i",
+        'ñ' | 'ń' | 'ň' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ő' => "o",
+        'œ' => "oe",
+        'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' | 'ű' => "u",
+        'ý' | 'ÿ' => "y",
+        'ß' => "ss",
+        'š' | 'ś' => "s",
+        'ž' | 'ź' | 'ż' => "z",
+        'ł' => "l",
+        'ř' => "r",
+        _ => return None,
+    })
+}
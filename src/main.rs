@@ -13,16 +13,22 @@ mod api;
 mod audio;
 mod cli;
 mod config;
+mod library;
 mod middleware;
 mod resolver;
 mod state;
 mod utils;
+mod voice;
 
 use crate::api::handlers::{
-    create_player, decode_track, decode_tracks, delete_player, enqueue, get_queue, info, list_players,
-    load_tracks, pause, play, resolve_http, set_loop_mode, skip, update_filters, update_metadata, ws_events,
-    ws_stream,
+    analyze, create_player, create_sink, decode_track, decode_tracks, delete_player, delete_recording,
+    delete_sink, delete_voice, download_recording, enqueue, get_artwork, get_lyrics, get_queue, get_silence,
+    hls_playlist, hls_segment, info, list_players, list_recordings, list_sinks, load_tracks, next_chapter,
+    pause, play, resolve_http, set_loop_mode, skip, start_recording, stop_recording, stream_mp3, stream_ogg,
+    stream_wav, update_filters, update_metadata, update_silence, update_voice, ws_events, ws_levels,
+    ws_spectrum, ws_stream,
 };
+use crate::audio::decoder::DecoderBackend;
 use crate::config::load_config;
 use crate::middleware::auth::auth_middleware;
 use crate::state::AppState;
@@ -80,7 +86,7 @@ async fn main() -> Result<()> {
     let version = env!("CARGO_PKG_VERSION");
     let os = System::name().unwrap_or_else(|| "Unknown OS".into());
     let os_ver = System::os_version().unwrap_or_default();
-    let total_mem_mb = (sys.total_memory() / (1024 * 1024)) as u64;
+    let total_mem_mb = sys.total_memory() / (1024 * 1024);
     let cpu_brand = sys.cpus().first().map(|c| c.brand().to_string()).unwrap_or_else(|| "Unknown CPU".into());
     if total_mem_mb == 0 {
         warn!("Unable to determine RAM size");
@@ -95,6 +101,7 @@ async fn main() -> Result<()> {
     );
 
     let state = AppState::new(cfg.clone());
+    state.library.spawn_scanner();
 
     let (shutdown_tx, mut shutdown_rx) = broadcast::channel::<()>(1);
     ctrlc::set_handler(move || {
@@ -109,15 +116,38 @@ async fn main() -> Result<()> {
         .route("/v0/players/{id}/pause", post(pause))
         .route("/v0/players/{id}", delete(delete_player))
         .route("/v0/players/{id}/filters", patch(update_filters))
+        .route("/v0/players/{id}/silence", get(get_silence))
+        .route("/v0/players/{id}/silence", patch(update_silence))
         .route("/v0/players/{id}/metadata", patch(update_metadata))
+        .route("/v0/players/{id}/artwork", get(get_artwork))
+        .route("/v0/players/{id}/lyrics", get(get_lyrics))
         .route("/v0/players/{id}/ws", get(ws_stream))
         .route("/v0/players/{id}/events", get(ws_events))
+        .route("/v0/players/{id}/levels", get(ws_levels))
+        .route("/v0/players/{id}/spectrum", get(ws_spectrum))
+        .route("/v0/players/{id}/stream.ogg", get(stream_ogg))
+        .route("/v0/players/{id}/stream.mp3", get(stream_mp3))
+        .route("/v0/players/{id}/stream.wav", get(stream_wav))
+        .route("/v0/players/{id}/hls/index.m3u8", get(hls_playlist))
+        .route("/v0/players/{id}/hls/{segment}", get(hls_segment))
+        .route("/v0/players/{id}/voice", patch(update_voice))
+        .route("/v0/players/{id}/voice", delete(delete_voice))
+        .route("/v0/players/{id}/sinks", post(create_sink))
+        .route("/v0/players/{id}/sinks", get(list_sinks))
+        .route("/v0/players/{id}/sinks/{sink_id}", delete(delete_sink))
+        .route("/v0/players/{id}/recordings", post(start_recording))
+        .route("/v0/recordings", get(list_recordings))
+        .route("/v0/recordings/{rec_id}", delete(delete_recording))
+        .route("/v0/recordings/{rec_id}/stop", post(stop_recording))
+        .route("/v0/recordings/{rec_id}/download", get(download_recording))
         .route("/v0/players/{id}/queue", post(enqueue))
         .route("/v0/players/{id}/queue", get(get_queue))
         .route("/v0/players/{id}/loop", patch(set_loop_mode))
         .route("/v0/players/{id}/skip", post(skip))
+        .route("/v0/players/{id}/chapters/next", post(next_chapter))
         .route("/v0/resolve", get(resolve_http))
         .route("/v0/loadtracks", get(load_tracks))
+        .route("/v0/analyze", get(analyze))
         .route("/v0/decodetrack", get(decode_track))
         .route("/v0/decodetracks", post(decode_tracks))
         .route("/info", get(info))
@@ -158,6 +188,14 @@ async fn ensure_ffmpeg_available(cfg: &mut crate::config::EffectiveConfig) -> Re
         }
     }
 
+    if cfg.decoder_backend == DecoderBackend::Native {
+        warn!(
+            "ffmpeg not found; continuing with the native decoder only (Opus and other formats, encoded streams, HLS and recordings will fail)"
+        );
+        cfg.ffmpeg_available = false;
+        return Ok(());
+    }
+
     let downloaded_path = ffmpeg::download_latest_ffmpeg().await?;
     cfg.ffmpeg_path = downloaded_path.to_string_lossy().into_owned();
     check_ffmpeg(&cfg.ffmpeg_path).await?;
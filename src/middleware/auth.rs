@@ -3,6 +3,11 @@ use axum::{http::StatusCode, middleware::Next};
 
 use crate::state::AppState;
 
+/// Routes meant for `<audio>` elements and media players, which cannot send headers.
+const TOKEN_QUERY_SUFFIXES: [&str; 3] = ["/stream.ogg", "/stream.mp3", "/stream.wav"];
+/// HLS playlist and segments, fetched by native players that cannot send headers either.
+const TOKEN_QUERY_HLS: &str = "/hls/";
+
 pub async fn auth_middleware(
     State(state): State<AppState>,
     req: axum::http::Request<axum::body::Body>,
@@ -10,10 +15,24 @@ pub async fn auth_middleware(
 ) -> Result<axum::response::Response, StatusCode> {
     if let Some(pw) = &state.cfg.password {
         let hdr = req.headers().get(axum::http::header::AUTHORIZATION).and_then(|h| h.to_str().ok());
-        match hdr {
-            Some(val) if val == pw => {}
-            _ => return Err(StatusCode::UNAUTHORIZED),
+        let authorized = hdr == Some(pw.as_str()) || query_token(&req).as_deref() == Some(pw.as_str());
+        if !authorized {
+            return Err(StatusCode::UNAUTHORIZED);
         }
     }
     Ok(next.run(req).await)
 }
+
+/// The `token` query parameter, for the stream and HLS routes only.
+fn query_token(req: &axum::http::Request<axum::body::Body>) -> Option<String> {
+    let path = req.uri().path();
+    let is_player = path.starts_with("/v0/players/");
+    if !is_player
+        || !(TOKEN_QUERY_SUFFIXES.iter().any(|s| path.ends_with(s)) || path.contains(TOKEN_QUERY_HLS))
+    {
+        return None;
+    }
+    url::form_urlencoded::parse(req.uri().query()?.as_bytes())
+        .find(|(k, _)| k == "token")
+        .map(|(_, v)| v.into_owned())
+}
@@ -0,0 +1,84 @@
+use anyhow::Result;
+use futures::future::BoxFuture;
+use std::{sync::Arc, time::Duration};
+
+use super::manager::{LoadedItem, PlaylistMeta, SourceManager, TrackMeta};
+use crate::audio::http::{HttpSettings, HttpStream};
+use crate::audio::playlist::load_playlist;
+use crate::config::EffectiveConfig;
+
+/// How long loading waits for tags embedded at the start of a file.
+const TAGS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Direct `http(s)://` audio URLs, played as they are.
+pub struct HttpSource {
+    cfg: Arc<EffectiveConfig>,
+}
+
+impl HttpSource {
+    pub fn new(cfg: Arc<EffectiveConfig>) -> Self {
+        Self { cfg }
+    }
+
+    /// Playlist files expand to their entries. Anything else is opened as a player
+    /// would open it: radio streams are named after their station, HLS and DASH take
+    /// liveness and length from the manifest, files take title, artist and length
+    /// from their tags.
+    async fn load(&self, url: &str) -> Result<LoadedItem> {
+        if let Some(playlist) = load_playlist(url).await? {
+            return Ok(LoadedItem::Playlist(PlaylistMeta::from_file(playlist)));
+        }
+        let mut stream = HttpStream::open(url, &HttpSettings::from_config(&self.cfg)).await?;
+        let mut track = TrackMeta::untitled(url, self.name());
+        if let Some(info) = stream.info() {
+            track.is_stream = info.live;
+            track.length_ms = info.duration_ms;
+        } else if let Some(icy) = stream.icy() {
+            track.is_stream = true;
+            if let Some(station) = icy.borrow().station.clone() {
+                track.title = station;
+            }
+        } else if let Some(tags) = stream.take_tags() {
+            // Nothing decodes the body here, so it is read and dropped to keep the
+            // download (and with it the tag probe) going past `read_ahead`.
+            let drain = async {
+                while let Some(Ok(_)) = stream.next_chunk().await {}
+                std::future::pending::<()>().await
+            };
+            let tags = tokio::time::timeout(TAGS_TIMEOUT, async {
+                tokio::select! {
+                    tags = tags => tags.ok(),
+                    _ = drain => None,
+                }
+            });
+            if let Ok(Some(tags)) = tags.await {
+                track.title = tags.title.unwrap_or(track.title);
+                track.author = tags.artist.unwrap_or_default();
+                track.length_ms = tags.duration_ms;
+                track.isrc = tags.isrc;
+                if let Some(album) = tags.album {
+                    track.plugin_info = serde_json::json!({ "album": album });
+                }
+            }
+        }
+        Ok(LoadedItem::Track(track))
+    }
+}
+
+impl SourceManager for HttpSource {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    fn matches(&self, identifier: &str) -> bool {
+        url::Url::parse(identifier).is_ok_and(|u| matches!(u.scheme(), "http" | "https"))
+    }
+
+    fn load_item<'a>(&'a self, identifier: &'a str) -> BoxFuture<'a, Result<LoadedItem>> {
+        Box::pin(self.load(identifier))
+    }
+
+    fn resolve<'a>(&'a self, identifier: &'a str) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move { Ok(identifier.to_string()) })
+    }
+}
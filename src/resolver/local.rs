@@ -0,0 +1,99 @@
+use anyhow::{anyhow, bail, Result};
+use futures::future::BoxFuture;
+use std::{path::PathBuf, sync::Arc};
+
+use super::manager::{search_query, LoadedItem, PlaylistMeta, SourceManager, TrackMeta};
+use crate::audio::playlist::load_playlist;
+use crate::audio::tags::read_tags;
+use crate::library::{Library, LibraryTrack, SEARCH_PREFIX};
+
+/// Files on disk (paths and `file://` URLs), playlist files and `localsearch:` queries over the library.
+pub struct LocalSource {
+    library: Arc<Library>,
+}
+
+impl LocalSource {
+    pub fn new(library: Arc<Library>) -> Self {
+        Self { library }
+    }
+
+    async fn load(&self, identifier: &str) -> Result<LoadedItem> {
+        if let Some(query) = search_query(identifier, &[SEARCH_PREFIX]) {
+            if !self.library.is_enabled() {
+                bail!("Local library is not configured (no [library] roots)");
+            }
+            let tracks = self.library.search(query, None).await;
+            return Ok(LoadedItem::Search(tracks.iter().map(|t| library_track(t)).collect()));
+        }
+        let path = local_path(identifier).ok_or_else(|| anyhow!("file not found: {identifier}"))?;
+        if let Some(playlist) = load_playlist(identifier).await? {
+            return Ok(LoadedItem::Playlist(PlaylistMeta::from_file(playlist)));
+        }
+        let tags = {
+            let path = path.clone();
+            tokio::task::spawn_blocking(move || read_tags(&path)).await?.unwrap_or_default()
+        };
+        let mut track = TrackMeta::untitled(identifier, self.name());
+        track.title = tags.title.unwrap_or(track.title);
+        track.author = tags.artist.unwrap_or_default();
+        track.length_ms = tags.duration_ms;
+        track.isrc = tags.isrc;
+        if let Some(album) = tags.album {
+            track.plugin_info = serde_json::json!({ "album": album });
+        }
+        Ok(LoadedItem::Track(track))
+    }
+
+    async fn resolve_identifier(&self, identifier: &str) -> Result<String> {
+        let Some(query) = search_query(identifier, &[SEARCH_PREFIX]) else {
+            return Ok(identifier.to_string());
+        };
+        let best = self.library.search(query, Some(1)).await;
+        let track = best.first().ok_or_else(|| anyhow!("no match in the local library"))?;
+        tracing::info!(%query, path = %track.path.display(), "local search matched");
+        Ok(track.uri())
+    }
+}
+
+impl SourceManager for LocalSource {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    fn search_prefixes(&self) -> &'static [&'static str] {
+        &[SEARCH_PREFIX]
+    }
+
+    fn matches(&self, identifier: &str) -> bool {
+        local_path(identifier).is_some()
+    }
+
+    fn load_item<'a>(&'a self, identifier: &'a str) -> BoxFuture<'a, Result<LoadedItem>> {
+        Box::pin(self.load(identifier))
+    }
+
+    fn resolve<'a>(&'a self, identifier: &'a str) -> BoxFuture<'a, Result<String>> {
+        Box::pin(self.resolve_identifier(identifier))
+    }
+}
+
+/// The file an identifier names, if it exists.
+fn local_path(identifier: &str) -> Option<PathBuf> {
+    let path = match url::Url::parse(identifier) {
+        Ok(u) if u.scheme() == "file" => u.to_file_path().ok()?,
+        // A single letter is a Windows drive, not a scheme.
+        Ok(u) if u.scheme().len() > 1 => return None,
+        _ => PathBuf::from(identifier),
+    };
+    path.is_file().then_some(path)
+}
+
+fn library_track(track: &LibraryTrack) -> TrackMeta {
+    TrackMeta {
+        title: track.title.clone(),
+        author: track.artist.clone(),
+        length_ms: track.duration_ms,
+        plugin_info: serde_json::json!({ "album": track.album }),
+        ..TrackMeta::untitled(&track.uri(), "local")
+    }
+}
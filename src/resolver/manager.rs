@@ -0,0 +1,210 @@
+use anyhow::{anyhow, Result};
+use futures::future::BoxFuture;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::audio::playlist::Playlist;
+use crate::config::EffectiveConfig;
+use crate::library::Library;
+
+use super::{
+    http::HttpSource, local::LocalSource, soundcloud::SoundcloudSource, spotify::SpotifySource,
+    youtube::YoutubeSource,
+};
+
+/// Metadata of one playable track.
+#[derive(Debug, Clone)]
+pub struct TrackMeta {
+    /// Source-specific id (YouTube video id, file path, ...).
+    pub identifier: String,
+    /// What to play; resolved again through the registry at playback.
+    pub uri: String,
+    pub title: String,
+    pub author: String,
+    /// 0 when unknown.
+    pub length_ms: u64,
+    pub is_stream: bool,
+    pub artwork_url: Option<String>,
+    pub isrc: Option<String>,
+    pub source_name: &'static str,
+    /// Extra fields reported under `pluginInfo`.
+    pub plugin_info: serde_json::Value,
+}
+
+impl TrackMeta {
+    /// A track known only by its uri, titled by its file name.
+    pub fn untitled(uri: &str, source_name: &'static str) -> Self {
+        Self {
+            identifier: uri.to_string(),
+            uri: uri.to_string(),
+            title: super::display_name(uri),
+            author: String::new(),
+            length_ms: 0,
+            is_stream: false,
+            artwork_url: None,
+            isrc: None,
+            source_name,
+            plugin_info: serde_json::json!({}),
+        }
+    }
+}
+
+/// A named list of tracks: an album, a playlist or a playlist file.
+#[derive(Debug, Clone)]
+pub struct PlaylistMeta {
+    pub name: String,
+    /// The track the identifier pointed at, e.g. the video of a YouTube `watch?v=..&list=..` link.
+    pub selected_track: Option<usize>,
+    pub tracks: Vec<TrackMeta>,
+}
+
+impl PlaylistMeta {
+    /// An M3U, PLS or XSPF file; entries are local files or `http(s)` URLs.
+    pub fn from_file(playlist: Playlist) -> Self {
+        let tracks = playlist
+            .entries
+            .into_iter()
+            .map(|e| {
+                let source_name = if e.uri.starts_with("http://") || e.uri.starts_with("https://") {
+                    "http"
+                } else {
+                    "local"
+                };
+                let mut track = TrackMeta::untitled(&e.uri, source_name);
+                track.title = e.title.unwrap_or(track.title);
+                track.author = e.author.unwrap_or_default();
+                track.length_ms = e.length_ms.unwrap_or(0);
+                track
+            })
+            .collect();
+        Self { name: playlist.name, selected_track: None, tracks }
+    }
+}
+
+/// What an identifier loads as.
+#[derive(Debug, Clone)]
+pub enum LoadedItem {
+    Track(TrackMeta),
+    Playlist(PlaylistMeta),
+    Search(Vec<TrackMeta>),
+    Empty,
+}
+
+/// A place tracks come from. Sources are asked in the order of `[sources] enabled`;
+/// the first one whose search prefix or `matches` fits an identifier handles it.
+pub trait SourceManager: Send + Sync {
+    /// Name used in `[sources] enabled` and as the tracks' `sourceName`.
+    fn name(&self) -> &'static str;
+
+    /// Identifier prefixes that make the rest a search query, e.g. `ytsearch:`.
+    fn search_prefixes(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Whether this source handles `identifier` when it is not a search.
+    fn matches(&self, identifier: &str) -> bool;
+
+    /// Looks up metadata without fetching any audio.
+    fn load_item<'a>(&'a self, identifier: &'a str) -> BoxFuture<'a, Result<LoadedItem>>;
+
+    /// Turns `identifier` into a file path or URL the decoder can open.
+    fn resolve<'a>(&'a self, identifier: &'a str) -> BoxFuture<'a, Result<String>>;
+}
+
+/// Strips one of `prefixes` (case-insensitively) and returns the trimmed query.
+pub fn search_query<'a>(identifier: &'a str, prefixes: &[&str]) -> Option<&'a str> {
+    prefixes.iter().find_map(|p| {
+        let head = identifier.get(..p.len())?;
+        head.eq_ignore_ascii_case(p).then(|| identifier[p.len()..].trim())
+    })
+}
+
+/// The enabled sources, in priority order.
+pub struct SourceRegistry {
+    sources: Vec<Arc<dyn SourceManager>>,
+}
+
+impl SourceRegistry {
+    /// Keeps the `available` sources named in `[sources] enabled`, in that order.
+    pub fn new(cfg: &EffectiveConfig, available: Vec<Arc<dyn SourceManager>>) -> Self {
+        let mut sources = Vec::with_capacity(cfg.sources_enabled.len());
+        for name in &cfg.sources_enabled {
+            match available.iter().find(|s| s.name().eq_ignore_ascii_case(name)) {
+                Some(s) => sources.push(s.clone()),
+                None if BUILTIN_REMOTE.contains(&name.as_str()) && !cfg.resolver_enabled => {}
+                None => warn!(source = %name, "unknown source in [sources] enabled"),
+            }
+        }
+        let registry = Self { sources };
+        info!(sources = ?registry.names(), "sources enabled");
+        registry
+    }
+
+    /// The built-in sources. YouTube, SoundCloud and Spotify need `[resolver] enabled`.
+    pub fn builtin(cfg: &Arc<EffectiveConfig>, library: &Arc<Library>) -> Vec<Arc<dyn SourceManager>> {
+        let mut sources: Vec<Arc<dyn SourceManager>> =
+            vec![Arc::new(LocalSource::new(library.clone())), Arc::new(HttpSource::new(cfg.clone()))];
+        if cfg.resolver_enabled {
+            sources.push(Arc::new(YoutubeSource::new(cfg.clone())));
+            sources.push(Arc::new(SoundcloudSource::new(cfg.clone())));
+            sources.push(Arc::new(SpotifySource::new(cfg.clone())));
+        }
+        sources
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.sources.iter().map(|s| s.name()).collect()
+    }
+
+    /// The source handling `identifier`: a search prefix wins over `matches`.
+    pub fn find(&self, identifier: &str) -> Option<&Arc<dyn SourceManager>> {
+        self.sources
+            .iter()
+            .find(|s| search_query(identifier, s.search_prefixes()).is_some())
+            .or_else(|| self.sources.iter().find(|s| s.matches(identifier)))
+    }
+
+    pub fn is_search(&self, identifier: &str) -> bool {
+        self.sources.iter().any(|s| search_query(identifier, s.search_prefixes()).is_some())
+    }
+
+    /// Loads `identifier`'s metadata; `Empty` when no source handles it.
+    pub async fn load(&self, identifier: &str) -> Result<LoadedItem> {
+        match self.find(identifier) {
+            Some(source) => source.load_item(identifier).await,
+            None => Ok(LoadedItem::Empty),
+        }
+    }
+
+    /// Resolves `identifier` for playback, retrying transient failures. Identifiers
+    /// no source handles are returned as they are.
+    pub async fn resolve(&self, identifier: &str) -> Result<String> {
+        let Some(source) = self.find(identifier) else {
+            return Ok(identifier.to_string());
+        };
+        let mut last_err: Option<anyhow::Error> = None;
+        for attempt in 1..=3 {
+            match source.resolve(identifier).await {
+                Ok(s) => return Ok(s),
+                Err(e) => {
+                    let em = e.to_string();
+                    if em.contains("probe")
+                        || em.contains("unsupported feature")
+                        || em.contains("unsupported codec")
+                    {
+                        last_err = Some(e);
+                        let delay_ms = 250 * attempt;
+                        tokio::time::sleep(std::time::Duration::from_millis(delay_ms)).await;
+                        continue;
+                    } else {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+        Err(last_err.unwrap_or_else(|| anyhow!("resolve failed after retries")))
+    }
+}
+
+/// Built-in sources that are only available with the resolver enabled.
+const BUILTIN_REMOTE: [&str; 3] = ["youtube", "soundcloud", "spotify"];
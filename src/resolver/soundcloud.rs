@@ -0,0 +1,77 @@
+use anyhow::{anyhow, Context, Result};
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use std::sync::Arc;
+
+use super::manager::{LoadedItem, SourceManager, TrackMeta};
+use super::{host, resolver_timeout, riva_client, write_bytes_to_temp_file};
+use crate::config::EffectiveConfig;
+
+/// SoundCloud track pages, downloaded through Riva.
+pub struct SoundcloudSource {
+    cfg: Arc<EffectiveConfig>,
+}
+
+#[derive(Deserialize)]
+struct SoundcloudOEmbed {
+    title: String,
+    #[serde(default)]
+    author_name: String,
+    #[serde(default)]
+    thumbnail_url: Option<String>,
+}
+
+impl SoundcloudSource {
+    pub fn new(cfg: Arc<EffectiveConfig>) -> Self {
+        Self { cfg }
+    }
+
+    /// SoundCloud's oEmbed gives the title, artist and artwork but not the length.
+    async fn load(&self, url: &str) -> Result<LoadedItem> {
+        let client = reqwest::Client::builder().timeout(resolver_timeout(&self.cfg)).build()?;
+        let resp = client
+            .get("https://soundcloud.com/oembed")
+            .query(&[("format", "json"), ("url", url)])
+            .send()
+            .await
+            .context("soundcloud oembed get")?
+            .error_for_status()
+            .context("soundcloud oembed bad status")?;
+        let v: SoundcloudOEmbed = resp.json().await.context("soundcloud oembed parse json")?;
+        // The oEmbed title reads "<track> by <artist>".
+        let suffix = format!(" by {}", v.author_name);
+        let title = v.title.strip_suffix(&suffix).unwrap_or(&v.title).to_string();
+        Ok(LoadedItem::Track(TrackMeta {
+            title,
+            author: v.author_name,
+            artwork_url: v.thumbnail_url,
+            ..TrackMeta::untitled(url, self.name())
+        }))
+    }
+
+    async fn download(&self, url: &str) -> Result<String> {
+        let client = riva_client()?;
+        let response =
+            client.soundcloud_stream(url).await.map_err(|e| anyhow!("soundcloud stream failed: {e}"))?;
+        let body = response.bytes().await.context("soundcloud stream read body failed")?;
+        write_bytes_to_temp_file(&body)
+    }
+}
+
+impl SourceManager for SoundcloudSource {
+    fn name(&self) -> &'static str {
+        "soundcloud"
+    }
+
+    fn matches(&self, identifier: &str) -> bool {
+        host(identifier).is_some_and(|h| h.contains("soundcloud.com"))
+    }
+
+    fn load_item<'a>(&'a self, identifier: &'a str) -> BoxFuture<'a, Result<LoadedItem>> {
+        Box::pin(self.load(identifier))
+    }
+
+    fn resolve<'a>(&'a self, identifier: &'a str) -> BoxFuture<'a, Result<String>> {
+        Box::pin(self.download(identifier))
+    }
+}
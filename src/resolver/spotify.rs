@@ -0,0 +1,278 @@
+use anyhow::{anyhow, Context, Result};
+use futures::{future::BoxFuture, StreamExt, TryStreamExt};
+use rspotify::{
+    model::{AlbumId, FullTrack, PlayableItem, PlaylistId, SimplifiedTrack, TrackId},
+    prelude::{BaseClient, Id},
+    ClientCredsSpotify, Credentials,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use url::Url;
+
+use super::manager::{LoadedItem, PlaylistMeta, SourceManager, TrackMeta};
+use super::{host, youtube::resolve_youtube_search};
+use crate::config::EffectiveConfig;
+
+/// Spotify tracks, albums and playlists, played by searching YouTube for each track's artist and title.
+pub struct SpotifySource {
+    cfg: Arc<EffectiveConfig>,
+}
+
+impl SpotifySource {
+    pub fn new(cfg: Arc<EffectiveConfig>) -> Self {
+        Self { cfg }
+    }
+
+    async fn load(&self, input: &str) -> Result<LoadedItem> {
+        let link = parse_spotify_link(input)
+            .ok_or_else(|| anyhow!("not a Spotify track, album or playlist link"))?;
+        if let Some((client_id, client_secret)) = cfg_spotify_creds(&self.cfg) {
+            let spotify = spotify_client(&client_id, &client_secret).await?;
+            return match link {
+                SpotifyLink::Track(id) => {
+                    let track = fetch_track(&spotify, &id).await?;
+                    Ok(LoadedItem::Track(track_meta(&track).unwrap_or_else(|| TrackMeta {
+                        identifier: id,
+                        title: track.name.clone(),
+                        ..TrackMeta::untitled(input, self.name())
+                    })))
+                }
+                SpotifyLink::Album(id) => load_album(&spotify, &id).await,
+                SpotifyLink::Playlist(id) => load_playlist(&spotify, &id).await,
+            };
+        }
+        let SpotifyLink::Track(track_id) = link else { anyhow::bail!(MISSING_CREDENTIALS) };
+        if self.cfg.allow_spotify_title_search {
+            let title = fetch_spotify_oembed_title(input).await?;
+            return Ok(LoadedItem::Track(TrackMeta {
+                identifier: track_id,
+                title,
+                ..TrackMeta::untitled(input, self.name())
+            }));
+        }
+        anyhow::bail!(MISSING_CREDENTIALS)
+    }
+
+    async fn resolve_link(&self, input: &str) -> Result<String> {
+        let cfg = &self.cfg;
+        if cfg_spotify_creds(cfg).is_none() {
+            anyhow::bail!(MISSING_CREDENTIALS);
+        }
+
+        if let Some(track_id) = parse_spotify_track_id(input) {
+            if let Some((client_id, client_secret)) = cfg_spotify_creds(cfg) {
+                if let Ok(track) = fetch_spotify_track(&client_id, &client_secret, &track_id).await {
+                    let title = track.name.clone();
+                    let artists = track.artists.iter().map(|a| a.name.clone()).collect::<Vec<_>>();
+                    let mut query = title.clone();
+                    if !artists.is_empty() {
+                        query = format!("{} - {}", artists.join(", "), title);
+                    }
+                    if let Ok(url) = resolve_youtube_search(cfg, &query).await {
+                        return Ok(url);
+                    }
+                }
+            }
+        }
+
+        if cfg.allow_spotify_title_search {
+            if let Ok(title) = fetch_spotify_oembed_title(input).await {
+                if let Ok(url) = resolve_youtube_search(cfg, &title).await {
+                    return Ok(url);
+                }
+            }
+        }
+
+        anyhow::bail!("Failed to resolve Spotify URL")
+    }
+}
+
+impl SourceManager for SpotifySource {
+    fn name(&self) -> &'static str {
+        "spotify"
+    }
+
+    fn matches(&self, identifier: &str) -> bool {
+        identifier.starts_with("spotify:") || host(identifier).is_some_and(|h| h.contains("spotify.com"))
+    }
+
+    fn load_item<'a>(&'a self, identifier: &'a str) -> BoxFuture<'a, Result<LoadedItem>> {
+        Box::pin(self.load(identifier))
+    }
+
+    fn resolve<'a>(&'a self, identifier: &'a str) -> BoxFuture<'a, Result<String>> {
+        Box::pin(self.resolve_link(identifier))
+    }
+}
+
+const MISSING_CREDENTIALS: &str = "Spotify URL provided but credentials are missing. Provide SPOTIFY_CLIENT_ID and SPOTIFY_CLIENT_SECRET or configure them in Resonix.toml.";
+
+/// Albums and playlists are cut off after this many tracks.
+const MAX_COLLECTION_TRACKS: usize = 500;
+
+/// A Spotify track as metadata; `None` for local files, which have no id to play.
+fn track_meta(track: &FullTrack) -> Option<TrackMeta> {
+    let id = track.id.as_ref()?.id();
+    let artwork = track.album.images.first().map(|i| i.url.clone());
+    let mut meta = simplified_meta(
+        id,
+        &track.name,
+        &track.artists,
+        track.duration.num_milliseconds().max(0) as u64,
+        artwork,
+        &track.album.name,
+    );
+    meta.isrc = track.external_ids.get("isrc").cloned();
+    Some(meta)
+}
+
+fn simplified_meta(
+    id: &str,
+    name: &str,
+    artists: &[rspotify::model::SimplifiedArtist],
+    length_ms: u64,
+    artwork_url: Option<String>,
+    album: &str,
+) -> TrackMeta {
+    TrackMeta {
+        identifier: id.to_string(),
+        uri: format!("https://open.spotify.com/track/{id}"),
+        title: name.to_string(),
+        author: artists.iter().map(|a| a.name.as_str()).collect::<Vec<_>>().join(", "),
+        length_ms,
+        is_stream: false,
+        artwork_url,
+        isrc: None,
+        source_name: "spotify",
+        plugin_info: serde_json::json!({ "album": album }),
+    }
+}
+
+async fn load_album(spotify: &ClientCredsSpotify, id: &str) -> Result<LoadedItem> {
+    let album_id = AlbumId::from_id(id).map_err(|e| anyhow!("invalid spotify album id: {e}"))?;
+    let album =
+        spotify.album(album_id.clone(), None).await.map_err(|e| anyhow!("spotify album fetch: {e}"))?;
+    let tracks: Vec<SimplifiedTrack> = spotify
+        .album_track(album_id, None)
+        .take(MAX_COLLECTION_TRACKS)
+        .try_collect()
+        .await
+        .map_err(|e| anyhow!("spotify album tracks fetch: {e}"))?;
+    let artwork = album.images.first().map(|i| i.url.clone());
+    let tracks = tracks
+        .iter()
+        .filter_map(|t| {
+            let id = t.id.as_ref()?.id();
+            Some(simplified_meta(
+                id,
+                &t.name,
+                &t.artists,
+                t.duration.num_milliseconds().max(0) as u64,
+                artwork.clone(),
+                &album.name,
+            ))
+        })
+        .collect();
+    Ok(LoadedItem::Playlist(PlaylistMeta { name: album.name, selected_track: None, tracks }))
+}
+
+async fn load_playlist(spotify: &ClientCredsSpotify, id: &str) -> Result<LoadedItem> {
+    let playlist_id = PlaylistId::from_id(id).map_err(|e| anyhow!("invalid spotify playlist id: {e}"))?;
+    let playlist = spotify
+        .playlist(playlist_id.clone(), Some("name"), None)
+        .await
+        .map_err(|e| anyhow!("spotify playlist fetch: {e}"))?;
+    let items: Vec<_> = spotify
+        .playlist_items(playlist_id, None, None)
+        .take(MAX_COLLECTION_TRACKS)
+        .try_collect()
+        .await
+        .map_err(|e| anyhow!("spotify playlist items fetch: {e}"))?;
+    // Podcast episodes are skipped; they cannot be found on YouTube by title.
+    let tracks = items
+        .iter()
+        .filter_map(|item| match &item.track {
+            Some(PlayableItem::Track(track)) => track_meta(track),
+            _ => None,
+        })
+        .collect();
+    Ok(LoadedItem::Playlist(PlaylistMeta { name: playlist.name, selected_track: None, tracks }))
+}
+
+fn cfg_spotify_creds(cfg: &EffectiveConfig) -> Option<(String, String)> {
+    match (&cfg.spotify_client_id, &cfg.spotify_client_secret) {
+        (Some(id), Some(sec)) if !id.is_empty() && !sec.is_empty() => Some((id.clone(), sec.clone())),
+        _ => None,
+    }
+}
+
+enum SpotifyLink {
+    Track(String),
+    Album(String),
+    Playlist(String),
+}
+
+/// `open.spotify.com/{track,album,playlist}/<id>` links (locale prefixes allowed) and
+/// `spotify:{track,album,playlist}:<id>` URIs.
+fn parse_spotify_link(input: &str) -> Option<SpotifyLink> {
+    let link = |kind: &str, id: &str| match kind {
+        _ if id.is_empty() => None,
+        "track" => Some(SpotifyLink::Track(id.to_string())),
+        "album" => Some(SpotifyLink::Album(id.to_string())),
+        "playlist" => Some(SpotifyLink::Playlist(id.to_string())),
+        _ => None,
+    };
+    if let Some(rest) = input.strip_prefix("spotify:") {
+        let (kind, id) = rest.split_once(':')?;
+        return link(kind, id);
+    }
+    let u = Url::parse(input).ok()?;
+    if !u.host_str()?.contains("spotify.com") {
+        return None;
+    }
+    let segments: Vec<&str> = u.path_segments()?.collect();
+    segments.windows(2).find_map(|w| link(w[0], w[1]))
+}
+
+fn parse_spotify_track_id(input: &str) -> Option<String> {
+    match parse_spotify_link(input)? {
+        SpotifyLink::Track(id) => Some(id),
+        _ => None,
+    }
+}
+
+#[derive(Deserialize)]
+struct SpotifyOEmbed {
+    title: String,
+}
+
+async fn fetch_spotify_oembed_title(url: &str) -> Result<String> {
+    let client = reqwest::Client::new();
+    let resp = client
+        .get("https://open.spotify.com/oembed")
+        .query(&[("url", url)])
+        .send()
+        .await
+        .context("spotify oembed get")?
+        .error_for_status()
+        .context("spotify oembed bad status")?;
+    let bytes = resp.bytes().await.context("spotify oembed read body")?;
+    let v: SpotifyOEmbed = serde_json::from_slice(&bytes).context("spotify oembed parse json")?;
+    Ok(v.title)
+}
+
+async fn spotify_client(client_id: &str, client_secret: &str) -> Result<ClientCredsSpotify> {
+    let creds = Credentials { id: client_id.to_string(), secret: Some(client_secret.to_string()) };
+    let spotify = ClientCredsSpotify::new(creds);
+    spotify.request_token().await.map_err(|e| anyhow!("spotify auth: {e}"))?;
+    Ok(spotify)
+}
+
+async fn fetch_track(spotify: &ClientCredsSpotify, track_id_b62: &str) -> Result<FullTrack> {
+    let tid = TrackId::from_id(track_id_b62).map_err(|e| anyhow!("invalid spotify track id: {e}"))?;
+    spotify.track(tid, None).await.map_err(|e| anyhow!("spotify track fetch: {e}"))
+}
+
+async fn fetch_spotify_track(client_id: &str, client_secret: &str, track_id_b62: &str) -> Result<FullTrack> {
+    fetch_track(&spotify_client(client_id, client_secret).await?, track_id_b62).await
+}
@@ -0,0 +1,348 @@
+use anyhow::{anyhow, bail, Context, Result};
+use futures::future::BoxFuture;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use reqwest::Client;
+use riva::YoutubeClientType;
+use serde_json::Value;
+use std::{collections::HashMap, sync::Arc, sync::Mutex};
+use url::{form_urlencoded, Url};
+
+use super::manager::{search_query, LoadedItem, PlaylistMeta, SourceManager, TrackMeta};
+use super::{host, resolver_timeout, riva_client, write_bytes_to_temp_file};
+use crate::config::EffectiveConfig;
+
+const YT_SEARCH_UA: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/130.0.0.0 Safari/537.36 Resonix/3.5";
+const YOUTUBE_FALLBACK_ITAG: u32 = 18;
+const SEARCH_PREFIXES: &[&str] = &["ytsearch:"];
+
+/// Video descriptions of resolved YouTube downloads, keyed by temp file path,
+/// so chapters can be read from their timestamps.
+static DESCRIPTIONS: Lazy<Mutex<HashMap<String, String>>> = Lazy::new(Default::default);
+
+static YT_VIDEO_ID_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"\"videoId\":\"([A-Za-z0-9_-]{11})\""#).expect("valid video id regex"));
+static YT_INITIAL_DATA_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?:var ytInitialData|window\[.ytInitialData.\])\s*=\s*").expect("valid regex"));
+
+/// YouTube videos and playlists (`youtube.com`, `youtu.be`) and `ytsearch:` queries, downloaded through Riva.
+pub struct YoutubeSource {
+    cfg: Arc<EffectiveConfig>,
+}
+
+impl YoutubeSource {
+    pub fn new(cfg: Arc<EffectiveConfig>) -> Self {
+        Self { cfg }
+    }
+
+    async fn load(&self, identifier: &str) -> Result<LoadedItem> {
+        if let Some(query) = search_query(identifier, SEARCH_PREFIXES) {
+            return Ok(LoadedItem::Search(search_videos(&self.cfg, query).await?));
+        }
+        let video_id = parse_youtube_video_id(identifier);
+        if let Some(list_id) = parse_youtube_playlist_id(identifier) {
+            match load_playlist(&self.cfg, &list_id, video_id.as_deref()).await {
+                Ok(playlist) if !playlist.tracks.is_empty() => return Ok(LoadedItem::Playlist(playlist)),
+                // Mixes and private lists have no playlist page; play the video alone.
+                Ok(_) | Err(_) if video_id.is_some() => {}
+                Ok(_) => bail!("youtube playlist is empty or private"),
+                Err(e) => return Err(e),
+            }
+        }
+        let video_id = video_id.ok_or_else(|| anyhow!("invalid youtube url"))?;
+        let info = riva_client()?
+            .youtube_info(&video_id, Some(YoutubeClientType::Android))
+            .await
+            .map_err(|e| anyhow!("youtube info failed: {e}"))?;
+        Ok(LoadedItem::Track(track_from_info(&video_id, &info)))
+    }
+
+    async fn resolve_identifier(&self, identifier: &str) -> Result<String> {
+        match search_query(identifier, SEARCH_PREFIXES) {
+            Some(query) => resolve_youtube_search(&self.cfg, query).await,
+            None => resolve_youtube_url(&self.cfg, identifier).await,
+        }
+    }
+}
+
+impl SourceManager for YoutubeSource {
+    fn name(&self) -> &'static str {
+        "youtube"
+    }
+
+    fn search_prefixes(&self) -> &'static [&'static str] {
+        SEARCH_PREFIXES
+    }
+
+    fn matches(&self, identifier: &str) -> bool {
+        host(identifier).is_some_and(|h| h.contains("youtube.com") || h == "youtu.be")
+    }
+
+    fn load_item<'a>(&'a self, identifier: &'a str) -> BoxFuture<'a, Result<LoadedItem>> {
+        Box::pin(self.load(identifier))
+    }
+
+    fn resolve<'a>(&'a self, identifier: &'a str) -> BoxFuture<'a, Result<String>> {
+        Box::pin(self.resolve_identifier(identifier))
+    }
+}
+
+/// The description of a YouTube video downloaded to `path` by the resolver.
+pub fn description_of(path: &str) -> Option<String> {
+    DESCRIPTIONS.lock().ok()?.get(path).cloned()
+}
+
+/// Drops what was remembered about a resolver download once it is deleted.
+pub fn forget_download(path: &str) {
+    if let Ok(mut d) = DESCRIPTIONS.lock() {
+        d.remove(path);
+    }
+}
+
+async fn resolve_youtube_url(_cfg: &EffectiveConfig, url: &str) -> Result<String> {
+    let video_id = parse_youtube_video_id(url).ok_or_else(|| anyhow!("invalid youtube url"))?;
+    tracing::info!(video_id, "Resolved YouTube video ID");
+    let client = riva_client()?;
+    let (response, info) = tokio::join!(
+        client.youtube_stream(&video_id, YOUTUBE_FALLBACK_ITAG, Some(YoutubeClientType::Android)),
+        client.youtube_info(&video_id, Some(YoutubeClientType::Android)),
+    );
+    let response = response.map_err(|e| anyhow!("youtube stream failed: {e}"))?;
+
+    let body = response.bytes().await.context("youtube stream read body failed")?;
+    let path = write_bytes_to_temp_file(&body)?;
+    match info {
+        Ok(info) => {
+            if let Some(description) = find_description(&info) {
+                if let Ok(mut d) = DESCRIPTIONS.lock() {
+                    d.insert(path.clone(), description.to_string());
+                }
+            }
+        }
+        Err(e) => tracing::debug!(video_id, error = %e, "youtube info unavailable"),
+    }
+    Ok(path)
+}
+
+/// Downloads the first search result for `query`.
+pub(super) async fn resolve_youtube_search(cfg: &EffectiveConfig, query: &str) -> Result<String> {
+    let video_id = search_youtube_video_id(cfg, query).await?;
+    let url = format!("https://www.youtube.com/watch?v={video_id}");
+    resolve_youtube_url(cfg, &url).await
+}
+
+async fn search_youtube_video_id(cfg: &EffectiveConfig, query: &str) -> Result<String> {
+    let body = fetch_search_page(cfg, query).await?;
+    let caps = YT_VIDEO_ID_REGEX
+        .captures(&body)
+        .ok_or_else(|| anyhow!("youtube search did not return any video ids"))?;
+    Ok(caps.get(1).map(|m| m.as_str()).unwrap_or_default().to_string())
+}
+
+async fn fetch_search_page(cfg: &EffectiveConfig, query: &str) -> Result<String> {
+    let encoded: String = form_urlencoded::byte_serialize(query.as_bytes()).collect();
+    fetch_page(cfg, &format!("https://www.youtube.com/results?search_query={encoded}")).await
+}
+
+async fn fetch_page(cfg: &EffectiveConfig, url: &str) -> Result<String> {
+    youtube_search_client(cfg)?
+        .get(url)
+        .send()
+        .await
+        .context("youtube request failed")?
+        .error_for_status()
+        .context("youtube returned error status")?
+        .text()
+        .await
+        .context("youtube body read failed")
+}
+
+/// The videos listed on a playlist's page (the first hundred), with `selected` as the
+/// selected track when it is among them.
+async fn load_playlist(cfg: &EffectiveConfig, list_id: &str, selected: Option<&str>) -> Result<PlaylistMeta> {
+    let encoded: String = form_urlencoded::byte_serialize(list_id.as_bytes()).collect();
+    let body = fetch_page(cfg, &format!("https://www.youtube.com/playlist?list={encoded}")).await?;
+    let data = initial_data(&body).ok_or_else(|| anyhow!("youtube playlist page could not be parsed"))?;
+    let name = [
+        "/metadata/playlistMetadataRenderer/title",
+        "/header/playlistHeaderRenderer/title/simpleText",
+        "/microformat/microformatDataRenderer/title",
+    ]
+    .iter()
+    .find_map(|p| data.pointer(p).and_then(Value::as_str))
+    .unwrap_or("YouTube playlist")
+    .to_string();
+    let mut renderers = Vec::new();
+    collect_objects(&data, "playlistVideoRenderer", &mut renderers);
+    let tracks: Vec<TrackMeta> = renderers
+        .into_iter()
+        // Deleted and private videos stay listed but cannot be played.
+        .filter(|r| r.get("isPlayable").and_then(Value::as_bool) != Some(false))
+        .filter_map(track_from_renderer)
+        .collect();
+    let selected_track = selected.and_then(|id| tracks.iter().position(|t| t.identifier == id));
+    Ok(PlaylistMeta { name, selected_track, tracks })
+}
+
+/// Search results with their titles, channels and lengths, read from the page's
+/// `ytInitialData`. Falls back to bare video ids if that cannot be parsed.
+async fn search_videos(cfg: &EffectiveConfig, query: &str) -> Result<Vec<TrackMeta>> {
+    let body = fetch_search_page(cfg, query).await?;
+    if let Some(data) = initial_data(&body) {
+        let mut renderers = Vec::new();
+        collect_objects(&data, "videoRenderer", &mut renderers);
+        let tracks: Vec<TrackMeta> = renderers.into_iter().filter_map(track_from_renderer).collect();
+        if !tracks.is_empty() {
+            return Ok(tracks);
+        }
+    }
+    let mut seen = Vec::new();
+    for caps in YT_VIDEO_ID_REGEX.captures_iter(&body) {
+        let id = caps[1].to_string();
+        if !seen.contains(&id) {
+            seen.push(id);
+        }
+    }
+    Ok(seen.iter().map(|id| video_track(id, id.clone(), String::new(), 0, false)).collect())
+}
+
+fn initial_data(body: &str) -> Option<Value> {
+    let start = YT_INITIAL_DATA_REGEX.find(body)?.end();
+    let mut de = serde_json::Deserializer::from_str(&body[start..]).into_iter::<Value>();
+    de.next()?.ok()
+}
+
+fn collect_objects<'a>(value: &'a Value, key: &str, out: &mut Vec<&'a Value>) {
+    match value {
+        Value::Object(map) => {
+            for (k, v) in map {
+                if k == key {
+                    out.push(v);
+                } else {
+                    collect_objects(v, key, out);
+                }
+            }
+        }
+        Value::Array(items) => items.iter().for_each(|v| collect_objects(v, key, out)),
+        _ => {}
+    }
+}
+
+fn track_from_renderer(r: &Value) -> Option<TrackMeta> {
+    let id = r.get("videoId")?.as_str()?;
+    let text = |v: Option<&Value>| -> Option<String> {
+        let v = v?;
+        v.get("simpleText")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .or_else(|| v.pointer("/runs/0/text").and_then(Value::as_str).map(str::to_string))
+    };
+    let title = text(r.get("title"))?;
+    let author = ["ownerText", "longBylineText", "shortBylineText"]
+        .iter()
+        .find_map(|k| text(r.get(*k)))
+        .unwrap_or_default();
+    let length = text(r.get("lengthText")).and_then(|l| parse_clock(&l));
+    Some(video_track(id, title, author, length.unwrap_or(0), length.is_none()))
+}
+
+fn track_from_info(video_id: &str, info: &Value) -> TrackMeta {
+    let field = |ptrs: &[&str]| ptrs.iter().find_map(|p| info.pointer(p)).cloned();
+    let as_string = |v: Option<Value>| match v {
+        Some(Value::String(s)) => Some(s),
+        Some(Value::Number(n)) => Some(n.to_string()),
+        _ => None,
+    };
+    let title = as_string(field(&["/videoDetails/title", "/title"])).unwrap_or_else(|| video_id.to_string());
+    let author = as_string(field(&["/videoDetails/author", "/author", "/uploader"])).unwrap_or_default();
+    let length_secs: u64 = as_string(field(&["/videoDetails/lengthSeconds", "/lengthSeconds", "/duration"]))
+        .and_then(|s| s.parse::<f64>().ok())
+        .map_or(0, |s| s as u64);
+    let live = field(&["/videoDetails/isLive", "/videoDetails/isLiveContent", "/isLive"])
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    video_track(video_id, title, author, length_secs * 1000, live)
+}
+
+fn video_track(id: &str, title: String, author: String, length_ms: u64, is_stream: bool) -> TrackMeta {
+    TrackMeta {
+        identifier: id.to_string(),
+        uri: format!("https://www.youtube.com/watch?v={id}"),
+        title,
+        author,
+        length_ms,
+        is_stream,
+        artwork_url: Some(format!("https://i.ytimg.com/vi/{id}/hqdefault.jpg")),
+        isrc: None,
+        source_name: "youtube",
+        plugin_info: serde_json::json!({}),
+    }
+}
+
+/// `h:mm:ss` or `m:ss` to milliseconds.
+fn parse_clock(text: &str) -> Option<u64> {
+    text.trim()
+        .split(':')
+        .try_fold(0u64, |acc, part| part.parse::<u64>().ok().map(|n| acc * 60 + n))
+        .map(|secs| secs * 1000)
+}
+
+fn find_description(info: &Value) -> Option<&str> {
+    [
+        "/videoDetails/shortDescription",
+        "/shortDescription",
+        "/description",
+        "/microformat/playerMicroformatRenderer/description/simpleText",
+    ]
+    .iter()
+    .find_map(|ptr| info.pointer(ptr).and_then(|v| v.as_str()))
+    .filter(|d| !d.trim().is_empty())
+}
+
+fn parse_youtube_video_id(url: &str) -> Option<String> {
+    let parsed = Url::parse(url).ok()?;
+    let host = parsed.host_str()?.to_lowercase();
+
+    if host == "youtu.be" {
+        return parsed.path_segments()?.find(|s| !s.is_empty()).map(|s| s.to_string());
+    }
+
+    if host.contains("youtube.com") {
+        for (k, v) in parsed.query_pairs() {
+            if k == "v" && !v.is_empty() {
+                return Some(v.into_owned());
+            }
+        }
+
+        let mut segments = parsed.path_segments()?;
+        if let Some(first) = segments.next() {
+            if first == "embed" || first == "shorts" || first == "live" {
+                if let Some(id) = segments.next() {
+                    if !id.is_empty() {
+                        return Some(id.to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// The `list=` id of a playlist link or of a video opened from a playlist.
+fn parse_youtube_playlist_id(url: &str) -> Option<String> {
+    let parsed = Url::parse(url).ok()?;
+    let host = parsed.host_str()?.to_lowercase();
+    if host != "youtu.be" && !host.contains("youtube.com") {
+        return None;
+    }
+    parsed.query_pairs().find(|(k, v)| k == "list" && !v.is_empty()).map(|(_, v)| v.into_owned())
+}
+
+fn youtube_search_client(cfg: &EffectiveConfig) -> Result<Client> {
+    Client::builder()
+        .user_agent(YT_SEARCH_UA)
+        .timeout(resolver_timeout(cfg))
+        .build()
+        .context("build youtube search client")
+}
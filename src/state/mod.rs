@@ -1,18 +1,31 @@
 use dashmap::DashMap;
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use crate::audio::player::Player;
+use crate::audio::recorder::RecordingStore;
 use crate::config::{load_config, EffectiveConfig};
+use crate::library::Library;
+use crate::resolver::SourceRegistry;
 
 #[derive(Clone)]
 pub struct AppState {
     pub players: Arc<DashMap<String, Arc<Player>>>,
     pub cfg: Arc<EffectiveConfig>,
+    pub recordings: RecordingStore,
+    pub library: Arc<Library>,
+    pub sources: Arc<SourceRegistry>,
 }
 
 impl AppState {
     pub fn new(cfg: EffectiveConfig) -> Self {
-        Self { players: Arc::new(DashMap::new()), cfg: Arc::new(cfg) }
+        let recordings = RecordingStore::new(
+            cfg.recording_dir.clone().into(),
+            Duration::from_secs(cfg.recording_max_duration_secs),
+        );
+        let library = Arc::new(Library::from_config(&cfg));
+        let cfg = Arc::new(cfg);
+        let sources = Arc::new(SourceRegistry::new(&cfg, SourceRegistry::builtin(&cfg, &library)));
+        Self { players: Arc::new(DashMap::new()), cfg, recordings, library, sources }
     }
 }
 
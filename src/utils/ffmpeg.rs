@@ -2,7 +2,6 @@ use anyhow::{anyhow, bail, Context, Result};
 use reqwest::Client;
 use serde::Deserialize;
 use std::{
-    ffi::OsString,
     fs::File,
     io::{self, Read},
     path::{Path, PathBuf},
@@ -183,7 +182,7 @@ fn home_dir() -> Result<PathBuf> {
         let drive = std::env::var_os("HOMEDRIVE");
         let path = std::env::var_os("HOMEPATH");
         if let (Some(drive), Some(path)) = (drive, path) {
-            let mut combined = OsString::from(drive);
+            let mut combined = drive;
             combined.push(path);
             return Ok(PathBuf::from(combined));
         }
@@ -192,7 +191,7 @@ fn home_dir() -> Result<PathBuf> {
     Err(anyhow!("Unable to determine the current user's home directory"))
 }
 
-fn select_asset<'a>(assets: &'a [GithubAsset], spec: PlatformSpec) -> Option<GithubAsset> {
+fn select_asset(assets: &[GithubAsset], spec: PlatformSpec) -> Option<GithubAsset> {
     assets.iter().find(|asset| match_asset(asset, spec)).cloned()
 }
 
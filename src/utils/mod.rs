@@ -1,2 +1,4 @@
 pub mod ffmpeg;
 pub mod stdu;
+pub mod task;
+pub mod xml;
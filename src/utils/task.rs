@@ -0,0 +1,11 @@
+use tokio::task::JoinHandle;
+
+/// Owns a spawned task and aborts it when dropped, tying the task's lifetime to
+/// whatever holds this value.
+pub struct AbortOnDrop(pub JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
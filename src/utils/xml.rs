@@ -0,0 +1,66 @@
+use anyhow::{anyhow, bail, Context, Result};
+use quick_xml::events::{BytesStart, Event};
+
+/// Just enough of an XML element tree to walk manifests and playlists.
+pub struct XmlNode {
+    pub name: String,
+    pub attrs: Vec<(String, String)>,
+    pub children: Vec<XmlNode>,
+    pub text: String,
+}
+
+impl XmlNode {
+    pub fn parse(xml: &str) -> Result<XmlNode> {
+        let mut reader = quick_xml::Reader::from_str(xml);
+        let mut stack: Vec<XmlNode> = Vec::new();
+        loop {
+            match reader.read_event().context("parse xml")? {
+                Event::Start(e) => stack.push(XmlNode::from_start(&e)?),
+                Event::Empty(e) => {
+                    let node = XmlNode::from_start(&e)?;
+                    match stack.last_mut() {
+                        Some(parent) => parent.children.push(node),
+                        None => return Ok(node),
+                    }
+                }
+                Event::Text(t) => {
+                    if let Some(node) = stack.last_mut() {
+                        node.text.push_str(&t.unescape().context("parse xml text")?);
+                    }
+                }
+                Event::End(_) => {
+                    let node = stack.pop().ok_or_else(|| anyhow!("unbalanced xml"))?;
+                    match stack.last_mut() {
+                        Some(parent) => parent.children.push(node),
+                        None => return Ok(node),
+                    }
+                }
+                Event::Eof => bail!("truncated xml"),
+                _ => {}
+            }
+        }
+    }
+
+    fn from_start(e: &BytesStart) -> Result<XmlNode> {
+        let name = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+        let mut attrs = Vec::new();
+        for a in e.attributes() {
+            let a = a.context("parse xml attribute")?;
+            let key = String::from_utf8_lossy(a.key.local_name().as_ref()).into_owned();
+            attrs.push((key, a.unescape_value()?.into_owned()));
+        }
+        Ok(XmlNode { name, attrs, children: Vec::new(), text: String::new() })
+    }
+
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+    }
+
+    pub fn child(&self, name: &str) -> Option<&XmlNode> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    pub fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a XmlNode> + 'a {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+}
@@ -0,0 +1,237 @@
+use anyhow::{anyhow, Context, Result};
+use futures::{SinkExt, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::net::UdpSocket;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tracing::{debug, info, warn};
+use url::Url;
+
+use crate::audio::player::PlayerEvent;
+use crate::utils::task::AbortOnDrop;
+use crate::voice::{
+    crypto::{EncryptionMode, PacketCipher},
+    udp, Connection,
+};
+
+const GATEWAY_VERSION: &str = "8";
+const MAX_RECONNECT_ATTEMPTS: u32 = 5;
+
+const OP_IDENTIFY: u8 = 0;
+const OP_SELECT_PROTOCOL: u8 = 1;
+const OP_READY: u8 = 2;
+const OP_HEARTBEAT: u8 = 3;
+const OP_SESSION_DESCRIPTION: u8 = 4;
+const OP_SPEAKING: u8 = 5;
+const OP_HEARTBEAT_ACK: u8 = 6;
+const OP_RESUME: u8 = 7;
+const OP_HELLO: u8 = 8;
+const OP_RESUMED: u8 = 9;
+
+/// Close codes after which neither resuming nor re-identifying can succeed; the
+/// client has to send fresh voice server/state updates.
+const FATAL_CLOSE_CODES: [u16; 6] = [4004, 4006, 4011, 4014, 4016, 4022];
+/// Close codes after which the session cannot be resumed but a new identify may work.
+const REIDENTIFY_CLOSE_CODES: [u16; 2] = [4009, 4021];
+
+#[derive(Deserialize)]
+struct GatewayPayload {
+    op: u8,
+    #[serde(default)]
+    d: Value,
+    #[serde(default)]
+    seq: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct Hello {
+    heartbeat_interval: f64,
+}
+
+#[derive(Deserialize)]
+struct Ready {
+    ssrc: u32,
+    ip: String,
+    port: u16,
+    modes: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct SessionDescription {
+    mode: String,
+    secret_key: Vec<u8>,
+}
+
+struct SessionEnd {
+    code: Option<u16>,
+    reason: String,
+    established: bool,
+}
+
+struct SessionState {
+    /// The running RTP sender; kept across resumes since the UDP session survives them.
+    sender: Option<AbortOnDrop>,
+    seq_ack: i64,
+}
+
+pub(super) async fn run(conn: Connection) {
+    let mut state = SessionState { sender: None, seq_ack: -1 };
+    let mut attempts = 0u32;
+    loop {
+        let end = match session(&conn, &mut state).await {
+            Ok(end) => end,
+            Err(e) => SessionEnd { code: None, reason: format!("{e:#}"), established: false },
+        };
+        conn.set_status(|s| s.connected = false);
+        if end.established {
+            attempts = 0;
+        }
+
+        let fatal = end.code.is_some_and(|c| FATAL_CLOSE_CODES.contains(&c));
+        if fatal || attempts >= MAX_RECONNECT_ATTEMPTS {
+            warn!(player=%conn.player_id, code=?end.code, reason=%end.reason, "voice connection closed");
+            let _ = conn.events.send(PlayerEvent::VoiceDisconnected {
+                id: conn.player_id.clone(),
+                guild_id: conn.info.guild_id.clone(),
+                code: end.code,
+                reason: end.reason,
+            });
+            return;
+        }
+        if end.code.is_some_and(|c| REIDENTIFY_CLOSE_CODES.contains(&c)) {
+            state.sender = None;
+        }
+        attempts += 1;
+        debug!(player=%conn.player_id, code=?end.code, reason=%end.reason, attempts, "voice gateway reconnecting");
+        tokio::time::sleep(Duration::from_millis(500 * 2u64.pow(attempts.min(5)))).await;
+    }
+}
+
+async fn session(conn: &Connection, state: &mut SessionState) -> Result<SessionEnd> {
+    let url = gateway_url(&conn.info.endpoint)?;
+    let (mut ws, _) = connect_async(url.as_str()).await.context("connect voice gateway")?;
+    let resuming = state.sender.is_some();
+    let hello = if resuming {
+        json!({ "op": OP_RESUME, "d": {
+            "server_id": conn.info.guild_id,
+            "session_id": conn.info.session_id,
+            "token": conn.info.token,
+            "seq_ack": state.seq_ack,
+        }})
+    } else {
+        json!({ "op": OP_IDENTIFY, "d": {
+            "server_id": conn.info.guild_id,
+            "user_id": conn.info.user_id,
+            "session_id": conn.info.session_id,
+            "token": conn.info.token,
+        }})
+    };
+    ws.send(Message::Text(hello.to_string().into())).await?;
+
+    let mut heartbeat: Option<tokio::time::Interval> = None;
+    let mut last_heartbeat: Option<Instant> = None;
+    let mut pending_udp: Option<(UdpSocket, u32)> = None;
+    let mut established = false;
+    loop {
+        let beat = async {
+            match heartbeat.as_mut() {
+                Some(i) => {
+                    i.tick().await;
+                }
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            _ = beat => {
+                let nonce = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+                let hb = json!({ "op": OP_HEARTBEAT, "d": { "t": nonce, "seq_ack": state.seq_ack } });
+                ws.send(Message::Text(hb.to_string().into())).await?;
+                last_heartbeat = Some(Instant::now());
+            }
+            msg = ws.next() => {
+                let text = match msg {
+                    None => return Ok(SessionEnd { code: None, reason: "gateway stream ended".into(), established }),
+                    Some(Err(e)) => return Ok(SessionEnd { code: None, reason: e.to_string(), established }),
+                    Some(Ok(Message::Close(frame))) => {
+                        let (code, reason) = frame
+                            .map(|f| (Some(u16::from(f.code)), f.reason.to_string()))
+                            .unwrap_or((None, "closed without frame".into()));
+                        return Ok(SessionEnd { code, reason, established });
+                    }
+                    Some(Ok(Message::Text(t))) => t,
+                    // Binary opcodes belong to the E2EE protocol, which is not negotiated.
+                    Some(Ok(_)) => continue,
+                };
+                let payload: GatewayPayload = serde_json::from_str(&text).context("parse voice gateway payload")?;
+                if let Some(seq) = payload.seq {
+                    state.seq_ack = seq;
+                }
+                match payload.op {
+                    OP_HELLO => {
+                        let h: Hello = serde_json::from_value(payload.d)?;
+                        heartbeat = Some(tokio::time::interval(Duration::from_millis(h.heartbeat_interval as u64)));
+                    }
+                    OP_READY => {
+                        let r: Ready = serde_json::from_value(payload.d)?;
+                        let ip: IpAddr = r.ip.parse().context("voice server ip")?;
+                        let socket = udp::connect(SocketAddr::new(ip, r.port)).await?;
+                        let (ext_ip, ext_port) = udp::discover_ip(&socket, r.ssrc).await?;
+                        let mode = EncryptionMode::negotiate(&r.modes)
+                            .ok_or_else(|| anyhow!("no supported voice encryption mode in {:?}", r.modes))?;
+                        let select = json!({ "op": OP_SELECT_PROTOCOL, "d": {
+                            "protocol": "udp",
+                            "data": { "address": ext_ip, "port": ext_port, "mode": mode.name() },
+                        }});
+                        ws.send(Message::Text(select.to_string().into())).await?;
+                        pending_udp = Some((socket, r.ssrc));
+                    }
+                    OP_SESSION_DESCRIPTION => {
+                        let sd: SessionDescription = serde_json::from_value(payload.d)?;
+                        let (socket, ssrc) = pending_udp.take().ok_or_else(|| anyhow!("session description before ready"))?;
+                        let mode = EncryptionMode::from_name(&sd.mode)
+                            .ok_or_else(|| anyhow!("server selected unsupported mode {}", sd.mode))?;
+                        let cipher = PacketCipher::new(mode, &sd.secret_key)?;
+                        let speaking = json!({ "op": OP_SPEAKING, "d": { "speaking": 1, "delay": 0, "ssrc": ssrc } });
+                        ws.send(Message::Text(speaking.to_string().into())).await?;
+                        let rx = conn.opus_rx.resubscribe();
+                        state.sender = Some(AbortOnDrop(tokio::spawn(udp::send_loop(socket, cipher, ssrc, rx))));
+                        established = true;
+                        conn.set_status(|s| s.connected = true);
+                        info!(player=%conn.player_id, guild=%conn.info.guild_id, mode=mode.name(), "voice connected");
+                        let _ = conn.events.send(PlayerEvent::VoiceConnected {
+                            id: conn.player_id.clone(),
+                            guild_id: conn.info.guild_id.clone(),
+                        });
+                    }
+                    OP_RESUMED => {
+                        established = true;
+                        conn.set_status(|s| s.connected = true);
+                        debug!(player=%conn.player_id, "voice session resumed");
+                    }
+                    OP_HEARTBEAT_ACK => {
+                        if let Some(sent) = last_heartbeat {
+                            let ping = sent.elapsed().as_millis() as u64;
+                            conn.set_status(|s| s.ping_ms = Some(ping));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// Discord hands out bare `host:port` endpoints; a full `ws://` or `wss://` URL is
+/// also accepted so a local stand-in server can be used.
+fn gateway_url(endpoint: &str) -> Result<Url> {
+    let raw = if endpoint.contains("://") { endpoint.to_string() } else { format!("wss://{endpoint}") };
+    let mut url = Url::parse(&raw).with_context(|| format!("invalid voice endpoint '{endpoint}'"))?;
+    if !url.query_pairs().any(|(k, _)| k == "v") {
+        url.query_pairs_mut().append_pair("v", GATEWAY_VERSION);
+    }
+    Ok(url)
+}
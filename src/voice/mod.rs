@@ -0,0 +1,78 @@
+mod crypto;
+mod gateway;
+mod udp;
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+use crate::audio::player::PlayerEvent;
+use crate::utils::task::AbortOnDrop;
+
+/// Voice server and state details forwarded by the bot from its Discord gateway.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VoiceServerInfo {
+    pub guild_id: String,
+    pub user_id: String,
+    pub session_id: String,
+    pub token: String,
+    pub endpoint: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct VoiceStatus {
+    pub connected: bool,
+    #[serde(rename = "ping")]
+    pub ping_ms: Option<u64>,
+    #[serde(rename = "guildId")]
+    pub guild_id: String,
+    pub endpoint: String,
+}
+
+struct Connection {
+    player_id: String,
+    info: VoiceServerInfo,
+    opus_rx: broadcast::Receiver<Bytes>,
+    events: broadcast::Sender<PlayerEvent>,
+    status: Arc<std::sync::Mutex<VoiceStatus>>,
+}
+
+impl Connection {
+    fn set_status(&self, f: impl FnOnce(&mut VoiceStatus)) {
+        if let Ok(mut s) = self.status.lock() {
+            f(&mut s);
+        }
+    }
+}
+
+/// A live voice connection for one player. Dropping it closes the gateway and
+/// stops sending audio.
+pub struct VoiceHandle {
+    status: Arc<std::sync::Mutex<VoiceStatus>>,
+    _task: AbortOnDrop,
+}
+
+impl VoiceHandle {
+    pub fn status(&self) -> VoiceStatus {
+        self.status.lock().map(|s| s.clone()).unwrap_or_default()
+    }
+}
+
+/// Starts the voice gateway handshake in the background. `opus_rx` must carry the
+/// player's 20 ms Opus packets.
+pub fn connect(
+    player_id: &str,
+    info: VoiceServerInfo,
+    opus_rx: broadcast::Receiver<Bytes>,
+    events: broadcast::Sender<PlayerEvent>,
+) -> VoiceHandle {
+    let status = Arc::new(std::sync::Mutex::new(VoiceStatus {
+        guild_id: info.guild_id.clone(),
+        endpoint: info.endpoint.clone(),
+        ..Default::default()
+    }));
+    let conn = Connection { player_id: player_id.to_string(), info, opus_rx, events, status: status.clone() };
+    VoiceHandle { status, _task: AbortOnDrop(tokio::spawn(gateway::run(conn))) }
+}
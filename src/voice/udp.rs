@@ -0,0 +1,101 @@
+use anyhow::{anyhow, bail, Context, Result};
+use bytes::Bytes;
+use std::{collections::VecDeque, net::SocketAddr, time::Duration};
+use tokio::{net::UdpSocket, sync::broadcast};
+use tracing::{debug, warn};
+
+use crate::audio::rtp::RtpPacketizer;
+use crate::voice::crypto::PacketCipher;
+
+const DISCOVERY_LEN: usize = 74;
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(5);
+const OPUS_PAYLOAD_TYPE: u8 = 0x78;
+const OPUS_FRAME_SAMPLES: u32 = 960;
+/// Discord expects a few Opus silence frames when a speaker stops transmitting.
+const OPUS_SILENCE: [u8; 3] = [0xf8, 0xff, 0xfe];
+const SILENCE_FRAMES: u32 = 5;
+/// Packets waiting beyond this many frames (100 ms) are dropped to bound latency.
+const MAX_QUEUED_PACKETS: usize = 5;
+
+pub async fn connect(addr: SocketAddr) -> Result<UdpSocket> {
+    let bind: SocketAddr = if addr.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" }.parse()?;
+    let socket = UdpSocket::bind(bind).await.context("bind voice udp socket")?;
+    socket.connect(addr).await.context("connect voice udp socket")?;
+    Ok(socket)
+}
+
+/// Performs Discord's IP discovery and returns our external address and port as
+/// seen by the voice server.
+pub async fn discover_ip(socket: &UdpSocket, ssrc: u32) -> Result<(String, u16)> {
+    let mut req = [0u8; DISCOVERY_LEN];
+    req[0..2].copy_from_slice(&1u16.to_be_bytes());
+    req[2..4].copy_from_slice(&70u16.to_be_bytes());
+    req[4..8].copy_from_slice(&ssrc.to_be_bytes());
+    socket.send(&req).await.context("send ip discovery")?;
+
+    let mut resp = [0u8; DISCOVERY_LEN];
+    let n = tokio::time::timeout(DISCOVERY_TIMEOUT, socket.recv(&mut resp))
+        .await
+        .map_err(|_| anyhow!("ip discovery timed out"))?
+        .context("receive ip discovery")?;
+    if n < DISCOVERY_LEN || u16::from_be_bytes([resp[0], resp[1]]) != 2 {
+        bail!("malformed ip discovery response");
+    }
+    let addr_bytes = &resp[8..72];
+    let end = addr_bytes.iter().position(|&b| b == 0).unwrap_or(addr_bytes.len());
+    let ip = std::str::from_utf8(&addr_bytes[..end]).context("ip discovery address")?.to_string();
+    let port = u16::from_be_bytes([resp[72], resp[73]]);
+    Ok((ip, port))
+}
+
+/// Sends the player's Opus packets as encrypted RTP, one every 20 ms, until the
+/// player's encoder goes away.
+pub async fn send_loop(
+    socket: UdpSocket,
+    mut cipher: PacketCipher,
+    ssrc: u32,
+    mut rx: broadcast::Receiver<Bytes>,
+) {
+    let mut rtp = RtpPacketizer::new(OPUS_PAYLOAD_TYPE, ssrc);
+    let mut queue: VecDeque<Bytes> = VecDeque::new();
+    let mut silence_left = 0u32;
+    let mut tick = tokio::time::interval(Duration::from_millis(20));
+    tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tick.tick().await;
+        loop {
+            match rx.try_recv() {
+                Ok(pkt) => queue.push_back(pkt),
+                Err(broadcast::error::TryRecvError::Lagged(n)) => debug!(lost = n, "voice sender lagged"),
+                Err(broadcast::error::TryRecvError::Empty) => break,
+                Err(broadcast::error::TryRecvError::Closed) => return,
+            }
+        }
+        while queue.len() > MAX_QUEUED_PACKETS {
+            queue.pop_front();
+        }
+
+        let payload = match queue.pop_front() {
+            Some(p) => {
+                silence_left = SILENCE_FRAMES;
+                p
+            }
+            None if silence_left > 0 => {
+                silence_left -= 1;
+                Bytes::from_static(&OPUS_SILENCE)
+            }
+            None => continue,
+        };
+        let header = rtp.next_header(OPUS_FRAME_SAMPLES, false);
+        match cipher.seal(&header, &payload) {
+            Ok(pkt) => {
+                if let Err(e) = socket.send(&pkt).await {
+                    warn!(?e, "voice udp send failed");
+                }
+            }
+            Err(e) => {
+                warn!(?e, "voice packet dropped");
+            }
+        }
+    }
+}